@@ -0,0 +1,64 @@
+use clap::{arg, value_parser, Command};
+use tokiocli::{Action, ClapAdapter, Cli};
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let command = Command::new("clap")
+        .subcommand(
+            Command::new("greet")
+                .about("Greet someone")
+                .arg(arg!(<name> "Name of the person to greet"))
+                .arg(arg!(-l --loud "Greet loudly"))
+                .arg(
+                    arg!(--lang <lang> "Language of the greeting")
+                        .value_parser(["en", "fr"])
+                        .default_value("en"),
+                ),
+        )
+        .subcommand(
+            Command::new("repeat")
+                .about("Repeat a word")
+                .arg(arg!(<word> "Word to repeat"))
+                .arg(arg!(-n --count <count> "Repetitions").value_parser(value_parser!(u32))),
+        )
+        .subcommand(Command::new("exit").about("Exit"));
+
+    let adapter = ClapAdapter::new(command);
+    let mut cli = Cli::new()?;
+    cli.setprompt("clap> ").setcompleter(adapter.clone());
+    let printer = cli.printer();
+
+    loop {
+        let cmd = match cli.getaction().await? {
+            Action::Command { args: cmd, .. } => cmd,
+            Action::AutoComplete(_) | Action::Key(_) | Action::Timeout => continue,
+            Action::NoAction => break,
+        };
+        let matches = match adapter.parse(&cmd, &printer) {
+            Some(matches) => matches,
+            None => continue,
+        };
+        match matches.subcommand() {
+            Some(("greet", args)) => {
+                let name = args.get_one::<String>("name").unwrap();
+                let greeting = match args.get_one::<String>("lang").unwrap().as_str() {
+                    "fr" => format!("Bonjour {}", name),
+                    _ => format!("Hello {}", name),
+                };
+                match args.get_flag("loud") {
+                    true => println!("{} !", greeting.to_uppercase()),
+                    false => println!("{}", greeting),
+                }
+            }
+            Some(("repeat", args)) => {
+                let word = args.get_one::<String>("word").unwrap();
+                let count = args.get_one::<u32>("count").copied().unwrap_or(2);
+                println!("{}", vec![word.as_str(); count as usize].join(" "));
+            }
+            Some(("exit", _)) => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
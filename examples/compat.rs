@@ -0,0 +1,20 @@
+use tokiocli::{Editor, Error};
+
+/** A synchronous loop, as written with a readline-like library */
+fn main() -> eyre::Result<()> {
+    let mut editor = Editor::new()?;
+    editor.cli().addcommand("hello").addcommand("exit");
+    loop {
+        let line = match editor.readline("compat> ") {
+            Ok(line) => line,
+            Err(Error::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+        match line.trim() {
+            "exit" => break,
+            "hello" => println!("Hello World !"),
+            line => println!("Line: {:?}", line),
+        }
+    }
+    Ok(())
+}
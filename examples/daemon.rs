@@ -0,0 +1,40 @@
+#[cfg(unix)]
+use tokiocli::{Action, UnixServer};
+
+/**
+ * A daemon serving an admin console on /tmp/tokiocli-daemon.sock, to try with:
+ * socat -,raw,echo=0 UNIX-CONNECT:/tmp/tokiocli-daemon.sock
+ */
+#[cfg(unix)]
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let server = UnixServer::bind("/tmp/tokiocli-daemon.sock")?;
+    let mut sessions = 0;
+    loop {
+        let mut cli = server.accept().await?;
+        sessions += 1;
+        tokio::spawn(async move {
+            cli.setprompt(&format!("session#{}> ", sessions));
+            cli.addcommand("status").addcommand("exit");
+            let printer = cli.printer();
+            loop {
+                match cli.getaction().await? {
+                    Action::Command { args: cmd, .. } => match cmd[0].as_str() {
+                        "exit" => break,
+                        "status" => printer.println(format!("{} sessions opened", sessions)),
+                        "" => {}
+                        _ => printer.println(format!("Unknown command: {}", cmd[0])),
+                    },
+                    Action::AutoComplete(_) | Action::Key(_) | Action::Timeout => {}
+                    Action::NoAction => break,
+                }
+            }
+            eyre::Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("Unix domain sockets are not supported on this platform");
+}
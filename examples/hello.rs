@@ -1,73 +1,86 @@
-use tokiocli::{Action, Cli};
+use tokiocli::{
+    Cli, Color, FileCompleter, Highlighter, IncompletePolicy, Matcher, PromptBuilder, Repl,
+};
 
-#[tokio::main]
-async fn main() -> eyre::Result<()> {
-    let mut cli = Cli::new()?;
-    let mut exit = false;
-
-    while !exit {
-        let action = cli.getaction().await?;
-        match action {
-            Action::Command(cmd) => runcmd(cmd, &mut exit),
-            Action::AutoComplete(cmd) => autocomplete(&mut cli, cmd)?,
-            Action::NoAction => exit = true,
-        };
-    }
+const COMMANDS: [&str; 6] = ["hello", "upper", "shell", "showkey", "exit", "help"];
 
-    Ok(())
-}
+/** Color known commands in green, unknown ones in red and strings in yellow */
+struct Syntax;
 
-fn runcmd(cmd: Vec<String>, exit: &mut bool) {
-    let cmdname = cmd.get(0).unwrap();
-    match cmdname.as_str() {
-        "hello" => {
-            println!("Hello from tokiocli");
-        }
-        "upper" => upper(cmd),
-        "exit" => {
-            *exit = true;
-        }
-        "help" => help(),
-        "" => {}
-        _ => {
-            println!(
-                "Unknown command '{}'.\nType 'help' to list available commands.",
-                cmdname
-            );
+impl Highlighter for Syntax {
+    fn highlight(&self, line: &str) -> String {
+        let name = line.split(' ').next().unwrap_or_default();
+        let mut styled = match COMMANDS.contains(&name) || ["set", "unset", "echo"].contains(&name)
+        {
+            true => Color::Green.paint(name),
+            false => Color::Red.paint(name),
+        };
+        let parts = line[name.len()..].split('"').collect::<Vec<&str>>();
+        for (i, part) in parts.iter().enumerate() {
+            if i % 2 == 0 {
+                styled += part;
+                continue;
+            }
+            let closing = if i + 1 < parts.len() { "\"" } else { "" };
+            styled += &Color::Yellow.paint(&format!("\"{}{}", part, closing));
         }
+        styled
     }
 }
 
-fn autocomplete(cli: &mut Cli, cmd: Vec<String>) -> eyre::Result<()> {
-    let mut cmdlist = vec![
-        String::from("hello"),
-        String::from("upper"),
-        String::from("exit"),
-        String::from("help"),
-    ];
-
-    if cmd.len() == 1 {
-        cmdlist.retain(|x| x.starts_with(&cmd[0]));
-        // autocomplete command
-        cli.autocomplete(&cmdlist)?;
-    } else if cmd.len() > 1 {
-        // autocomplete arguments
-    }
-    Ok(())
-}
-
-fn upper(cmd: Vec<String>) {
-    for arg in cmd.iter().skip(1) {
-        print!("{} ", arg.to_uppercase());
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let mut cli = Cli::new()?;
+    let prompt = PromptBuilder::new()
+        .color("hello", Color::Green)
+        .text("> ")
+        .build();
+    cli.setprompt(&prompt)
+        .setcomments(true)
+        .setvariables(true)
+        .setsoftwrap(true)
+        .setcursorsync(true)
+        .setcompletionmenu(true)
+        .setcompletionsort(true)
+        .setmenuthreshold(5)
+        .setmatcher(Matcher::Fuzzy)
+        .setincompletepolicy(IncompletePolicy::Continue)
+        .sethistoryindicator(true)
+        .sethighlighter(Syntax);
+    if let Some(script) = std::env::args().nth(1) {
+        cli.run_script(script).await?;
     }
-    println!("");
-}
 
-fn help() {
-    println!("Simple interactive cli example.");
-    println!("Available commands:");
-    println!("  hello: Print hello world");
-    println!("  upper: Print arguments to upper case");
-    println!("  exit: Exit this application");
-    println!("  help: Display this help");
+    let mut repl = Repl::new(cli);
+    // Command names are completed by the Repl: complete arguments with filenames
+    repl.setcompleter(FileCompleter::new());
+    repl.addcommand("hello", "Print hello world", |_, _| {
+        Box::pin(async move {
+            println!("Hello from tokiocli");
+            Ok(())
+        })
+    });
+    repl.addcommand("upper", "Print arguments to upper case", |_, cmd| {
+        Box::pin(async move {
+            let args = cmd[1..].iter().map(|arg| arg.to_uppercase());
+            println!("{}", args.collect::<Vec<String>>().join(" "));
+            Ok(())
+        })
+    });
+    repl.addcommand("shell", "Run an interactive shell", |cli, _| {
+        Box::pin(async move {
+            // Hand the terminal over to an interactive shell
+            let _suspend = cli.suspend()?;
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".into());
+            std::process::Command::new(shell).status()?;
+            Ok(())
+        })
+    });
+    repl.addcommand(
+        "showkey",
+        "Display the sequences of the keys pressed",
+        |cli, _| Box::pin(async move { Ok(cli.debugkeys().await?) }),
+    );
+    repl.run().await?;
+    Ok(())
 }
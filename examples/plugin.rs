@@ -0,0 +1,41 @@
+use tokiocli::{Action, Cli, EditAction, LineBuffer, Plugin};
+
+/** Insert the closing double quote when an opening double quote is typed */
+struct AutoPair {}
+
+impl Plugin for AutoPair {
+    fn on_key(&mut self, line: &mut LineBuffer, action: &EditAction) -> bool {
+        if *action != EditAction::Insert('"') {
+            return false;
+        }
+        let cursor = line.cursor();
+        if line.text()[cursor..].starts_with('"') {
+            // Skip the closing double quote
+            line.set_cursor(cursor + 1);
+        } else {
+            line.insert("\"\"");
+            line.set_cursor(cursor + 1);
+        }
+        true
+    }
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let mut cli = Cli::new()?;
+    cli.addplugin(AutoPair {});
+
+    loop {
+        match cli.getaction().await? {
+            Action::Command { args: cmd, .. } => match cmd[0].as_str() {
+                "exit" => break,
+                "" => {}
+                _ => println!("{:?}", cmd),
+            },
+            Action::AutoComplete(_) | Action::Key(_) | Action::Timeout => {}
+            Action::NoAction => break,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+use tokiocli::{Action, Cli, EditAction};
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let mut cli = Cli::new()?;
+    cli.setprompt("printer> ");
+    // Alt-z: hold the messages while typing a command
+    cli.bindsequence(b"\x1Bz", EditAction::ToggleDoNotDisturb);
+
+    // Log a message every second, while the user is typing
+    let printer = cli.printer();
+    tokio::spawn(async move {
+        for i in 1.. {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            printer.println(format!("tick #{}", i));
+        }
+    });
+
+    // Output of the last command, recalled with 'last'
+    let printer = cli.printer();
+    let mut last = String::new();
+    loop {
+        match cli.getaction().await? {
+            Action::Command { args: cmd, .. } => match cmd[0].as_str() {
+                "exit" => break,
+                "last" => print!("{}", last),
+                "timer" => {
+                    let secs = cmd.get(1).and_then(|secs| secs.parse().ok()).unwrap_or(10);
+                    cli.addcountdown("timer", Duration::from_secs(secs), || {
+                        Some(Action::Command {
+                            line: String::from("expired"),
+                            args: vec![String::from("expired")],
+                        })
+                    });
+                }
+                "expired" => println!("Timer expired !"),
+                "" => {}
+                _ => {
+                    let capture = printer.capture(true);
+                    printer.println(format!("{:?}", cmd));
+                    last = capture.finish();
+                }
+            },
+            Action::AutoComplete(_) | Action::Key(_) | Action::Timeout => {}
+            Action::NoAction => break,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use std::time::Duration;
+use tokiocli::Cli;
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let mut cli = Cli::new()?;
+    cli.setprompt("run> ").setqueuelimit(3);
+    cli.addcommand("sleep").addcommand("exit");
+
+    // Commands typed while 'sleep' is running are queued
+    let printer = cli.printer();
+    cli.run(|cmd| {
+        let printer = printer.clone();
+        async move {
+            match cmd[0].as_str() {
+                "exit" => return Ok(false),
+                "sleep" => {
+                    let secs = cmd.get(1).and_then(|secs| secs.parse().ok()).unwrap_or(3);
+                    tokio::time::sleep(Duration::from_secs(secs)).await;
+                    printer.println(format!("slept {}s", secs));
+                }
+                _ => printer.println(format!("{:?}", cmd)),
+            }
+            Ok(true)
+        }
+    })
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use tokio::net::TcpListener;
+use tokiocli::{Action, Cli, Terminal};
+
+/** A raw TCP connection: no line discipline to configure */
+struct Remote;
+
+impl Terminal for Remote {}
+
+/**
+ * Serve a CLI on 127.0.0.1:2323, to try with:
+ * socat -,raw,echo=0 tcp:127.0.0.1:2323
+ */
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:2323").await?;
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            let (reader, writer) = stream.into_split();
+            let mut cli = Cli::with_backend(reader, writer, Remote)?;
+            cli.setprompt(&format!("{}> ", peer));
+            cli.addcommand("hello").addcommand("exit");
+            let printer = cli.printer();
+            loop {
+                match cli.getaction().await? {
+                    Action::Command { args: cmd, .. } => match cmd[0].as_str() {
+                        "exit" => break,
+                        "hello" => printer.println("Hello World !"),
+                        "" => {}
+                        _ => printer.println(format!("Unknown command: {}", cmd[0])),
+                    },
+                    Action::AutoComplete(_) | Action::Key(_) | Action::Timeout => {}
+                    Action::NoAction => break,
+                }
+            }
+            eyre::Ok(())
+        });
+    }
+}
@@ -0,0 +1,52 @@
+use tokiocli::{Action, ArgHint, Cli, CommandSpec};
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let interfaces = vec![String::from("eth0"), String::from("eth1")];
+    let spec = CommandSpec::root()
+        .subcommand(
+            CommandSpec::new("interface")
+                .about("Manage network interfaces")
+                .subcommand(
+                    CommandSpec::new("show")
+                        .about("Show the interface status")
+                        .flag("--verbose")
+                        .arg(ArgHint::Values(interfaces.clone())),
+                )
+                .subcommand(
+                    CommandSpec::new("set")
+                        .about("Configure the interface")
+                        .arg(ArgHint::Values(interfaces))
+                        .option(
+                            "--mtu",
+                            ArgHint::Values(vec![String::from("1500"), String::from("9000")]),
+                        ),
+                ),
+        )
+        .subcommand(
+            CommandSpec::new("load")
+                .about("Load a configuration file")
+                .arg(ArgHint::Files),
+        )
+        .subcommand(CommandSpec::new("exit").about("Exit the application"));
+
+    let mut cli = Cli::builder()
+        .completionmenu(true)
+        .completer(spec)
+        .build()
+        .await?;
+
+    loop {
+        match cli.getaction().await? {
+            Action::Command { args: cmd, .. } => match cmd[0].as_str() {
+                "exit" => break,
+                "" => {}
+                _ => println!("{:?}", cmd),
+            },
+            Action::AutoComplete(_) | Action::Key(_) | Action::Timeout => {}
+            Action::NoAction => break,
+        }
+    }
+
+    Ok(())
+}
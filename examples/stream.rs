@@ -0,0 +1,32 @@
+use std::time::Duration;
+use tokio_stream::StreamExt;
+use tokiocli::{Action, Cli};
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let mut cli = Cli::new()?;
+    cli.setprompt("stream> ");
+    let printer = cli.printer();
+    let mut actions = cli.into_stream();
+    let period = Duration::from_secs(5);
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+
+    loop {
+        tokio::select! {
+            action = actions.next() => match action.transpose()? {
+                Some(Action::Command { args: cmd, .. }) if cmd[0] == "exit" => break,
+                Some(Action::Command { args: cmd, .. }) => println!("{:?}", cmd),
+                Some(Action::AutoComplete(_)) => {
+                    if let Some(cli) = actions.cli() {
+                        cli.autocomplete(&[String::from("exit")])?;
+                    }
+                }
+                Some(Action::Key(_) | Action::Timeout) => {}
+                Some(Action::NoAction) | None => break,
+            },
+            _ = interval.tick() => printer.println("5 seconds elapsed"),
+        }
+    }
+
+    Ok(())
+}
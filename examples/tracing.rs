@@ -0,0 +1,31 @@
+use std::time::Duration;
+use tokiocli::{Action, Cli};
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let mut cli = Cli::new()?;
+    cli.setprompt("tracing> ");
+
+    // Logs are printed without messing up the command line being typed
+    tracing_subscriber::fmt().with_writer(cli.printer()).init();
+    tokio::spawn(async move {
+        for i in 1.. {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+            tracing::info!("background job #{} done", i);
+        }
+    });
+
+    loop {
+        match cli.getaction().await? {
+            Action::Command { args: cmd, .. } => match cmd[0].as_str() {
+                "exit" => break,
+                "" => {}
+                _ => tracing::warn!("unknown command: {}", cmd[0]),
+            },
+            Action::AutoComplete(_) | Action::Key(_) | Action::Timeout => {}
+            Action::NoAction => break,
+        }
+    }
+
+    Ok(())
+}
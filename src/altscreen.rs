@@ -0,0 +1,105 @@
+//! Full-screen views displayed on the alternate screen of the terminal.
+use crate::{printer, Cli, EscSeq, KeyEvent, Result};
+use std::fmt::Display;
+
+/**
+ * Guard returned by [`Cli::enter_alt_screen`].
+ *
+ * The terminal displays its alternate screen while the guard is alive, so the application
+ * can draw a full-screen view (log viewer, table browser, ...) with escape sequences.
+ * Dropping the guard (or calling [`AltScreen::leave`]) switches back to the main screen,
+ * with its content intact, shows the cursor and writes back the command line if it was
+ * displayed.
+ *
+ * ```no_run
+ * # async fn example(cli: &mut tokiocli::Cli) -> eyre::Result<()> {
+ * let mut screen = cli.enter_alt_screen();
+ * screen.hide_cursor();
+ * screen.write("Press q to quit\r\n");
+ * while screen.readkey().await? != tokiocli::KeyEvent::Char('q') {}
+ * screen.leave();
+ * # Ok(())
+ * # }
+ * ```
+ */
+pub struct AltScreen<'a> {
+    cli: &'a mut Cli,
+    active: bool,
+}
+
+impl<'a> AltScreen<'a> {
+    pub(crate) fn new(cli: &'a mut Cli) -> Self {
+        let active = {
+            let mut screen = printer::lock(&cli.screen);
+            let active = screen.active;
+            if active {
+                out!(screen.output, "{}", screen.erase);
+            }
+            screen.active = false;
+            active
+        };
+        out!(
+            cli.output,
+            "{}{}{}",
+            EscSeq::AltScreenOn,
+            EscSeq::EraseInDisplay(2),
+            EscSeq::CursorHome
+        );
+        Self { cli, active }
+    }
+
+    /** Write text on the alternate screen, as is: newlines are not translated */
+    pub fn write(&self, text: impl Display) {
+        out!(self.cli.output, "{}", text);
+    }
+
+    /** Erase the alternate screen, and move the cursor to its top left corner */
+    pub fn clear(&self) {
+        out!(
+            self.cli.output,
+            "{}{}",
+            EscSeq::EraseInDisplay(2),
+            EscSeq::CursorHome
+        );
+    }
+
+    /** Hide the cursor, until it is shown again or the alternate screen is left */
+    pub fn hide_cursor(&self) {
+        self.cli.hide_cursor();
+    }
+
+    /** Show the cursor */
+    pub fn show_cursor(&self) {
+        self.cli.show_cursor();
+    }
+
+    /** Return the size of the terminal (Columns, Rows) */
+    pub fn size(&self) -> (usize, usize) {
+        self.cli.terminal.size()
+    }
+
+    /** Wait for the next key pressed by the user */
+    pub async fn readkey(&mut self) -> Result<KeyEvent> {
+        self.cli.dialog_key().await
+    }
+
+    /** Switch back to the main screen, as when the guard is dropped */
+    pub fn leave(self) {}
+}
+
+impl Drop for AltScreen<'_> {
+    fn drop(&mut self) {
+        out!(
+            self.cli.output,
+            "{}{}",
+            EscSeq::AltScreenOff,
+            EscSeq::ShowCursor
+        );
+        if self.active {
+            let mut screen = printer::lock(&self.cli.screen);
+            self.cli.wrap_rows.set((0, 0));
+            let _ = self.cli.refresh_line();
+            self.cli.snapshot(&mut screen, true);
+        }
+    }
+}
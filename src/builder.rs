@@ -0,0 +1,360 @@
+//! Builder-style construction of the Cli.
+use crate::Result;
+use crate::{
+    Bell, Cli, ColorSupport, Completer, EditAction, Highlighter, Hinter, HistorySave, HistoryScope,
+    IncompletePolicy, Matcher, MetaKey, OutputTarget, Plugin, Prompt, TabKey, Theme, Tokenizer,
+    Validator,
+};
+use std::path::PathBuf;
+
+/** Configuration step applied to the Cli once created */
+type Step = Box<dyn FnOnce(&mut Cli) -> &mut Cli + Send>;
+
+/**
+ * Build a [`Cli`] with its options.
+ *
+ * ```no_run
+ * # async fn example() -> eyre::Result<()> {
+ * let cli = tokiocli::CliBuilder::new()
+ *     .prompt("demo> ")
+ *     .historyfile("/tmp/demo_history")
+ *     .completionmenu(true)
+ *     .build()
+ *     .await?;
+ * # Ok(())
+ * # }
+ * ```
+ */
+#[derive(Default)]
+pub struct CliBuilder {
+    steps: Vec<Step>,
+    history_file: Option<PathBuf>,
+    record_file: Option<PathBuf>,
+    cast_file: Option<PathBuf>,
+    inputrc: bool,
+}
+
+impl CliBuilder {
+    /** Create a builder with the default options */
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with(mut self, step: impl FnOnce(&mut Cli) -> &mut Cli + Send + 'static) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /**
+     * Load the history from a file when building the Cli, if the file exists.
+     *
+     * The history is saved by the application with [`Cli::save_history`].
+     */
+    pub fn historyfile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history_file = Some(path.into());
+        self
+    }
+
+    /**
+     * Apply settings loaded from a TOML file, see [`crate::CliConfig`]. The options set
+     * afterwards take precedence.
+     */
+    #[cfg(feature = "config")]
+    pub fn with_config(mut self, config: crate::CliConfig) -> Self {
+        if let Some(path) = &config.history.file {
+            self.history_file = Some(path.clone());
+        }
+        self.with(move |cli| config.apply(cli))
+    }
+
+    /**
+     * Load the readline configuration file of the user when building the Cli, see
+     * [`Cli::load_user_inputrc`]. The options set with the builder take precedence.
+     */
+    pub fn inputrc(mut self, enable: bool) -> Self {
+        self.inputrc = enable;
+        self
+    }
+
+    /** Record the session to a file, see [`Cli::record`] */
+    pub fn record(mut self, path: impl Into<PathBuf>) -> Self {
+        self.record_file = Some(path.into());
+        self
+    }
+
+    /** Record the session in the asciinema format, see [`Cli::record_cast`] */
+    pub fn recordcast(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cast_file = Some(path.into());
+        self
+    }
+
+    /** Set the styles of the text displayed by the Cli, see [`Cli::settheme`] */
+    pub fn theme(self, theme: Theme) -> Self {
+        self.with(move |cli| cli.settheme(theme))
+    }
+
+    /** Set the colors supported by the terminal, see [`Cli::setcolorsupport`] */
+    pub fn colorsupport(self, support: ColorSupport) -> Self {
+        self.with(move |cli| cli.setcolorsupport(support))
+    }
+
+    /** Set the prompt, see [`Cli::setprompt`] */
+    pub fn prompt(self, prompt: &str) -> Self {
+        let prompt = prompt.to_string();
+        self.with(move |cli| cli.setprompt(&prompt))
+    }
+
+    /** Set a prompt evaluated for each new command, see [`Cli::setdynamicprompt`] */
+    pub fn dynamicprompt(self, prompt: impl Prompt + Send + 'static) -> Self {
+        self.with(move |cli| cli.setdynamicprompt(prompt))
+    }
+
+    /** Set the right prompt, see [`Cli::setrightprompt`] */
+    pub fn rightprompt(self, prompt: &str) -> Self {
+        let prompt = prompt.to_string();
+        self.with(move |cli| cli.setrightprompt(&prompt))
+    }
+
+    /** Set the continuation prompt, see [`Cli::setcontinuationprompt`] */
+    pub fn continuationprompt(self, prompt: &str) -> Self {
+        let prompt = prompt.to_string();
+        self.with(move |cli| cli.setcontinuationprompt(&prompt))
+    }
+
+    /** Set the transient prompt, see [`Cli::settransientprompt`] */
+    pub fn transientprompt(self, prompt: &str) -> Self {
+        let prompt = prompt.to_string();
+        self.with(move |cli| cli.settransientprompt(&prompt))
+    }
+
+    /** Set the destination of the interactive output, see [`Cli::setoutput`] */
+    pub fn output(self, target: OutputTarget) -> Self {
+        self.with(move |cli| cli.setoutput(target))
+    }
+
+    /** Set the scope of the history navigation, see [`Cli::sethistoryscope`] */
+    pub fn historyscope(self, scope: HistoryScope) -> Self {
+        self.with(move |cli| cli.sethistoryscope(scope))
+    }
+
+    /** Enable the wraparound of the history search, see [`Cli::setsearchwraparound`] */
+    pub fn searchwraparound(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setsearchwraparound(enable))
+    }
+
+    /** Set how the history is saved, see [`Cli::sethistorysave`] */
+    pub fn historysave(self, strategy: HistorySave) -> Self {
+        self.with(move |cli| cli.sethistorysave(strategy))
+    }
+
+    /** Set the maximum number of entries of the history, see [`Cli::sethistorymax`] */
+    pub fn historymax(self, max: usize) -> Self {
+        self.with(move |cli| cli.sethistorymax(max))
+    }
+
+    /** Set the session recorded in the history, see [`Cli::sethistorysession`] */
+    pub fn historysession(self, session: &str) -> Self {
+        let session = session.to_string();
+        self.with(move |cli| cli.sethistorysession(&session))
+    }
+
+    /** Display the history index in the prompt, see [`Cli::sethistoryindicator`] */
+    pub fn historyindicator(self, enable: bool) -> Self {
+        self.with(move |cli| cli.sethistoryindicator(enable))
+    }
+
+    /** Set the completion matcher, see [`Cli::setmatcher`] */
+    pub fn matcher(self, matcher: Matcher) -> Self {
+        self.with(move |cli| cli.setmatcher(matcher))
+    }
+
+    /** Enable the completion menu, see [`Cli::setcompletionmenu`] */
+    pub fn completionmenu(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setcompletionmenu(enable))
+    }
+
+    /** Set the completion menu threshold, see [`Cli::setmenuthreshold`] */
+    pub fn menuthreshold(self, threshold: usize) -> Self {
+        self.with(move |cli| cli.setmenuthreshold(threshold))
+    }
+
+    /** Sort the completion candidates, see [`Cli::setcompletionsort`] */
+    pub fn completionsort(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setcompletionsort(enable))
+    }
+
+    /** Set the completion query threshold, see [`Cli::setcompletionquery`] */
+    pub fn completionquery(self, items: usize) -> Self {
+        self.with(move |cli| cli.setcompletionquery(items))
+    }
+
+    /** Set the bell, see [`Cli::setbell`] */
+    pub fn bell(self, bell: Bell) -> Self {
+        self.with(move |cli| cli.setbell(bell))
+    }
+
+    /** Set the completer, see [`Cli::setcompleter`] */
+    pub fn completer(self, completer: impl Completer + Send + 'static) -> Self {
+        self.with(move |cli| cli.setcompleter(completer))
+    }
+
+    /** Register a command name, see [`Cli::addcommand`] */
+    pub fn command(self, name: &str) -> Self {
+        let name = name.to_string();
+        self.with(move |cli| cli.addcommand(&name))
+    }
+
+    /** Set the validator, see [`Cli::setvalidator`] */
+    pub fn validator(self, validator: impl Validator + Send + 'static) -> Self {
+        self.with(move |cli| cli.setvalidator(validator))
+    }
+
+    /** Set the policy for incomplete commands, see [`Cli::setincompletepolicy`] */
+    pub fn incompletepolicy(self, policy: IncompletePolicy) -> Self {
+        self.with(move |cli| cli.setincompletepolicy(policy))
+    }
+
+    /** Enable the history expansion, see [`Cli::sethistoryexpansion`] */
+    pub fn historyexpansion(self, enable: bool) -> Self {
+        self.with(move |cli| cli.sethistoryexpansion(enable))
+    }
+
+    /** Define an alias, see [`Cli::setalias`] */
+    pub fn alias(self, name: &str, value: &str) -> Self {
+        let (name, value) = (name.to_string(), value.to_string());
+        self.with(move |cli| cli.setalias(&name, &value))
+    }
+
+    /** Define an abbreviation, see [`Cli::setabbreviation`] */
+    pub fn abbreviation(self, name: &str, value: &str) -> Self {
+        let (name, value) = (name.to_string(), value.to_string());
+        self.with(move |cli| cli.setabbreviation(&name, &value))
+    }
+
+    /** Enable comments, see [`Cli::setcomments`] */
+    pub fn comments(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setcomments(enable))
+    }
+
+    /** Set the tokenizer, see [`Cli::settokenizer`] */
+    pub fn tokenizer(self, tokenizer: impl Tokenizer + Send + 'static) -> Self {
+        self.with(move |cli| cli.settokenizer(tokenizer))
+    }
+
+    /** Enable session variables, see [`Cli::setvariables`] */
+    pub fn variables(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setvariables(enable))
+    }
+
+    /** Expand the environment variables, see [`Cli::setenvironment`] */
+    pub fn environment(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setenvironment(enable))
+    }
+
+    /** Display the commands executed from scripts, see [`Cli::setscriptecho`] */
+    pub fn scriptecho(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setscriptecho(enable))
+    }
+
+    /** Set the highlighter, see [`Cli::sethighlighter`] */
+    pub fn highlighter(self, highlighter: impl Highlighter + Send + 'static) -> Self {
+        self.with(move |cli| cli.sethighlighter(highlighter))
+    }
+
+    /** Set the hinter, see [`Cli::sethinter`] */
+    pub fn hinter(self, hinter: impl Hinter + Send + 'static) -> Self {
+        self.with(move |cli| cli.sethinter(hinter))
+    }
+
+    /** Add a plugin, see [`Cli::addplugin`] */
+    pub fn plugin(self, plugin: impl Plugin + Send + 'static) -> Self {
+        self.with(move |cli| cli.addplugin(plugin))
+    }
+
+    /** Enable the keypad application mode, see [`Cli::setkeypadmode`] */
+    pub fn keypadmode(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setkeypadmode(enable))
+    }
+
+    /** Restore the terminal on panics, see [`Cli::restore_on_panic`] */
+    pub fn restoreonpanic(self, enable: bool) -> Self {
+        match enable {
+            true => self.with(|cli| cli.restore_on_panic()),
+            false => self,
+        }
+    }
+
+    /** Enable the bracketed paste mode, see [`Cli::setbracketedpaste`] */
+    pub fn bracketedpaste(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setbracketedpaste(enable))
+    }
+
+    /** Set how the Meta key is reported, see [`Cli::setmetakey`] */
+    pub fn metakey(self, meta: MetaKey) -> Self {
+        self.with(move |cli| cli.setmetakey(meta))
+    }
+
+    /** Set the maximum length of the command line, see [`Cli::setmaxlinelength`] */
+    pub fn maxlinelength(self, max: usize) -> Self {
+        self.with(move |cli| cli.setmaxlinelength(max))
+    }
+
+    /** Add a pattern of sensitive command lines, see [`Cli::addsensitivepattern`] */
+    pub fn sensitivepattern(self, pattern: &str) -> Self {
+        let pattern = pattern.to_string();
+        self.with(move |cli| cli.addsensitivepattern(&pattern))
+    }
+
+    /** Set what the Tab key does, see [`Cli::settabkey`] */
+    pub fn tabkey(self, tab: TabKey) -> Self {
+        self.with(move |cli| cli.settabkey(tab))
+    }
+
+    /** Bind a sequence to an editing action, see [`Cli::bindsequence`] */
+    pub fn bindsequence(self, sequence: &[u8], action: EditAction) -> Self {
+        let sequence = sequence.to_vec();
+        self.with(move |cli| cli.bindsequence(&sequence, action))
+    }
+
+    /** Wrap long command lines, see [`Cli::setsoftwrap`] */
+    pub fn softwrap(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setsoftwrap(enable))
+    }
+
+    /** Synchronize the prompt column, see [`Cli::setcursorsync`] */
+    pub fn cursorsync(self, enable: bool) -> Self {
+        self.with(move |cli| cli.setcursorsync(enable))
+    }
+
+    /** Set the maximum number of queued commands, see [`Cli::setqueuelimit`] */
+    pub fn queuelimit(self, limit: usize) -> Self {
+        self.with(move |cli| cli.setqueuelimit(limit))
+    }
+
+    /**
+     * Create the Cli with the configured options.
+     *
+     * Note that it configures the terminal in character mode.
+     */
+    pub async fn build(self) -> Result<Cli> {
+        let mut cli = Cli::new()?;
+        if self.inputrc {
+            cli.load_user_inputrc()?;
+        }
+        if let Some(path) = self.record_file {
+            cli.record(&path)?;
+        }
+        if let Some(path) = self.cast_file {
+            cli.record_cast(&path)?;
+        }
+        for step in self.steps {
+            step(&mut cli);
+        }
+        if let Some(path) = self.history_file {
+            if path.exists() {
+                cli.load_history(&path).await?;
+            }
+        }
+        Ok(cli)
+    }
+}
@@ -0,0 +1,147 @@
+//! Parsing of the commands with clap.
+use crate::{Completer, Printer};
+use clap::error::ErrorKind;
+
+/**
+ * Parse the commands with a [`clap::Command`], whose subcommands are the commands of the
+ * Cli, and complete the subcommands, flags and values from its definition.
+ *
+ * ```no_run
+ * # async fn example() -> eyre::Result<()> {
+ * let command = clap::Command::new("demo")
+ *     .subcommand(clap::Command::new("greet").arg(clap::arg!(--loud "Greet loudly")));
+ * let adapter = tokiocli::ClapAdapter::new(command);
+ * let mut cli = tokiocli::Cli::new()?;
+ * cli.setcompleter(adapter.clone());
+ * while let tokiocli::Action::Command { args, .. } = cli.getaction().await? {
+ *     if let Some(matches) = adapter.parse(&args, &cli.printer()) {
+ *         println!("{:?}", matches.subcommand_name());
+ *     }
+ * }
+ * # Ok(())
+ * # }
+ * ```
+ */
+#[derive(Clone)]
+pub struct ClapAdapter {
+    command: clap::Command,
+}
+
+impl ClapAdapter {
+    /** Create an adapter parsing the commands with the provided definition */
+    pub fn new(command: clap::Command) -> Self {
+        let command = command.no_binary_name(true).disable_help_subcommand(true);
+        Self { command }
+    }
+
+    /**
+     * Parse a command (Command Name + Arguments).
+     *
+     * The usage or the error reported by clap is printed with the Printer, and None is
+     * returned, if the command can not be parsed or if its help was demanded.
+     */
+    pub fn parse(&self, args: &[String], printer: &Printer) -> Option<clap::ArgMatches> {
+        if args.first().is_some_and(|name| name.is_empty()) {
+            return None;
+        }
+        match self.command.clone().try_get_matches_from(args) {
+            Ok(matches) => Some(matches),
+            Err(err) => {
+                let text = err.render().to_string();
+                let text = text.trim_end_matches('\n');
+                match err.kind() {
+                    ErrorKind::DisplayHelp | ErrorKind::DisplayVersion => printer.println(text),
+                    _ => printer.eprintln(text),
+                }
+                None
+            }
+        }
+    }
+
+    /** Return the (sub)command reached by the words, and the words left */
+    fn walk<'a>(&self, words: &'a [String]) -> (&clap::Command, &'a [String]) {
+        let mut command = &self.command;
+        let mut rest = words;
+        while let Some((word, next)) = rest.split_first() {
+            match command.find_subcommand(word) {
+                Some(subcommand) => {
+                    command = subcommand;
+                    rest = next;
+                }
+                None => break,
+            }
+        }
+        (command, rest)
+    }
+}
+
+impl Completer for ClapAdapter {
+    fn completions(&self, args: &[String]) -> Vec<String> {
+        let (typed, words) = match args.split_last() {
+            Some(split) => split,
+            None => return Vec::new(),
+        };
+        let (command, rest) = self.walk(words);
+
+        // Value of an option
+        if let Some(option) = rest.last().and_then(|word| option(command, word)) {
+            if option.get_action().takes_values() {
+                let values = option.get_possible_values();
+                return values.iter().map(|value| value.get_name().into()).collect();
+            }
+        }
+
+        if typed.starts_with('-') {
+            let mut flags = Vec::new();
+            for arg in command.get_arguments() {
+                if let Some(long) = arg.get_long() {
+                    flags.push(format!("--{}", long));
+                }
+                if let Some(short) = arg.get_short() {
+                    flags.push(format!("-{}", short));
+                }
+            }
+            flags.sort();
+            return flags;
+        }
+
+        let mut words = command
+            .get_subcommands()
+            .map(|subcommand| subcommand.get_name().to_string())
+            .collect::<Vec<String>>();
+        if words.is_empty() {
+            // Possible values of the positional arguments
+            for arg in command.get_positionals() {
+                let values = arg.get_possible_values();
+                words.extend(values.iter().map(|value| value.get_name().to_string()));
+            }
+        }
+        words
+    }
+
+    fn help(&self, args: &[String]) -> Option<String> {
+        let (command, rest) = self.walk(args);
+        if !rest.is_empty() || std::ptr::eq(command, &self.command) {
+            return None;
+        }
+        let help = command.clone().render_help().to_string();
+        Some(help.trim_end_matches('\n').into())
+    }
+}
+
+/** Return the option designated by a word (--long or -s) */
+fn option<'a>(command: &'a clap::Command, word: &str) -> Option<&'a clap::Arg> {
+    if let Some(long) = word.strip_prefix("--") {
+        return command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long));
+    }
+    let short = word.strip_prefix('-')?;
+    let mut chars = short.chars();
+    let (c, None) = (chars.next()?, chars.next()) else {
+        return None;
+    };
+    command
+        .get_arguments()
+        .find(|arg| arg.get_short() == Some(c))
+}
@@ -0,0 +1,102 @@
+//! Blocking line editor, for applications migrating from a readline-like library.
+use crate::{Action, Cli, Error, Result};
+use std::path::Path;
+use tokio::runtime::{Handle, Runtime};
+
+enum Driver {
+    Owned(Runtime),
+    Handle(Handle),
+}
+
+impl Driver {
+    fn block_on<F: std::future::Future>(&self, future: F) -> F::Output {
+        match self {
+            Self::Owned(runtime) => runtime.block_on(future),
+            Self::Handle(handle) => handle.block_on(future),
+        }
+    }
+}
+
+/**
+ * A blocking line editor built on the [`Cli`], with a rustyline-like API.
+ *
+ * It lets an application keep its synchronous loop while migrating to tokiocli:
+ *
+ * ```no_run
+ * let mut editor = tokiocli::Editor::new()?;
+ * loop {
+ *     match editor.readline("> ") {
+ *         Ok(line) => println!("{}", line),
+ *         Err(tokiocli::Error::Eof) => break,
+ *         Err(err) => return Err(err.into()),
+ *     }
+ * }
+ * # eyre::Ok(())
+ * ```
+ *
+ * The editor must not be used from an async task: readline blocks the calling thread.
+ */
+pub struct Editor {
+    cli: Cli,
+    driver: Driver,
+}
+
+impl Editor {
+    /** Create an editor driven by its own single-threaded runtime */
+    pub fn new() -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Self::with_driver(Driver::Owned(runtime))
+    }
+
+    /** Create an editor driven by the runtime of the provided handle */
+    pub fn with_handle(handle: Handle) -> Result<Self> {
+        Self::with_driver(Driver::Handle(handle))
+    }
+
+    fn with_driver(driver: Driver) -> Result<Self> {
+        let cli = driver.block_on(async { Cli::new() })?;
+        Ok(Self { cli, driver })
+    }
+
+    /** Return the Cli, to configure it */
+    pub fn cli(&mut self) -> &mut Cli {
+        &mut self.cli
+    }
+
+    /**
+     * Display the prompt and return the line entered by the user, as typed.
+     *
+     * An [`Error::Eof`] is returned when EOT is received on an empty line. Completion demands
+     * are ignored: a [`crate::Completer`] should be set on the Cli instead.
+     */
+    pub fn readline(&mut self, prompt: &str) -> Result<String> {
+        let cli = &mut self.cli;
+        cli.setprompt(prompt);
+        self.driver.block_on(async {
+            loop {
+                match cli.getaction().await? {
+                    Action::Command { line, .. } => return Ok(line),
+                    Action::AutoComplete(_) | Action::Key(_) | Action::Timeout => {}
+                    Action::NoAction => return Err(Error::Eof),
+                }
+            }
+        })
+    }
+
+    /** Add an entry to the history (entered lines are already added) */
+    pub fn add_history_entry(&mut self, line: &str) {
+        self.cli.editor.add_history(line);
+    }
+
+    /** Load the history from a file, see [`Cli::load_history`] */
+    pub fn load_history(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.driver.block_on(self.cli.load_history(path))
+    }
+
+    /** Save the history to a file, see [`Cli::save_history`] */
+    pub fn save_history(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.driver.block_on(self.cli.save_history(path))
+    }
+}
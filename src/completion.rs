@@ -0,0 +1,217 @@
+//! Completion of the command line.
+use std::path::Path;
+
+/**
+ * Provide the possible words to auto-complete a command line.
+ *
+ * A Completer can be installed with [`crate::Cli::setcompleter`] to answer completion
+ * demands directly in the Cli, instead of handling [`crate::Action::AutoComplete`].
+ */
+pub trait Completer {
+    /**
+     * Return the possible words for the last argument of the command (Command Name + Arguments).
+     *
+     * As for [`crate::Cli::autocomplete`], returned words are filtered and ranked according to
+     * the [`Matcher`] of the Cli.
+     */
+    fn completions(&self, args: &[String]) -> Vec<String>;
+
+    /**
+     * Return the help of the command (Command Name + Arguments), if any.
+     *
+     * The help is displayed by the Cli, instead of submitting the command, when the command
+     * ends with `--help` or `-h`.
+     */
+    fn help(&self, _args: &[String]) -> Option<String> {
+        None
+    }
+}
+
+/** Strategy used to match the possible words against the word being completed */
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Matcher {
+    /** Words starting with the typed word. */
+    Prefix,
+    /** Words starting with the typed word, ignoring case. */
+    PrefixIgnoreCase,
+    /**
+     * Words containing all characters of the typed word in the same order, ignoring case
+     * (e.g. `gsp` matches `get-system-parameters`).
+     * Words are ranked by score, favoring consecutive characters and word boundaries.
+     */
+    Fuzzy,
+}
+
+impl Matcher {
+    /**
+     * Return the score of a word for the typed input, or None if the word does not match.
+     *
+     * A higher score denotes a better match.
+     */
+    pub fn score(&self, input: &str, word: &str) -> Option<usize> {
+        match self {
+            Self::Prefix => word.starts_with(input).then_some(0),
+            Self::PrefixIgnoreCase => word
+                .to_lowercase()
+                .starts_with(&input.to_lowercase())
+                .then_some(0),
+            Self::Fuzzy => fuzzy_score(input, word),
+        }
+    }
+
+    /** Return the words matching the typed input, from the best match to the worst. */
+    pub fn filter(&self, input: &str, words: &[String]) -> Vec<String> {
+        let mut scored = words
+            .iter()
+            .filter_map(|word| self.score(input, word).map(|score| (score, word)))
+            .collect::<Vec<_>>();
+        scored.sort_by(|(lscore, _), (rscore, _)| rscore.cmp(lscore));
+        scored.into_iter().map(|(_, word)| word.clone()).collect()
+    }
+}
+
+/**
+ * Return the edit distance between two words, ignoring case: the number of characters to
+ * insert, delete, substitute or swap with their neighbour to turn one word into the other.
+ *
+ * A lower distance denotes closer words, see [`crate::Cli::suggest`].
+ *
+ * ```
+ * assert_eq!(tokiocli::edit_distance("hlep", "help"), 1);
+ * assert_eq!(tokiocli::edit_distance("Exit", "quit"), 2);
+ * ```
+ */
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.to_lowercase().chars().collect::<Vec<char>>();
+    let b = b.to_lowercase().chars().collect::<Vec<char>>();
+    // Distances of the prefixes of a to the two previous and the current prefixes of b
+    let mut before = Vec::new();
+    let mut prev = (0..=a.len()).collect::<Vec<usize>>();
+    for j in 1..=b.len() {
+        let mut row = vec![j; a.len() + 1];
+        for i in 1..=a.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[i] = (prev[i] + 1).min(row[i - 1] + 1).min(prev[i - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[i] = row[i].min(before[i - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut prev, row);
+    }
+    prev[a.len()]
+}
+
+fn fuzzy_score(input: &str, word: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut chars = word.chars();
+    let mut prev: Option<char> = None;
+    let mut consecutive = false;
+
+    for ichar in input.chars() {
+        loop {
+            let wchar = chars.next()?;
+            let boundary = match prev {
+                Some(prev) => {
+                    matches!(prev, '-' | '_' | ' ' | '/' | '.')
+                        || (prev.is_lowercase() && wchar.is_uppercase())
+                }
+                None => true,
+            };
+            prev = Some(wchar);
+            if wchar.to_lowercase().eq(ichar.to_lowercase()) {
+                score += 1;
+                if consecutive {
+                    score += 4;
+                }
+                if boundary {
+                    score += 8;
+                }
+                consecutive = true;
+                break;
+            }
+            consecutive = false;
+        }
+    }
+    Some(score)
+}
+
+/**
+ * Complete filenames and paths relative to the current working directory.
+ *
+ * Directories are completed with a trailing `/` so the completion can go on with their
+ * content. Hidden files are only proposed when the word to complete starts with a `.`.
+ *
+ * Returned words are not escaped: [`crate::Cli::autocomplete`] takes care of escaping
+ * spaces when inserting them in the command line.
+ *
+ * FileCompleter can be used from an [`crate::Action::AutoComplete`] handler with
+ * [`FileCompleter::complete`], or installed as a [`Completer`] completing the last argument.
+ */
+#[derive(Default)]
+pub struct FileCompleter {}
+
+impl FileCompleter {
+    /** Create a new FileCompleter */
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /**
+     * Return the list of paths starting with the provided word.
+     *
+     * The list is sorted and can be passed as is to [`crate::Cli::autocomplete`].
+     */
+    pub fn complete(&self, word: &str) -> Vec<String> {
+        let (dir, prefix) = match word.rfind('/') {
+            Some(idx) => word.split_at(idx + 1),
+            None => ("", word),
+        };
+        let path = match dir {
+            "" => Path::new("."),
+            dir => Path::new(dir),
+        };
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => {
+                return Vec::new();
+            }
+        };
+
+        let mut words = Vec::<String>::new();
+        for entry in entries.flatten() {
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => {
+                    continue;
+                }
+            };
+            if !name.starts_with(prefix) {
+                continue;
+            }
+            if name.starts_with('.') && !prefix.starts_with('.') {
+                continue;
+            }
+            let mut word = format!("{}{}", dir, name);
+            if entry.path().is_dir() {
+                word.push('/');
+            }
+            words.push(word);
+        }
+        words.sort();
+        words
+    }
+}
+
+impl Completer for FileCompleter {
+    fn completions(&self, args: &[String]) -> Vec<String> {
+        match args.last() {
+            Some(word) => self.complete(word),
+            None => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Settings of the Cli loaded from a TOML file.
+use crate::{inputrc, Cli, ColorSupport, Error, Matcher, Result, Style, TabKey};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/**
+ * Settings of the Cli, loaded from a TOML file and applied with
+ * [`crate::CliBuilder::with_config`], so an application can ship CLI settings editable by
+ * its users.
+ *
+ * ```toml
+ * prompt = "demo> "
+ *
+ * [colors]
+ * support = "ansi256"
+ * prompt = "bold cyan"
+ * error = "red"
+ *
+ * [history]
+ * file = "/home/user/.demo_history"
+ * size = 1000
+ *
+ * [completion]
+ * matcher = "fuzzy"
+ * menu = true
+ *
+ * [keybindings]
+ * '\C-x\C-e' = "edit-and-execute-command"
+ * "Control-t" = "transpose-chars"
+ * ```
+ *
+ * ```no_run
+ * # async fn example() -> eyre::Result<()> {
+ * let config = tokiocli::CliConfig::load("/etc/demo/cli.toml")?;
+ * let cli = tokiocli::CliBuilder::new()
+ *     .with_config(config)
+ *     .build()
+ *     .await?;
+ * # Ok(())
+ * # }
+ * ```
+ */
+#[derive(Clone, Default, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CliConfig {
+    /** The prompt, see [`Cli::setprompt`] */
+    pub prompt: Option<String>,
+    /** The right prompt, see [`Cli::setrightprompt`] */
+    pub right_prompt: Option<String>,
+    /** The continuation prompt, see [`Cli::setcontinuationprompt`] */
+    pub continuation_prompt: Option<String>,
+    pub colors: ColorsConfig,
+    pub history: HistoryConfig,
+    pub completion: CompletionConfig,
+    /**
+     * Keys bound to readline functions (e.g. `transpose-chars`), with the syntax of the
+     * inputrc file: a sequence with escapes (e.g. `\C-x\C-e`) or a key name (e.g.
+     * `Control-t`), see [`Cli::load_inputrc`]
+     */
+    pub keybindings: BTreeMap<String, String>,
+}
+
+/**
+ * The colors of the Cli. A style is made of attributes and colors separated by spaces, e.g.
+ * `bold yellow on #202020` (see [`Style`]).
+ */
+#[derive(Clone, Default, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorsConfig {
+    /**
+     * The colors supported by the terminal: `none`, `standard`, `ansi256` or `true-color`,
+     * detected from the environment by default
+     */
+    pub support: Option<ColorSupport>,
+    pub prompt: Option<Style>,
+    pub hint: Option<Style>,
+    pub selection: Option<Style>,
+    pub error: Option<Style>,
+    pub control: Option<Style>,
+}
+
+/** The history of the Cli */
+#[derive(Clone, Default, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /** The file the history is loaded from, see [`crate::CliBuilder::historyfile`] */
+    pub file: Option<PathBuf>,
+    /** The maximum number of entries, see [`Cli::sethistorymax`] */
+    pub size: Option<usize>,
+}
+
+/** The completion of the Cli */
+#[derive(Clone, Default, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CompletionConfig {
+    /** `prefix`, `prefix-ignore-case` or `fuzzy`, see [`Cli::setmatcher`] */
+    pub matcher: Option<Matcher>,
+    /** See [`Cli::setcompletionmenu`] */
+    pub menu: Option<bool>,
+    /** See [`Cli::setmenuthreshold`] */
+    pub menu_threshold: Option<usize>,
+    /** See [`Cli::setcompletionsort`] */
+    pub sort: Option<bool>,
+    /** See [`Cli::setcompletionquery`] */
+    pub query_items: Option<usize>,
+    /** `complete`, `literal` or `{ spaces = N }`, see [`Cli::settabkey`] */
+    pub tab: Option<TabKey>,
+}
+
+impl CliConfig {
+    /** Parse the settings from a TOML text */
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text).map_err(|err| Error::Syntax(err.to_string()))?;
+        config.check_bindings()?;
+        Ok(config)
+    }
+
+    /** Load the settings from a TOML file */
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Self::from_toml(&text).map_err(|err| Error::Syntax(format!("{}: {}", path.display(), err)))
+    }
+
+    /** Check that the keys and the functions of the key bindings are valid */
+    fn check_bindings(&self) -> Result<()> {
+        for (key, function) in &self.keybindings {
+            if inputrc::key(key).is_none() {
+                return Err(Error::Syntax(format!("invalid key '{}'", key)));
+            }
+            if inputrc::action(function).is_none() {
+                return Err(Error::Syntax(format!("unknown function '{}'", function)));
+            }
+        }
+        Ok(())
+    }
+
+    /** Apply the settings to the Cli, except the history file loaded by the builder */
+    pub(crate) fn apply(self, cli: &mut Cli) -> &mut Cli {
+        if let Some(prompt) = &self.prompt {
+            cli.setprompt(prompt);
+        }
+        if let Some(prompt) = &self.right_prompt {
+            cli.setrightprompt(prompt);
+        }
+        if let Some(prompt) = &self.continuation_prompt {
+            cli.setcontinuationprompt(prompt);
+        }
+
+        let colors = self.colors;
+        if let Some(support) = colors.support {
+            cli.setcolorsupport(support);
+        }
+        let mut theme = cli.theme;
+        theme.prompt = colors.prompt.unwrap_or(theme.prompt);
+        theme.hint = colors.hint.unwrap_or(theme.hint);
+        theme.selection = colors.selection.unwrap_or(theme.selection);
+        theme.error = colors.error.unwrap_or(theme.error);
+        theme.control = colors.control.unwrap_or(theme.control);
+        cli.settheme(theme);
+
+        if let Some(max) = self.history.size {
+            cli.sethistorymax(max);
+        }
+
+        let completion = self.completion;
+        if let Some(matcher) = completion.matcher {
+            cli.setmatcher(matcher);
+        }
+        if let Some(enable) = completion.menu {
+            cli.setcompletionmenu(enable);
+        }
+        if let Some(threshold) = completion.menu_threshold {
+            cli.setmenuthreshold(threshold);
+        }
+        if let Some(enable) = completion.sort {
+            cli.setcompletionsort(enable);
+        }
+        if let Some(items) = completion.query_items {
+            cli.setcompletionquery(items);
+        }
+        if let Some(tab) = completion.tab {
+            cli.settabkey(tab);
+        }
+
+        // The invalid bindings were reported when the settings were loaded
+        for (key, function) in &self.keybindings {
+            if let (Some(sequence), Some(action)) = (inputrc::key(key), inputrc::action(function)) {
+                cli.bindsequence(&sequence, action);
+            }
+        }
+        cli
+    }
+}
+
+impl<'de> Deserialize<'de> for Style {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
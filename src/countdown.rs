@@ -0,0 +1,32 @@
+//! Countdowns displayed below the command line.
+use crate::Action;
+use tokio::time::{Duration, Instant};
+
+/** Function called when a countdown reaches zero, optionally returning an Action to report */
+pub(crate) type OnExpire = Box<dyn FnOnce() -> Option<Action> + Send>;
+
+/** A countdown displayed in the status row */
+pub(crate) struct Countdown {
+    pub label: String,
+    pub deadline: Instant,
+    pub on_expire: OnExpire,
+}
+
+impl Countdown {
+    /** Return the countdown as displayed, with the remaining time rounded up to the second */
+    pub fn display(&self, now: Instant) -> String {
+        let remaining = self.deadline.saturating_duration_since(now);
+        let mut secs = remaining.as_secs();
+        if remaining.subsec_nanos() > 0 {
+            secs += 1;
+        }
+        let (hours, mins, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+        match hours {
+            0 => format!("{} {:02}:{:02}", self.label, mins, secs),
+            _ => format!("{} {}:{:02}:{:02}", self.label, hours, mins, secs),
+        }
+    }
+}
+
+/** Period of the refresh of the countdowns */
+pub(crate) const TICK: Duration = Duration::from_secs(1);
@@ -0,0 +1,295 @@
+//! Interactive dialogs: confirmation, choice of one or several items in a list, password.
+use crate::keys::{Decoded, EditAction, KeyEvent};
+use crate::sensitive::wipe;
+use crate::Result;
+use crate::{Cli, EscSeq, ESCAPE_TIMEOUT, MENU_ROWS};
+
+/** Maximum length of a password, in bytes */
+const PASSWORD_CAPACITY: usize = 1024;
+
+/** State of a list displayed by a dialog */
+struct List<'a> {
+    items: Vec<&'a str>,
+    /** Index of the highlighted item */
+    selected: usize,
+    /** Checked items, for a multiple choice */
+    checked: Option<Vec<bool>>,
+}
+
+impl List<'_> {
+    fn up(&mut self) {
+        self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+    }
+
+    fn down(&mut self) {
+        self.selected = (self.selected + 1) % self.items.len();
+    }
+
+    fn toggle(&mut self) {
+        if let Some(checked) = &mut self.checked {
+            checked[self.selected] = !checked[self.selected];
+        }
+    }
+
+    fn toggle_all(&mut self) {
+        if let Some(checked) = &mut self.checked {
+            let all = checked.iter().all(|checked| *checked);
+            checked.iter_mut().for_each(|checked| *checked = !all);
+        }
+    }
+}
+
+impl Cli {
+    /**
+     * Display a list of items below the prompt, and let the user choose one of them.
+     *
+     * The highlighted item is moved with the Up and Down arrows, Enter confirms the choice
+     * and Escape (or Ctrl+C) cancels it. The list is erased afterwards, and the chosen item
+     * is displayed after the prompt. Return the index of the chosen item, or None if the
+     * choice was cancelled.
+     *
+     * ```no_run
+     * # async fn example(cli: &mut tokiocli::Cli) -> eyre::Result<()> {
+     * let interfaces = ["eth0", "eth1", "wlan0"];
+     * if let Some(idx) = cli.select("Which interface ?", &interfaces).await? {
+     *     println!("{} selected", interfaces[idx]);
+     * }
+     * # Ok(())
+     * # }
+     * ```
+     */
+    pub async fn select(
+        &mut self,
+        prompt: &str,
+        items: &[impl AsRef<str>],
+    ) -> Result<Option<usize>> {
+        let chosen = self.dialog(prompt, items, false).await?;
+        Ok(chosen.map(|chosen| chosen[0]))
+    }
+
+    /**
+     * Display a list of items below the prompt, and let the user check several of them.
+     *
+     * Space toggles the highlighted item, `a` toggles all the items, Enter confirms the
+     * choice and Escape (or Ctrl+C) cancels it. Return the indices of the checked items, or
+     * None if the choice was cancelled.
+     */
+    pub async fn multi_select(
+        &mut self,
+        prompt: &str,
+        items: &[impl AsRef<str>],
+    ) -> Result<Option<Vec<usize>>> {
+        self.dialog(prompt, items, true).await
+    }
+
+    /**
+     * Ask the user a yes/no question, and return the answer.
+     *
+     * The answer is given with `y` or `n`, and Enter answers the default. Any other key asks
+     * the question again, and Escape (or Ctrl+C) answers no.
+     *
+     * ```no_run
+     * # async fn example(cli: &mut tokiocli::Cli) -> eyre::Result<()> {
+     * if cli.confirm("Delete 14 files ?", false).await? {
+     *     println!("Deleting");
+     * }
+     * # Ok(())
+     * # }
+     * ```
+     */
+    pub async fn confirm(&mut self, prompt: &str, default: bool) -> Result<bool> {
+        self.enable_raw_mode()?;
+        let choices = if default { "[Y/n]" } else { "[y/N]" };
+        out!(
+            self.output,
+            "{}{} {} ",
+            EscSeq::EraseInLineAll,
+            prompt,
+            choices
+        );
+        let answer = loop {
+            match self.dialog_key().await? {
+                KeyEvent::Char('y' | 'Y') => break true,
+                KeyEvent::Char('n' | 'N') => break false,
+                KeyEvent::Enter => break default,
+                KeyEvent::Escape | KeyEvent::Ctrl('c') | KeyEvent::Ctrl('d') => break false,
+                _ => {
+                    out!(
+                        self.output,
+                        "\nPlease answer y or n.\n{} {} ",
+                        prompt,
+                        choices
+                    );
+                }
+            }
+        };
+        out!(self.output, "{}\n", if answer { "y" } else { "n" });
+        Ok(answer)
+    }
+
+    /**
+     * Read a password after the prompt, without echoing it.
+     *
+     * Backspace deletes the last character, Ctrl+U deletes all of them, Enter confirms the
+     * password and Escape (or Ctrl+C) cancels it. The password is kept out of the history
+     * and of the session recording, and the text pasted or cancelled is zeroized with the
+     * `zeroize` feature. Return None if the password was cancelled.
+     *
+     * ```no_run
+     * # async fn example(cli: &mut tokiocli::Cli) -> eyre::Result<()> {
+     * if let Some(password) = cli.read_password("Password: ").await? {
+     *     println!("{} characters", password.chars().count());
+     * }
+     * # Ok(())
+     * # }
+     * ```
+     */
+    pub async fn read_password(&mut self, prompt: &str) -> Result<Option<String>> {
+        self.enable_raw_mode()?;
+        out!(self.output, "{}{}", EscSeq::EraseInLineAll, prompt);
+        self.output.hold_record();
+        // Allocated once: no copy of the password is left in memory by a reallocation
+        let mut password = String::with_capacity(PASSWORD_CAPACITY);
+        let confirmed = loop {
+            let mut text = match self.dialog_input().await? {
+                Decoded::Key(KeyEvent::Char(c)) | Decoded::Action(EditAction::Insert(c)) => {
+                    c.to_string()
+                }
+                Decoded::Paste(text) => text,
+                Decoded::Key(KeyEvent::Enter) => break true,
+                Decoded::Key(KeyEvent::Escape | KeyEvent::Ctrl('c')) => break false,
+                Decoded::Key(KeyEvent::Ctrl('d')) if password.is_empty() => break false,
+                Decoded::Key(KeyEvent::Backspace | KeyEvent::Ctrl('h')) => {
+                    password.pop();
+                    continue;
+                }
+                Decoded::Key(KeyEvent::Ctrl('u')) => {
+                    password.clear();
+                    continue;
+                }
+                _ => continue,
+            };
+            for c in text.chars().filter(|c| !c.is_control()) {
+                if password.len() + c.len_utf8() > PASSWORD_CAPACITY {
+                    self.bell();
+                    break;
+                }
+                password.push(c);
+            }
+            wipe(&mut text);
+        };
+        self.output.release_record(false);
+        out!(self.output, "\n");
+        if !confirmed {
+            wipe(&mut password);
+            return Ok(None);
+        }
+        Ok(Some(password))
+    }
+
+    /** Run a dialog and return the chosen items */
+    async fn dialog(
+        &mut self,
+        prompt: &str,
+        items: &[impl AsRef<str>],
+        multiple: bool,
+    ) -> Result<Option<Vec<usize>>> {
+        if items.is_empty() {
+            return Ok(None);
+        }
+        self.enable_raw_mode()?;
+        let mut list = List {
+            items: items.iter().map(AsRef::as_ref).collect(),
+            selected: 0,
+            checked: multiple.then(|| vec![false; items.len()]),
+        };
+        out!(self.output, "{}{}", EscSeq::EraseInLineAll, prompt);
+        let chosen = loop {
+            self.dialog_render(prompt, &list);
+            match self.dialog_key().await? {
+                KeyEvent::Up | KeyEvent::Ctrl('p') => list.up(),
+                KeyEvent::Down | KeyEvent::Ctrl('n') | KeyEvent::Tab => list.down(),
+                KeyEvent::Home => list.selected = 0,
+                KeyEvent::End => list.selected = list.items.len() - 1,
+                KeyEvent::Char(' ') => list.toggle(),
+                KeyEvent::Char('a') => list.toggle_all(),
+                KeyEvent::Enter => {
+                    let chosen = match &list.checked {
+                        Some(checked) => (0..checked.len()).filter(|i| checked[*i]).collect(),
+                        None => vec![list.selected],
+                    };
+                    break Some(chosen);
+                }
+                KeyEvent::Escape | KeyEvent::Ctrl('c') | KeyEvent::Ctrl('d') => break None,
+                _ => {}
+            }
+        };
+
+        // Erase the list and display the choice after the prompt
+        out!(self.output, "{}", EscSeq::EraseInDisplay(0));
+        if let Some(chosen) = &chosen {
+            let chosen = chosen.iter().map(|i| list.items[*i]).collect::<Vec<&str>>();
+            out!(self.output, " {}", chosen.join(", "));
+        }
+        out!(self.output, "\n");
+        Ok(chosen)
+    }
+
+    /** Render the list below the prompt, and move back the cursor after the prompt */
+    fn dialog_render(&self, prompt: &str, list: &List) {
+        let _batch = self.output.batch();
+        let first = match list.selected {
+            selected if selected >= MENU_ROWS => selected + 1 - MENU_ROWS,
+            _ => 0,
+        };
+        let rows = std::cmp::min(list.items.len(), MENU_ROWS);
+        let width = self.terminal.size().0.saturating_sub(6);
+        for (i, item) in list.items.iter().enumerate().skip(first).take(rows) {
+            let item = item.chars().take(width).collect::<String>();
+            let mark = match &list.checked {
+                Some(checked) if checked[i] => "[x] ",
+                Some(_) => "[ ] ",
+                None => "",
+            };
+            out!(self.output, "\n{}", EscSeq::EraseInLineAll);
+            if i == list.selected {
+                let item = self.styled(self.theme.selection, &item);
+                out!(self.output, "> {}{}", mark, item);
+            } else {
+                out!(self.output, "  {}{}", mark, item);
+            }
+        }
+        out!(
+            self.output,
+            "{}{}",
+            EscSeq::Up(rows),
+            EscSeq::HorizontalAbs(prompt.chars().count() + 1)
+        );
+    }
+
+    /** Read the next key pressed by the user */
+    pub(crate) async fn dialog_key(&mut self) -> Result<KeyEvent> {
+        loop {
+            if let Decoded::Key(key) = self.dialog_input().await? {
+                return Ok(key);
+            }
+        }
+    }
+
+    /** Read the next key, pasted text or bound sequence, ignoring cursor position reports */
+    async fn dialog_input(&mut self) -> Result<Decoded> {
+        loop {
+            match self.typeahead.pop_front() {
+                Some(Decoded::CursorPosition(_, _)) => {}
+                Some(decoded) => return Ok(decoded),
+                None if self.decoder.escape_pending() => {
+                    match tokio::time::timeout(ESCAPE_TIMEOUT, self.read_input()).await {
+                        Ok(input) => input?,
+                        Err(_) => return Ok(Decoded::Key(self.decoder.escape())),
+                    }
+                }
+                None => self.read_input().await?,
+            }
+        }
+    }
+}
@@ -0,0 +1,532 @@
+//! Editing engine of the command line, without any I/O.
+use crate::history::History;
+use crate::{EditAction, HistoryScope};
+
+/**
+ * The state of the command line being edited (text, cursor and history navigation), and
+ * the editing operations on it.
+ *
+ * The LineEditor performs no I/O: the [`crate::Cli`] drives it from the keys read on the
+ * terminal and renders its state, and other frontends can reuse it the same way. The
+ * cursor is a byte position in the line, always on a character boundary.
+ *
+ * ```
+ * use tokiocli::{EditAction, LineEditor};
+ *
+ * let mut editor = LineEditor::new();
+ * editor.insert_str("helo");
+ * editor.apply(&EditAction::BackwardChar);
+ * editor.apply(&EditAction::Insert('l'));
+ * assert_eq!(editor.line(), "hello");
+ * assert_eq!(editor.cursor(), 4);
+ * ```
+ */
+pub struct LineEditor {
+    pub(crate) line: String,
+    pub(crate) cursor: usize,
+    pub(crate) history: History,
+    pub(crate) history_idx: Option<usize>,
+    pub(crate) history_scope: HistoryScope,
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineEditor {
+    /** Create an editor with an empty line and an empty history */
+    pub fn new() -> Self {
+        Self {
+            line: String::new(),
+            cursor: 0,
+            history: History::new(),
+            history_idx: None,
+            history_scope: HistoryScope::All,
+        }
+    }
+
+    /** Return the text of the line */
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /** Return the position of the cursor in the line */
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /** Replace the text of the line, and move the cursor at its end */
+    pub fn setline(&mut self, line: &str) -> &mut Self {
+        self.line = line.into();
+        self.cursor = self.line.len();
+        self
+    }
+
+    /** Move the cursor, within the bounds of the line and on a character boundary */
+    pub fn setcursor(&mut self, cursor: usize) -> &mut Self {
+        let mut cursor = std::cmp::min(cursor, self.line.len());
+        while !self.line.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+        self.cursor = cursor;
+        self
+    }
+
+    /** Empty the line and leave the history navigation */
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+        self.history_idx = None;
+    }
+
+    /** Insert a character at the cursor, and move the cursor after it */
+    pub fn insert(&mut self, c: char) {
+        self.line.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /** Insert a text at the cursor, and move the cursor after it */
+    pub fn insert_str(&mut self, text: &str) {
+        self.line.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    /** Return the position of the character before the cursor, if any */
+    fn prev_boundary(&self) -> Option<usize> {
+        let c = self.line[..self.cursor].chars().next_back()?;
+        Some(self.cursor - c.len_utf8())
+    }
+
+    /** Return the position after the character under the cursor, if any */
+    fn next_boundary(&self) -> Option<usize> {
+        let c = self.line[self.cursor..].chars().next()?;
+        Some(self.cursor + c.len_utf8())
+    }
+
+    /** Move the cursor one character left, and tell if it moved */
+    pub fn move_left(&mut self) -> bool {
+        match self.prev_boundary() {
+            Some(cursor) => {
+                self.cursor = cursor;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /** Move the cursor one character right, and tell if it moved */
+    pub fn move_right(&mut self) -> bool {
+        match self.next_boundary() {
+            Some(cursor) => {
+                self.cursor = cursor;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /** Move the cursor at the beginning of the line, and tell if it moved */
+    pub fn move_home(&mut self) -> bool {
+        let moved = self.cursor > 0;
+        self.cursor = 0;
+        moved
+    }
+
+    /** Move the cursor at the end of the line, and tell if it moved */
+    pub fn move_end(&mut self) -> bool {
+        let moved = self.cursor < self.line.len();
+        self.cursor = self.line.len();
+        moved
+    }
+
+    /** Delete the character before the cursor, and return it */
+    pub fn delete_backward(&mut self) -> Option<char> {
+        self.cursor = self.prev_boundary()?;
+        Some(self.line.remove(self.cursor))
+    }
+
+    /** Delete the character under the cursor, and return it */
+    pub fn delete_forward(&mut self) -> Option<char> {
+        self.next_boundary()?;
+        Some(self.line.remove(self.cursor))
+    }
+
+    /**
+     * Swap the character before the cursor with the one under the cursor, and move the
+     * cursor after them. At the end of the line, the last two characters are swapped.
+     * Return false if there are not two characters to swap.
+     */
+    pub fn transpose_chars(&mut self) -> bool {
+        let end = self.next_boundary().unwrap_or(self.cursor);
+        let Some((second, _)) = self.line[..end].char_indices().next_back() else {
+            return false;
+        };
+        let Some((first, _)) = self.line[..second].char_indices().next_back() else {
+            return false;
+        };
+        let swapped = self.line[second..end].to_string() + &self.line[first..second];
+        self.line.replace_range(first..end, &swapped);
+        self.cursor = end;
+        true
+    }
+
+    /**
+     * Swap the word before the cursor with the word after it (or under it), and move the
+     * cursor after them. At the end of the line, the last two words are swapped. Words are
+     * made of alphanumeric characters. Return false if there are not two words to swap.
+     */
+    pub fn transpose_words(&mut self) -> bool {
+        let line = &self.line;
+        // End of the word after the cursor, or of the last word
+        let after = line[self.cursor..]
+            .find(is_word_char)
+            .map(|idx| self.cursor + idx);
+        let end = match after {
+            Some(start) => word_end(line, start),
+            None => match line.rfind(is_word_char) {
+                Some(idx) => word_end(line, idx),
+                None => return false,
+            },
+        };
+        let start = word_start(line, end);
+        let Some(idx) = line[..start].rfind(is_word_char) else {
+            return false;
+        };
+        let prev_end = word_end(line, idx);
+        let prev_start = word_start(line, prev_end);
+        let swapped = format!(
+            "{}{}{}",
+            &line[start..end],
+            &line[prev_end..start],
+            &line[prev_start..prev_end]
+        );
+        self.line.replace_range(prev_start..end, &swapped);
+        self.cursor = end;
+        true
+    }
+
+    /**
+     * Convert the word from the cursor (the rest of the word under the cursor, or the next
+     * word) with the provided function, and move the cursor after it. Return false if there
+     * is no word after the cursor.
+     */
+    fn convert_word(&mut self, convert: impl Fn(&str) -> String) -> bool {
+        let Some(idx) = self.line[self.cursor..].find(is_word_char) else {
+            return false;
+        };
+        let start = self.cursor + idx;
+        let end = word_end(&self.line, start);
+        let converted = convert(&self.line[start..end]);
+        self.line.replace_range(start..end, &converted);
+        self.cursor = start + converted.len();
+        true
+    }
+
+    /** Convert the word from the cursor to uppercase, and move the cursor after it */
+    pub fn upcase_word(&mut self) -> bool {
+        self.convert_word(str::to_uppercase)
+    }
+
+    /** Convert the word from the cursor to lowercase, and move the cursor after it */
+    pub fn downcase_word(&mut self) -> bool {
+        self.convert_word(str::to_lowercase)
+    }
+
+    /** Capitalize the word from the cursor, and move the cursor after it */
+    pub fn capitalize_word(&mut self) -> bool {
+        self.convert_word(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().map(char::to_uppercase);
+            first
+                .into_iter()
+                .flatten()
+                .chain(chars.flat_map(char::to_lowercase))
+                .collect()
+        })
+    }
+
+    /** Return the index of the history entry being edited, None for a new line */
+    pub fn history_index(&self) -> Option<usize> {
+        self.history_idx
+    }
+
+    /** Return the number of entries in the history */
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /** Append an entry to the history */
+    pub fn add_history(&mut self, line: impl Into<String>) {
+        self.history.push(line.into());
+    }
+
+    /** Set the entries of the history reachable while navigating it */
+    pub fn sethistoryscope(&mut self, scope: HistoryScope) -> &mut Self {
+        self.history_scope = scope;
+        self
+    }
+
+    /** Switch between the two history scopes, and leave the history navigation */
+    pub fn toggle_history_scope(&mut self) {
+        self.history_scope = match self.history_scope {
+            HistoryScope::All => HistoryScope::Session,
+            HistoryScope::Session => HistoryScope::All,
+        };
+        self.history_idx = None;
+    }
+
+    /** Replace the line by the history entry being edited, if any */
+    fn history_restore(&mut self) {
+        if let Some(entry) = self.history_idx.and_then(|idx| self.history.get(idx)) {
+            self.line = entry.text.clone();
+            self.cursor = self.line.len();
+        }
+    }
+
+    /**
+     * Edit the previous entry of the history, within the history scope. The line is kept
+     * on the first entry, or if the history is empty.
+     */
+    pub fn history_prev(&mut self) {
+        let first = self.history.first(self.history_scope);
+        self.history_idx = match self.history_idx {
+            Some(idx) => match idx > first {
+                true => Some(idx - 1),
+                false => Some(idx),
+            },
+            None => match self.history.len() > first {
+                true => Some(self.history.len() - 1),
+                false => None,
+            },
+        };
+        self.history_restore();
+    }
+
+    /**
+     * Edit the next entry of the history. Going past the last entry leaves the history
+     * navigation, keeping the line as is.
+     */
+    pub fn history_next(&mut self) {
+        self.history_idx = self
+            .history_idx
+            .filter(|idx| idx + 1 < self.history.len())
+            .map(|idx| idx + 1);
+        self.history_restore();
+    }
+
+    /**
+     * Edit the nearest history entry containing the query, in the direction of the search
+     * from the entry being edited (or from a new line), and move the cursor on the match.
+     * The entry being edited is also a candidate unless `skip` is set. If `wraparound` is
+     * set, the search continues from the other end of the history scope.
+     *
+     * Return false, leaving the line as is, if no entry matches.
+     */
+    pub fn history_search(
+        &mut self,
+        query: &str,
+        forward: bool,
+        skip: bool,
+        wraparound: bool,
+    ) -> bool {
+        let first = self.history.first(self.history_scope);
+        let len = self.history.len();
+        let candidates: Vec<usize> = match forward {
+            true => {
+                let start = match self.history_idx {
+                    Some(idx) => idx + usize::from(skip),
+                    None => len,
+                };
+                let start = start.clamp(first, len);
+                let wrapped = match wraparound {
+                    true => first..start,
+                    false => first..first,
+                };
+                (start..len).chain(wrapped).collect()
+            }
+            false => {
+                let end = match self.history_idx {
+                    Some(idx) => idx + usize::from(!skip),
+                    None => len,
+                };
+                let end = end.clamp(first, len);
+                let wrapped = match wraparound {
+                    true => end..len,
+                    false => len..len,
+                };
+                (first..end).rev().chain(wrapped.rev()).collect()
+            }
+        };
+        let found = candidates.into_iter().find_map(|idx| {
+            let entry = self.history.get(idx)?;
+            let position = match forward {
+                true => entry.text.find(query),
+                false => entry.text.rfind(query),
+            };
+            position.map(|position| (idx, entry.text.clone(), position))
+        });
+        match found {
+            Some((idx, entry, position)) => {
+                self.history_idx = Some(idx);
+                self.line = entry;
+                self.cursor = position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /**
+     * Perform an action editing the line or navigating the history, and tell if it was
+     * performed (or, for a transposition or a case conversion, if there was something to
+     * convert). The other actions (e.g. completion or submission) are left to the caller.
+     */
+    pub fn apply(&mut self, action: &EditAction) -> bool {
+        match action {
+            EditAction::Insert(c) => self.insert(*c),
+            EditAction::BeginningOfLine => {
+                self.move_home();
+            }
+            EditAction::EndOfLine => {
+                self.move_end();
+            }
+            EditAction::ForwardChar => {
+                self.move_right();
+            }
+            EditAction::BackwardChar => {
+                self.move_left();
+            }
+            EditAction::PreviousHistory => self.history_prev(),
+            EditAction::NextHistory => self.history_next(),
+            EditAction::ToggleHistoryScope => self.toggle_history_scope(),
+            EditAction::BackwardDeleteChar => {
+                self.delete_backward();
+            }
+            EditAction::DeleteChar => {
+                self.delete_forward();
+            }
+            EditAction::TransposeChars => return self.transpose_chars(),
+            EditAction::TransposeWords => return self.transpose_words(),
+            EditAction::UpcaseWord => return self.upcase_word(),
+            EditAction::DowncaseWord => return self.downcase_word(),
+            EditAction::CapitalizeWord => return self.capitalize_word(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/** Tell if a character is part of a word, for the word commands */
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric()
+}
+
+/** Return the end of the word containing the character at the provided position */
+fn word_end(line: &str, position: usize) -> usize {
+    line[position..]
+        .find(|c| !is_word_char(c))
+        .map_or(line.len(), |idx| position + idx)
+}
+
+/** Return the start of the word ending at the provided position */
+fn word_start(line: &str, end: usize) -> usize {
+    line[..end].rfind(|c| !is_word_char(c)).map_or(0, |idx| {
+        idx + line[idx..].chars().next().map_or(1, char::len_utf8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_line(line: &str, cursor: usize) -> LineEditor {
+        let mut editor = LineEditor::new();
+        editor.setline(line).setcursor(cursor);
+        editor
+    }
+
+    #[test]
+    fn cursor_moves_on_char_boundaries() {
+        let mut editor = with_line("aé€😀", 0);
+        let mut positions = vec![editor.cursor()];
+        while editor.move_right() {
+            positions.push(editor.cursor());
+        }
+        assert_eq!(positions, [0, 1, 3, 6, 10]);
+        assert!(!editor.move_right());
+        while editor.move_left() {
+            positions.pop();
+            assert_eq!(Some(&editor.cursor()), positions.last());
+        }
+        assert_eq!(editor.cursor(), 0);
+
+        // Inside a multi-byte character, the cursor moves back to its start
+        editor.setcursor(5);
+        assert_eq!(editor.cursor(), 3);
+    }
+
+    #[test]
+    fn insert_and_delete_multibyte() {
+        let mut editor = with_line("hé", 1);
+        editor.insert('€');
+        assert_eq!((editor.line(), editor.cursor()), ("h€é", 4));
+        assert_eq!(editor.delete_forward(), Some('é'));
+        assert_eq!(editor.delete_backward(), Some('€'));
+        assert_eq!((editor.line(), editor.cursor()), ("h", 1));
+        assert_eq!(editor.delete_forward(), None);
+    }
+
+    #[test]
+    fn transpose_chars_multibyte() {
+        let mut editor = with_line("aé€", 1);
+        assert!(editor.transpose_chars());
+        assert_eq!((editor.line(), editor.cursor()), ("éa€", 3));
+
+        // At the end of the line, the last two characters are swapped
+        editor.move_end();
+        assert!(editor.transpose_chars());
+        assert_eq!((editor.line(), editor.cursor()), ("é€a", 6));
+
+        let mut editor = with_line("é", 0);
+        assert!(!editor.transpose_chars());
+    }
+
+    #[test]
+    fn transpose_words_multibyte() {
+        let mut editor = with_line("été café", 6);
+        assert!(editor.transpose_words());
+        assert_eq!((editor.line(), editor.cursor()), ("café été", 11));
+
+        let mut editor = with_line("seul", 2);
+        assert!(!editor.transpose_words());
+    }
+
+    #[test]
+    fn convert_words_multibyte() {
+        let mut editor = with_line("élan ÉTÉ çà", 0);
+        assert!(editor.upcase_word());
+        assert_eq!((editor.line(), editor.cursor()), ("ÉLAN ÉTÉ çà", 5));
+        assert!(editor.downcase_word());
+        assert_eq!((editor.line(), editor.cursor()), ("ÉLAN été çà", 11));
+        assert!(editor.capitalize_word());
+        assert_eq!((editor.line(), editor.cursor()), ("ÉLAN été Çà", 16));
+        assert!(!editor.capitalize_word());
+    }
+
+    #[test]
+    fn apply_actions() {
+        let mut editor = LineEditor::new();
+        for c in "ab€".chars() {
+            assert!(editor.apply(&EditAction::Insert(c)));
+        }
+        assert!(editor.apply(&EditAction::BeginningOfLine));
+        assert!(editor.apply(&EditAction::DeleteChar));
+        assert!(editor.apply(&EditAction::EndOfLine));
+        assert!(editor.apply(&EditAction::BackwardChar));
+        assert!(editor.apply(&EditAction::BackwardDeleteChar));
+        assert_eq!((editor.line(), editor.cursor()), ("€", 0));
+        assert!(!editor.apply(&EditAction::Complete));
+    }
+}
@@ -0,0 +1,43 @@
+//! Errors reported by the Cli.
+
+/** Error returned by a command handler, see [`crate::Cli::run`] and [`crate::Repl`] */
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/**
+ * An error reported by the Cli.
+ *
+ * It implements [`std::error::Error`], `Send` and `Sync`: an application using `eyre` or
+ * `anyhow` converts it with `?`, the crate does not depend on them.
+ */
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /** Reading from or writing to the terminal failed. */
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /** The process has no terminal to interact with. */
+    #[error("not a terminal")]
+    NotATty,
+    /** The configuration of the terminal mode failed. */
+    #[error("failed to configure the terminal: {0}")]
+    Terminal(#[source] std::io::Error),
+    /** Reading from the terminal was interrupted by a signal. */
+    #[error("interrupted")]
+    Interrupted,
+    /**
+     * The input of the terminal was closed. EOT on an empty line is reported by
+     * [`crate::Cli::getaction`] as [`crate::Action::NoAction`], and by the blocking
+     * [`crate::Editor::readline`] as this error.
+     */
+    #[error("end of file")]
+    Eof,
+    /** A script or a configuration could not be parsed, or a script could not be executed. */
+    #[error("{0}")]
+    Syntax(String),
+    /** A command handler failed. */
+    #[error(transparent)]
+    Command(BoxError),
+}
+
+/** Result of the operations of the Cli */
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,18 @@
+//! Syntax highlighting of the command line.
+
+/**
+ * Style the command line while the user types.
+ *
+ * A Highlighter can be installed with [`crate::Cli::sethighlighter`]. The command line is
+ * then fully repainted after each modification, with the styled version returned by the
+ * Highlighter.
+ */
+pub trait Highlighter {
+    /**
+     * Return the styled version of the command line.
+     *
+     * Only ANSI escape sequences (e.g. colors) may be added: the displayed text must be
+     * left unchanged for the cursor to be correctly positioned.
+     */
+    fn highlight(&self, line: &str) -> String;
+}
@@ -0,0 +1,34 @@
+//! Inline hints displayed after the command line.
+
+/**
+ * Suggest a placeholder text while the user types, e.g. the expected arguments of the
+ * typed command: `connect <host> <port>`.
+ *
+ * A Hinter can be installed with [`crate::Cli::sethinter`]. The hint is displayed in grey
+ * after the command line while the cursor is at its end, and is recomputed after each
+ * keystroke. It is only a visual aid: it is neither inserted in the command line nor
+ * submitted.
+ *
+ * ```no_run
+ * struct ConnectHinter;
+ *
+ * impl tokiocli::Hinter for ConnectHinter {
+ *     fn hint(&self, line: &str) -> Option<String> {
+ *         match line {
+ *             "connect" => Some(String::from(" <host> <port>")),
+ *             "connect " => Some(String::from("<host> <port>")),
+ *             _ => None,
+ *         }
+ *     }
+ * }
+ * ```
+ */
+pub trait Hinter {
+    /**
+     * Return the text displayed after the command line, if any.
+     *
+     * The hint should be a single line of text: it is not displayed if it does not fit on
+     * the terminal row.
+     */
+    fn hint(&self, line: &str) -> Option<String>;
+}
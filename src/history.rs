@@ -0,0 +1,712 @@
+//! Commands history.
+use crate::{Error, Result};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/** Entries of the history reachable while navigating or searching the history */
+#[derive(Clone, Copy)]
+pub enum HistoryScope {
+    /** All entries, including the ones loaded from a history file. */
+    All,
+    /** Only the entries entered during the current session. */
+    Session,
+}
+
+/** How the history is written to a file which other sessions may have saved to */
+#[derive(Clone, Copy)]
+pub enum HistorySave {
+    /** Write all entries, discarding the entries saved by other sessions since the load. */
+    Overwrite,
+    /** Append the entries of the session not saved yet at the end of the file. */
+    Append,
+    /**
+     * Write back the entries of the file, including the ones saved by other sessions since
+     * the load, followed by the entries of the session not saved yet. The file is replaced
+     * atomically.
+     */
+    Merge,
+}
+
+/** An entry of the history, with the metadata recorded when the command was entered */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /** The command line */
+    pub text: String,
+    /** When the command was entered, unknown for the entries of a plain history file */
+    pub timestamp: Option<SystemTime>,
+    /** The session which entered the command, see [`crate::Cli::sethistorysession`] */
+    pub session: Option<String>,
+    /** The status of the command, see [`crate::Cli::setstatus`] */
+    pub status: Option<i32>,
+}
+
+impl HistoryEntry {
+    /**
+     * Append the entry to the content of a history file, preceded by its metadata line:
+     * `#<timestamp> [status=<status>] [session=<session>]`, the timestamp being in seconds
+     * since the Unix epoch.
+     *
+     * The entry is written on a single line: its backslashes, newlines and leading '#' are
+     * escaped (see [`escape`]).
+     */
+    fn write(&self, content: &mut String) {
+        let since_epoch = self
+            .timestamp
+            .and_then(|timestamp| timestamp.duration_since(UNIX_EPOCH).ok());
+        if let Some(since_epoch) = since_epoch {
+            *content += &format!("#{}", since_epoch.as_secs());
+            if let Some(status) = self.status {
+                *content += &format!(" status={}", status);
+            }
+            if let Some(session) = &self.session {
+                *content += &format!(" session={}", session);
+            }
+            content.push('\n');
+        }
+        *content += &escape(&self.text);
+        content.push('\n');
+    }
+
+    /** Return what identifies the entry once written to a history file */
+    fn key(&self) -> (&str, Option<u64>, Option<&str>) {
+        let since_epoch = self
+            .timestamp
+            .and_then(|timestamp| timestamp.duration_since(UNIX_EPOCH).ok());
+        (
+            &self.text,
+            since_epoch.map(|since_epoch| since_epoch.as_secs()),
+            self.session.as_deref(),
+        )
+    }
+}
+
+/**
+ * Escape the text of an entry, to write it on a single line of a history file which does
+ * not look like a metadata line: `\\` for a backslash, `\n` for a newline and `\#` for a
+ * leading '#'
+ */
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    if text.starts_with('#') {
+        escaped.push('\\');
+    }
+    for c in text.chars() {
+        match c {
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/** Unescape a line of a history file (see [`escape`]) */
+fn unescape(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some(c) => text.push(c),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/** Parse the metadata line of an entry: timestamp, status and session */
+fn parse_metadata(line: &str) -> Option<(SystemTime, Option<i32>, Option<String>)> {
+    let line = line.strip_prefix('#')?;
+    let (timestamp, mut rest) = line.split_once(' ').unwrap_or((line, ""));
+    let timestamp = UNIX_EPOCH + Duration::from_secs(timestamp.parse().ok()?);
+    let mut status = None;
+    if let Some(value) = rest.strip_prefix("status=") {
+        let (value, next) = value.split_once(' ').unwrap_or((value, ""));
+        status = Some(value.parse().ok()?);
+        rest = next;
+    }
+    let session = match rest {
+        "" => None,
+        rest => Some(rest.strip_prefix("session=")?.to_string()),
+    };
+    Some((timestamp, status, session))
+}
+
+/** Commands history, with the boundary between persisted and current session entries */
+pub(crate) struct History {
+    entries: Vec<HistoryEntry>,
+    session_start: usize,
+    /** Number of entries of the session already saved */
+    saved: usize,
+    /** Length of the history file content already loaded, or saved by the session */
+    synced: usize,
+    /** Hash of this content, telling if the file was rewritten by another session since */
+    synced_hash: u64,
+    /** The file was rewritten by another session while saving: the next reload reads it all */
+    resync: bool,
+    /** Entries saved by the other sessions, read while saving and not loaded yet */
+    unread: String,
+    pub strategy: HistorySave,
+    /** Maximum number of entries, the oldest entries being evicted beyond */
+    pub max: Option<usize>,
+    /** Number of entries evicted, so that the entries keep their number */
+    evicted: usize,
+    /** Session recorded in the entries of the current session */
+    pub session: Option<String>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::<HistoryEntry>::new(),
+            session_start: 0,
+            saved: 0,
+            synced: 0,
+            synced_hash: hash(""),
+            resync: false,
+            unread: String::new(),
+            strategy: HistorySave::Merge,
+            max: None,
+            evicted: 0,
+            session: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&HistoryEntry> {
+        self.entries.get(idx)
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /** Append an entry entered now in the current session */
+    pub fn push(&mut self, text: String) {
+        self.entries.push(HistoryEntry {
+            text,
+            timestamp: Some(SystemTime::now()),
+            session: self.session.clone(),
+            status: None,
+        });
+        self.evict();
+    }
+
+    /** Apply the settings of another history: save strategy, maximum size and session */
+    pub fn configure_like(&mut self, other: &History) {
+        self.strategy = other.strategy;
+        self.max = other.max;
+        self.session = other.session.clone();
+        self.evict();
+    }
+
+    /** Evict the oldest entries beyond the maximum number of entries */
+    pub fn evict(&mut self) {
+        let Some(excess) = self.max.and_then(|max| self.entries.len().checked_sub(max)) else {
+            return;
+        };
+        self.entries.drain(..excess);
+        self.evicted += excess;
+        let persisted = std::cmp::min(excess, self.session_start);
+        self.session_start -= persisted;
+        self.saved = self.saved.saturating_sub(excess - persisted);
+    }
+
+    /**
+     * Return the number of an entry, starting from 1: the number of an entry is not changed
+     * by the eviction of the oldest entries
+     */
+    pub fn number(&self, idx: usize) -> usize {
+        self.evicted + idx + 1
+    }
+
+    /** Record the status of the last entry of the session, if not recorded yet */
+    pub fn setstatus(&mut self, status: i32) {
+        if let Some(entry) = self.entries[self.session_start..].last_mut() {
+            entry.status.get_or_insert(status);
+        }
+    }
+
+    /** Return the index of the first entry reachable in the provided scope */
+    pub fn first(&self, scope: HistoryScope) -> usize {
+        match scope {
+            HistoryScope::All => 0,
+            HistoryScope::Session => self.session_start,
+        }
+    }
+
+    /**
+     * Replace the references to history entries by the entries: `!!` is the last entry,
+     * `!n` the entry numbered n (see [`History::number`]), `!-n` the n-th last entry and
+     * `!prefix` the last entry starting with prefix. A '!' within single quotes, escaped by
+     * a backslash, or followed by a space, '=' or '"', is kept as is.
+     *
+     * Return the reference not found in the history on error.
+     */
+    pub fn expand(&self, line: &str) -> std::result::Result<String, String> {
+        let mut expanded = String::with_capacity(line.len());
+        let mut chars = line.chars().peekable();
+        let mut quote = None;
+        while let Some(c) = chars.next() {
+            if quote == Some('\'') {
+                // Nothing is expanded within single quotes, not even after a backslash
+                if c == '\'' {
+                    quote = None;
+                }
+                expanded.push(c);
+                continue;
+            }
+            if c == '\\' {
+                expanded.push(c);
+                expanded.extend(chars.next());
+                continue;
+            }
+            if c == '\'' || c == '"' {
+                quote = match quote {
+                    Some(opening) if opening == c => None,
+                    None => Some(c),
+                    quote => quote,
+                };
+                expanded.push(c);
+                continue;
+            }
+            if c != '!'
+                || chars
+                    .peek()
+                    .is_none_or(|c| matches!(c, ' ' | '\t' | '\n' | '=' | '"'))
+            {
+                expanded.push(c);
+                continue;
+            }
+            let mut event = String::from("!");
+            let entry = match chars.next_if_eq(&'!') {
+                Some(c) => {
+                    event.push(c);
+                    self.entries.last().map(|entry| &entry.text)
+                }
+                None => {
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        event.push(c);
+                    }
+                    let word = &event[1..];
+                    if let Ok(n) = word.parse::<usize>() {
+                        n.checked_sub(self.evicted + 1)
+                            .and_then(|idx| self.entries.get(idx))
+                    } else if let Some(Ok(n)) = word.strip_prefix('-').map(str::parse::<usize>) {
+                        self.entries
+                            .len()
+                            .checked_sub(n)
+                            .and_then(|idx| self.entries.get(idx).filter(|_| n > 0))
+                    } else {
+                        self.entries
+                            .iter()
+                            .rev()
+                            .find(|entry| entry.text.starts_with(word))
+                    }
+                    .map(|entry| &entry.text)
+                }
+            };
+            match entry {
+                Some(entry) => expanded += entry,
+                None => return Err(event),
+            }
+        }
+        Ok(expanded)
+    }
+
+    /**
+     * Load entries from a history file, one entry per line, each entry being optionally
+     * preceded by its metadata line (see [`HistoryEntry::write`]).
+     *
+     * Loaded entries are inserted before the entries of the current session.
+     */
+    pub async fn load(&mut self, path: &Path) -> Result<()> {
+        let mut file = lock(path, false).await?;
+        let mut content = String::new();
+        file.read_to_string(&mut content).await?;
+        self.sync(&content);
+        self.insert(&content);
+        Ok(())
+    }
+
+    /**
+     * Load the entries written to a history file by the other sessions since the last
+     * load, reload or save.
+     *
+     * If the file was rewritten by another session meanwhile (e.g. truncated to its last
+     * entries), the entries loaded from the file are replaced by the entries of the file.
+     */
+    pub async fn reload(&mut self, path: &Path) -> Result<()> {
+        let mut content = String::new();
+        match lock(path, false).await {
+            Ok(mut file) => {
+                file.read_to_string(&mut content).await?;
+            }
+            Err(Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        }
+        let unsynced = match self.resync {
+            true => None,
+            false => self.unsynced(&content).map(String::from),
+        };
+        self.sync(&content);
+        match unsynced {
+            Some(unsynced) => {
+                let unread = std::mem::take(&mut self.unread) + &unsynced;
+                self.insert(&unread);
+            }
+            None => {
+                self.unread.clear();
+                self.resync = false;
+                self.replace(&content);
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Return the end of the history file content not loaded or saved by the session yet, or
+     * None if the file was rewritten by another session since
+     */
+    fn unsynced<'a>(&self, content: &'a str) -> Option<&'a str> {
+        let synced = content.get(..self.synced)?;
+        (hash(synced) == self.synced_hash).then(|| &content[self.synced..])
+    }
+
+    /** Record the history file content as loaded or saved by the session */
+    fn sync(&mut self, content: &str) {
+        self.synced = content.len();
+        self.synced_hash = hash(content);
+    }
+
+    /** Insert the entries of a history file content before the entries of the session */
+    fn insert(&mut self, content: &str) {
+        let loaded = parse(content);
+        let start = self.session_start;
+        self.session_start += loaded.len();
+        self.entries.splice(start..start, loaded);
+        self.evict();
+    }
+
+    /**
+     * Replace the entries loaded from the history file by the entries of its content, but
+     * the entries saved by the session
+     */
+    fn replace(&mut self, content: &str) {
+        let session = &self.entries[self.session_start..];
+        let mut saved: Vec<_> = session[..self.saved]
+            .iter()
+            .map(HistoryEntry::key)
+            .collect();
+        let mut loaded = parse(content);
+        loaded.retain(
+            |entry| match saved.iter().position(|key| *key == entry.key()) {
+                Some(idx) => {
+                    saved.swap_remove(idx);
+                    false
+                }
+                None => true,
+            },
+        );
+        let end = std::mem::replace(&mut self.session_start, loaded.len());
+        self.entries.splice(..end, loaded);
+        self.evict();
+    }
+
+    /**
+     * Save the entries in a history file, one entry per line preceded by its metadata line,
+     * according to the strategy.
+     *
+     * The file is locked while it is written, so that concurrent sessions can safely save
+     * to the same file. The entries saved by the other sessions since the last load are
+     * kept for the next reload (unless they are discarded by [`HistorySave::Overwrite`]).
+     *
+     * When the file is rewritten ([`HistorySave::Overwrite`] and [`HistorySave::Merge`]),
+     * only the last entries are kept within the maximum number of entries. The file is
+     * not truncated by [`HistorySave::Append`], as other sessions may keep appending to it.
+     */
+    pub async fn save(&mut self, path: &Path, strategy: HistorySave) -> Result<()> {
+        let mut file = lock(path, true).await?;
+        let mut content = String::new();
+        file.read_to_string(&mut content).await?;
+        let unsaved = &self.entries[self.session_start + self.saved..];
+        if !matches!(strategy, HistorySave::Overwrite) {
+            match self.unsynced(&content) {
+                Some(unread) => self.unread.push_str(unread),
+                None => self.resync = true,
+            }
+        }
+        match strategy {
+            HistorySave::Overwrite => {
+                content.clear();
+                for entry in &self.entries {
+                    entry.write(&mut content);
+                }
+                self.unread.clear();
+                self.resync = false;
+                tokio::fs::write(path, &content).await?;
+            }
+            HistorySave::Append => {
+                let mut appended = String::new();
+                for entry in unsaved {
+                    entry.write(&mut appended);
+                }
+                file.write_all(appended.as_bytes()).await?;
+                file.flush().await?;
+                content += &appended;
+            }
+            HistorySave::Merge => {
+                if !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                for entry in unsaved {
+                    entry.write(&mut content);
+                }
+                if let Some(max) = self.max {
+                    content = last_entries(&content, max).to_string();
+                }
+                let mut tmp = path.as_os_str().to_owned();
+                tmp.push(format!(".{}.tmp", std::process::id()));
+                tokio::fs::write(&tmp, &content).await?;
+                tokio::fs::rename(&tmp, path).await?;
+            }
+        }
+        self.sync(&content);
+        self.saved = self.entries.len() - self.session_start;
+        Ok(())
+    }
+}
+
+/** Parse the entries of a history file content */
+fn parse(content: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::<HistoryEntry>::new();
+    let mut metadata = None;
+    for line in content.lines().filter(|line| !line.is_empty()) {
+        if let Some(parsed) = parse_metadata(line) {
+            metadata = Some(parsed);
+            continue;
+        }
+        let (timestamp, status, session) = match metadata.take() {
+            Some((timestamp, status, session)) => (Some(timestamp), status, session),
+            None => (None, None, None),
+        };
+        entries.push(HistoryEntry {
+            text: unescape(line),
+            timestamp,
+            session,
+            status,
+        });
+    }
+    entries
+}
+
+/** Hash a history file content */
+fn hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/** Return the end of a history file content holding its last `max` entries */
+fn last_entries(content: &str, max: usize) -> &str {
+    // Beginning of each entry, its metadata line included
+    let mut starts = Vec::<usize>::new();
+    let mut start = None;
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\r', '\n']);
+        if !text.is_empty() {
+            let begin = *start.get_or_insert(offset);
+            if parse_metadata(text).is_none() {
+                starts.push(begin);
+                start = None;
+            }
+        }
+        offset += line.len();
+    }
+    match starts.len().checked_sub(max) {
+        Some(excess) if excess > 0 => &content[starts[excess]..],
+        _ => content,
+    }
+}
+
+/**
+ * Open a history file, creating it to write it, and lock it: exclusively to write it, or
+ * shared to read it. The file is unlocked when it is closed.
+ */
+async fn lock(path: &Path, write: bool) -> Result<tokio::fs::File> {
+    let path = path.to_path_buf();
+    let file = tokio::task::spawn_blocking(move || -> std::io::Result<std::fs::File> {
+        loop {
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .append(write)
+                .create(write)
+                .open(&path)?;
+            if flock(&file, &path, write)? {
+                return Ok(file);
+            }
+        }
+    })
+    .await
+    .map_err(std::io::Error::other)??;
+    Ok(tokio::fs::File::from_std(file))
+}
+
+/**
+ * Lock an opened file, waiting for the other sessions to release it, and tell if it is
+ * still the file at this path: it may have been replaced while waiting for the lock (see
+ * [`HistorySave::Merge`]).
+ */
+#[cfg(unix)]
+fn flock(file: &std::fs::File, path: &Path, write: bool) -> std::io::Result<bool> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::MetadataExt;
+    let operation = match write {
+        true => libc::LOCK_EX,
+        false => libc::LOCK_SH,
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let locked = file.metadata()?;
+    match std::fs::metadata(path) {
+        Ok(current) => Ok(current.ino() == locked.ino() && current.dev() == locked.dev()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/** File locking is not supported: the history file is used without lock */
+#[cfg(not(unix))]
+fn flock(_file: &std::fs::File, _path: &Path, _write: bool) -> std::io::Result<bool> {
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn save_load_roundtrip() {
+        let path = std::env::temp_dir().join(format!("tokiocli-test-{}.hist", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let texts = [
+            "echo a\necho b",
+            "#1700000000",
+            "echo \\n is not a newline",
+            "ls foo\\ bar",
+            "\\#",
+        ];
+        let mut history = History::new();
+        for text in texts {
+            history.push(text.to_string());
+        }
+        history.save(&path, HistorySave::Overwrite).await.unwrap();
+
+        let mut loaded = History::new();
+        loaded.load(&path).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        let loaded = loaded.entries().iter().map(|entry| entry.text.as_str());
+        assert_eq!(loaded.collect::<Vec<_>>(), texts);
+    }
+
+    fn texts(history: &History) -> Vec<&str> {
+        history
+            .entries()
+            .iter()
+            .map(|entry| entry.text.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reload_rewritten_file() {
+        let path = std::env::temp_dir().join(format!("tokiocli-test-{}.rw", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut first = History::new();
+        first.push("a1".into());
+        first.save(&path, HistorySave::Overwrite).await.unwrap();
+
+        // Rewritten with the same length, but another entry
+        let mut second = History::new();
+        second.max = Some(1);
+        second.load(&path).await.unwrap();
+        second.push("b1".into());
+        second.save(&path, HistorySave::Overwrite).await.unwrap();
+
+        first.reload(&path).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(texts(&first), ["b1", "a1"]);
+    }
+
+    #[tokio::test]
+    async fn merge_interleaved_truncated() {
+        let path = std::env::temp_dir().join(format!("tokiocli-test-{}.mrg", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut initial = History::new();
+        for text in ["e1", "e2", "e3"] {
+            initial.push(text.into());
+        }
+        initial.save(&path, HistorySave::Overwrite).await.unwrap();
+        let mut first = History::new();
+        first.load(&path).await.unwrap();
+        let mut second = History::new();
+        second.max = Some(3);
+        second.load(&path).await.unwrap();
+
+        first.push("a1".into());
+        first.save(&path, HistorySave::Merge).await.unwrap();
+        // The file is truncated to its last 3 entries: "e3", "a1" and "b1"
+        second.push("b1".into());
+        second.save(&path, HistorySave::Merge).await.unwrap();
+        first.push("a2".into());
+        first.save(&path, HistorySave::Merge).await.unwrap();
+
+        first.reload(&path).await.unwrap();
+        assert_eq!(texts(&first), ["e3", "b1", "a1", "a2"]);
+        second.reload(&path).await.unwrap();
+        assert_eq!(texts(&second), ["a1", "a2", "b1"]);
+        let mut loaded = History::new();
+        loaded.load(&path).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(texts(&loaded), ["e3", "a1", "b1", "a2"]);
+    }
+
+    #[test]
+    fn expand_quoted() {
+        let mut history = History::new();
+        history.push("ls".into());
+        assert_eq!(history.expand("echo 'wow!'").unwrap(), "echo 'wow!'");
+        assert_eq!(history.expand("echo 'a\\' !!").unwrap(), "echo 'a\\' ls");
+        assert_eq!(history.expand("echo \\!!").unwrap(), "echo \\!!");
+        assert_eq!(
+            history.expand("echo \"it's !!\"").unwrap(),
+            "echo \"it's ls\""
+        );
+        assert_eq!(history.expand("echo !x").unwrap_err(), "!x");
+    }
+
+    #[test]
+    fn expand_number_after_eviction() {
+        let mut history = History::new();
+        history.max = Some(2);
+        for text in ["one", "two", "three"] {
+            history.push(text.into());
+        }
+        assert_eq!(history.number(0), 2);
+        assert_eq!(history.expand("!2").unwrap(), "two");
+        assert_eq!(history.expand("!3").unwrap(), "three");
+        assert_eq!(history.expand("!1").unwrap_err(), "!1");
+    }
+}
@@ -0,0 +1,270 @@
+//! Parsing of a subset of the readline configuration file (inputrc).
+use crate::EditAction;
+use std::path::PathBuf;
+
+/** A line of an inputrc file, applied by [`crate::Cli::load_inputrc`] */
+pub(crate) enum Directive {
+    /** Set a variable: `set name value`. */
+    Set(String, String),
+    /** Bind a key sequence to an editing action: `"\C-x\C-e": edit-and-execute-command`. */
+    Bind(Vec<u8>, EditAction),
+    /** Read the directives of another file: `$include path`. */
+    Include(PathBuf),
+}
+
+/**
+ * Return the path of the inputrc file of the user: `$INPUTRC`, or `~/.inputrc` if it is
+ * not set.
+ */
+pub(crate) fn default_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("INPUTRC") {
+        return Some(path.into());
+    }
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".inputrc"))
+}
+
+/**
+ * Parse an inputrc file, keeping the directives which apply to the terminal.
+ *
+ * The lines in `$if mode=emacs` and `$if term=<terminal>` blocks (matching `$TERM`) apply,
+ * while the blocks of the vi mode and of named applications are skipped. Comments, macros,
+ * unknown functions and malformed lines are ignored, as readline does.
+ */
+pub(crate) fn parse(text: &str) -> Vec<Directive> {
+    let term = std::env::var("TERM").unwrap_or_default();
+    let mut directives = Vec::new();
+    // Whether each nested $if block is in its active branch
+    let mut conditions = Vec::<bool>::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(condition) = line.strip_prefix("$if") {
+            conditions.push(test(condition.trim(), &term));
+            continue;
+        }
+        if line.starts_with("$else") {
+            if let Some(active) = conditions.last_mut() {
+                *active = !*active;
+            }
+            continue;
+        }
+        if line.starts_with("$endif") {
+            conditions.pop();
+            continue;
+        }
+        if !conditions.iter().all(|active| *active) {
+            continue;
+        }
+        if let Some(path) = line.strip_prefix("$include") {
+            directives.push(Directive::Include(expand_home(path.trim())));
+        } else if let Some(setting) = line
+            .strip_prefix("set")
+            .filter(|setting| setting.starts_with(char::is_whitespace))
+        {
+            let mut words = setting.split_whitespace();
+            if let (Some(name), Some(value)) = (words.next(), words.next()) {
+                directives.push(Directive::Set(name.to_lowercase(), value.to_string()));
+            }
+        } else if let Some(binding) = binding(line) {
+            directives.push(binding);
+        }
+    }
+    directives
+}
+
+/** Evaluate the condition of an `$if` block */
+fn test(condition: &str, term: &str) -> bool {
+    if let Some(mode) = condition.strip_prefix("mode=") {
+        return mode == "emacs";
+    }
+    if let Some(name) = condition.strip_prefix("term=") {
+        let short = term.split('-').next().unwrap_or_default();
+        return !term.is_empty() && (name == term || name == short);
+    }
+    // The name of an application
+    false
+}
+
+/** Replace a leading `~/` with the home directory of the user */
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(path), Some(home)) => PathBuf::from(home).join(path),
+        _ => PathBuf::from(path),
+    }
+}
+
+/** Parse a key binding: `"keyseq": function-name` or `keyname: function-name` */
+fn binding(line: &str) -> Option<Directive> {
+    let (sequence, function) = match line.strip_prefix('"') {
+        Some(rest) => {
+            let end = closing_quote(rest)?;
+            let function = rest[end + 1..].trim_start().strip_prefix(':')?;
+            (keyseq(&rest[..end])?, function)
+        }
+        None => {
+            let (name, function) = line.split_once(':')?;
+            (keyname(name.trim())?, function)
+        }
+    };
+    let function = function.split_whitespace().next()?;
+    Some(Directive::Bind(sequence, action(function)?))
+}
+
+/**
+ * Parse a key sequence: a sequence with escapes if it contains a backslash (e.g. `\C-x\C-e`),
+ * a key name otherwise (e.g. `Control-t`)
+ */
+#[cfg(feature = "config")]
+pub(crate) fn key(text: &str) -> Option<Vec<u8>> {
+    match text.contains('\\') {
+        true => keyseq(text),
+        false => keyname(text),
+    }
+}
+
+/** Return the position of the quote ending a quoted key sequence */
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, c) in text.char_indices() {
+        match c {
+            '"' if !escaped => return Some(idx),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/** Parse a quoted key sequence, with its escapes (e.g. `\C-x`, `\M-f`, `\e[A`) */
+fn keyseq(text: &str) -> Option<Vec<u8>> {
+    let mut sequence = Vec::new();
+    let mut rest = text.as_bytes();
+    while !rest.is_empty() {
+        let (byte, len) = match rest {
+            [b'\\', b'C', b'-', b'\\', b'M', b'-', c, ..]
+            | [b'\\', b'M', b'-', b'\\', b'C', b'-', c, ..] => {
+                sequence.push(0x1B);
+                (control(*c), 7)
+            }
+            [b'\\', b'C', b'-', c, ..] => (control(*c), 4),
+            [b'\\', b'M', b'-', c, ..] => {
+                sequence.push(0x1B);
+                (*c, 4)
+            }
+            [b'\\', b'e', ..] => (0x1B, 2),
+            [b'\\', b'a', ..] => (0x07, 2),
+            [b'\\', b'b', ..] => (0x08, 2),
+            [b'\\', b'd', ..] => (0x7F, 2),
+            [b'\\', b'f', ..] => (0x0C, 2),
+            [b'\\', b'n', ..] => (b'\n', 2),
+            [b'\\', b'r', ..] => (b'\r', 2),
+            [b'\\', b't', ..] => (b'\t', 2),
+            [b'\\', b'v', ..] => (0x0B, 2),
+            [b'\\', b'x', digits @ ..] => {
+                let len = digits
+                    .iter()
+                    .take(2)
+                    .take_while(|c| c.is_ascii_hexdigit())
+                    .count();
+                (number(&digits[..len], 16)?, 2 + len)
+            }
+            [b'\\', digits @ ..] if matches!(digits, [b'0'..=b'7', ..]) => {
+                let len = digits
+                    .iter()
+                    .take(3)
+                    .take_while(|c| (b'0'..=b'7').contains(*c))
+                    .count();
+                (number(&digits[..len], 8)?, 1 + len)
+            }
+            [b'\\', c, ..] => (*c, 2),
+            [c, ..] => (*c, 1),
+            [] => break,
+        };
+        sequence.push(byte);
+        rest = rest.get(len..)?;
+    }
+    Some(sequence)
+}
+
+/** Parse the digits of a byte in the provided radix */
+fn number(digits: &[u8], radix: u32) -> Option<u8> {
+    let digits = std::str::from_utf8(digits).ok()?;
+    u8::from_str_radix(digits, radix).ok()
+}
+
+/** Parse a key name with its modifiers (e.g. `Control-u`, `Meta-Rubout`, `C-M-f`) */
+fn keyname(text: &str) -> Option<Vec<u8>> {
+    let mut sequence = Vec::new();
+    let mut ctrl = false;
+    let mut rest = text;
+    loop {
+        let lower = rest.to_lowercase();
+        if let Some(len) = ["control-", "c-"].iter().find(|m| lower.starts_with(*m)) {
+            ctrl = true;
+            rest = &rest[len.len()..];
+        } else if let Some(len) = ["meta-", "m-"].iter().find(|m| lower.starts_with(*m)) {
+            sequence.push(0x1B);
+            rest = &rest[len.len()..];
+        } else {
+            break;
+        }
+    }
+    let byte = match rest.to_lowercase().as_str() {
+        "del" | "rubout" => 0x7F,
+        "esc" | "escape" => 0x1B,
+        "lfd" | "newline" => b'\n',
+        "ret" | "return" => b'\r',
+        "space" | "spc" => b' ',
+        "tab" => b'\t',
+        _ if rest.len() == 1 => rest.as_bytes()[0],
+        _ => return None,
+    };
+    sequence.push(if ctrl { control(byte) } else { byte });
+    Some(sequence)
+}
+
+/** Return the control character of a key pressed with Ctrl */
+fn control(c: u8) -> u8 {
+    match c {
+        b'?' => 0x7F,
+        c => c.to_ascii_lowercase() & 0x1F,
+    }
+}
+
+/** Return the editing action of a readline function */
+pub(crate) fn action(function: &str) -> Option<EditAction> {
+    let action = match function.to_lowercase().as_str() {
+        "beginning-of-line" => EditAction::BeginningOfLine,
+        "end-of-line" => EditAction::EndOfLine,
+        "forward-char" => EditAction::ForwardChar,
+        "backward-char" => EditAction::BackwardChar,
+        "previous-history" => EditAction::PreviousHistory,
+        "next-history" => EditAction::NextHistory,
+        "reverse-search-history" => EditAction::ReverseSearchHistory,
+        "forward-search-history" => EditAction::ForwardSearchHistory,
+        "abort" => EditAction::Abort,
+        "yank-last-arg" | "insert-last-argument" => EditAction::YankLastArg,
+        "backward-delete-char" => EditAction::BackwardDeleteChar,
+        "delete-char" => EditAction::DeleteChar,
+        "transpose-chars" => EditAction::TransposeChars,
+        "transpose-words" => EditAction::TransposeWords,
+        "upcase-word" => EditAction::UpcaseWord,
+        "downcase-word" => EditAction::DowncaseWord,
+        "capitalize-word" => EditAction::CapitalizeWord,
+        "complete" => EditAction::Complete,
+        "accept-line" => EditAction::AcceptLine,
+        "end-of-file" => EditAction::EndOfFile,
+        "edit-and-execute-command" => EditAction::EditInEditor,
+        "quoted-insert" => EditAction::QuotedInsert,
+        "start-kbd-macro" => EditAction::StartKbdMacro,
+        "end-kbd-macro" => EditAction::EndKbdMacro,
+        "call-last-kbd-macro" => EditAction::CallLastKbdMacro,
+        _ => {
+            return None;
+        }
+    };
+    Some(action)
+}
@@ -0,0 +1,573 @@
+//! Decoding of the terminal input into keys and editing actions.
+use crate::MAX_LINE_LENGTH;
+
+/** How the terminal reports keys pressed with the Meta (Alt) modifier */
+#[derive(Clone, Copy)]
+pub enum MetaKey {
+    /**
+     * The key is prefixed with ESC (e.g. `ESC h` for Alt+H). The other bytes with their 8th
+     * bit set are decoded as UTF-8 multi-bytes characters.
+     */
+    Escape,
+    /**
+     * The key is sent with its 8th bit set (e.g. `0xE8` for Alt+H).
+     * ESC-prefixed keys are still recognized, but no character beyond ASCII can be typed.
+     */
+    EightBit,
+    /**
+     * Detected from the locale: [`MetaKey::Escape`] if the character set is UTF-8,
+     * [`MetaKey::EightBit`] otherwise.
+     */
+    Auto,
+}
+
+impl MetaKey {
+    /** Return true if the 8th bit of input bytes denotes the Meta modifier */
+    fn eight_bit(&self) -> bool {
+        match self {
+            Self::Escape => false,
+            Self::EightBit => true,
+            Self::Auto => {
+                let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+                    .iter()
+                    .filter_map(|var| std::env::var(var).ok())
+                    .find(|value| !value.is_empty())
+                    .unwrap_or_default()
+                    .to_uppercase();
+                !locale.contains("UTF-8") && !locale.contains("UTF8")
+            }
+        }
+    }
+}
+
+/** What the Tab key does, when it is not bound to a custom action */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum TabKey {
+    /** Auto-complete the word under the cursor. */
+    Complete,
+    /** Insert a literal tab character, displayed as `^I`. */
+    Literal,
+    /** Insert the provided number of spaces. */
+    Spaces(usize),
+}
+
+/** An editing action performed on the command line */
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EditAction {
+    /** Insert a character at the cursor position. */
+    Insert(char),
+    /** Move the cursor to the beginning of the line. */
+    BeginningOfLine,
+    /** Move the cursor to the end of the line. */
+    EndOfLine,
+    /** Move the cursor forward by one character. */
+    ForwardChar,
+    /** Move the cursor backward by one character. */
+    BackwardChar,
+    /** Recall the previous entry of the history. */
+    PreviousHistory,
+    /** Recall the next entry of the history. */
+    NextHistory,
+    /** Toggle the history scope between all entries and the current session entries. */
+    ToggleHistoryScope,
+    /** Search the history backward incrementally, or find the previous match. */
+    ReverseSearchHistory,
+    /** Search the history forward incrementally, or find the next match. */
+    ForwardSearchHistory,
+    /** Cancel the history search, restoring the command line. */
+    Abort,
+    /**
+     * Insert the last argument of the previous history entry. Repeating it replaces the
+     * inserted argument with the one of the entry before.
+     */
+    YankLastArg,
+    /** Delete the character before the cursor. */
+    BackwardDeleteChar,
+    /** Delete the character under the cursor. */
+    DeleteChar,
+    /**
+     * Swap the character before the cursor with the one under the cursor, or the last two
+     * characters at the end of the line.
+     */
+    TransposeChars,
+    /**
+     * Swap the word before the cursor with the word after it (or under it), or the last two
+     * words at the end of the line.
+     */
+    TransposeWords,
+    /** Convert the word from the cursor to uppercase, and move the cursor after it. */
+    UpcaseWord,
+    /** Convert the word from the cursor to lowercase, and move the cursor after it. */
+    DowncaseWord,
+    /**
+     * Capitalize the word from the cursor (first character in uppercase, the others in
+     * lowercase), and move the cursor after it.
+     */
+    CapitalizeWord,
+    /** Auto-complete the word under the cursor. */
+    Complete,
+    /** Submit the command line. */
+    AcceptLine,
+    /** Stop reading actions if the command line is empty. */
+    EndOfFile,
+    /** Hold or release the messages printed by other tasks (do-not-disturb mode). */
+    ToggleDoNotDisturb,
+    /**
+     * Edit the command line with the editor of the user ($VISUAL or $EDITOR), only on the
+     * terminal of the process (see [`crate::Terminal::local`]).
+     */
+    EditInEditor,
+    /**
+     * Insert the next byte received literally (e.g. a Tab or an ESC), instead of decoding
+     * it as a key.
+     */
+    QuotedInsert,
+    /** Start recording the keys typed in a keyboard macro. */
+    StartKbdMacro,
+    /** Stop recording the keyboard macro. */
+    EndKbdMacro,
+    /** Replay the keys of the last keyboard macro recorded. */
+    CallLastKbdMacro,
+}
+
+/** A key decoded from the terminal input */
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum KeyEvent {
+    /** A printable character. */
+    Char(char),
+    /** A letter (in lowercase) or a symbol pressed with Ctrl (e.g. `Ctrl('x')`). */
+    Ctrl(char),
+    /** A character pressed with Alt (Meta). */
+    Alt(char),
+    /** The Enter key. */
+    Enter,
+    /** The Escape key. */
+    Escape,
+    /** The Tab key. */
+    Tab,
+    /** The Backspace key. */
+    Backspace,
+    /** The Up arrow. */
+    Up,
+    /** The Down arrow. */
+    Down,
+    /** The Right arrow. */
+    Right,
+    /** The Left arrow. */
+    Left,
+    /** The Home key. */
+    Home,
+    /** The End key. */
+    End,
+    /** The Insert key. */
+    Insert,
+    /** The Delete key. */
+    Delete,
+    /** The Page Up key. */
+    PageUp,
+    /** The Page Down key. */
+    PageDown,
+    /** A function key, from F1 to F12. */
+    F(u8),
+    /** An escape sequence which is not recognized, as received. */
+    Unknown(Vec<u8>),
+}
+
+impl KeyEvent {
+    /** Return the editing action bound to this key by default */
+    pub(crate) fn action(&self) -> Option<EditAction> {
+        let action = match self {
+            Self::Char(c) => EditAction::Insert(*c),
+            Self::Ctrl('a') | Self::Ctrl('b') | Self::Home => EditAction::BeginningOfLine,
+            Self::End => EditAction::EndOfLine,
+            Self::Ctrl('d') => EditAction::EndOfFile,
+            Self::Ctrl('g') => EditAction::Abort,
+            Self::Ctrl('r') => EditAction::ReverseSearchHistory,
+            Self::Ctrl('s') => EditAction::ForwardSearchHistory,
+            Self::Ctrl('t') => EditAction::TransposeChars,
+            Self::Ctrl('v') => EditAction::QuotedInsert,
+            Self::Alt('t') => EditAction::TransposeWords,
+            Self::Alt('u') => EditAction::UpcaseWord,
+            Self::Alt('l') => EditAction::DowncaseWord,
+            Self::Alt('c') => EditAction::CapitalizeWord,
+            Self::Alt('h') => EditAction::ToggleHistoryScope,
+            Self::Alt('.') | Self::Alt('_') => EditAction::YankLastArg,
+            Self::Enter => EditAction::AcceptLine,
+            Self::Tab => EditAction::Complete,
+            Self::Backspace => EditAction::BackwardDeleteChar,
+            Self::Delete => EditAction::DeleteChar,
+            Self::Up => EditAction::PreviousHistory,
+            Self::Down => EditAction::NextHistory,
+            Self::Right => EditAction::ForwardChar,
+            Self::Left => EditAction::BackwardChar,
+            _ => {
+                return None;
+            }
+        };
+        Some(action)
+    }
+}
+
+/** Result of the decoding of the terminal input */
+#[derive(Clone)]
+pub(crate) enum Decoded {
+    /** A key was decoded. */
+    Key(KeyEvent),
+    /** A custom sequence bound to an editing action was decoded. */
+    Action(EditAction),
+    /** The terminal reported the position of the cursor (Row, Column), starting at 1. */
+    CursorPosition(usize, usize),
+    /** An unknown escape sequence was received. */
+    Unknown(Vec<u8>),
+    /** Text pasted in bracketed paste mode. */
+    Paste(String),
+}
+
+impl Decoded {
+    /** Return the editing action of what was decoded, if any */
+    pub fn action(&self) -> Option<EditAction> {
+        match self {
+            Self::Action(action) => Some(action.clone()),
+            Self::Key(key) => key.action(),
+            _ => None,
+        }
+    }
+}
+
+/** Result of the parsing of standard sequences */
+enum Parse {
+    Incomplete,
+    Key(KeyEvent),
+    PasteStart,
+    CursorPosition(usize, usize),
+    Unknown,
+}
+
+/** Decode the terminal input byte per byte */
+pub(crate) struct Decoder {
+    sequences: Vec<(Vec<u8>, EditAction)>,
+    pending: Vec<u8>,
+    eight_bit_meta: bool,
+    /** Text being pasted, until the end of the bracketed paste */
+    paste: Option<Vec<u8>>,
+    /** The next character is inserted literally, following a quoted insert */
+    quoted: bool,
+    tab: TabKey,
+    /** Maximum length of pasted text, in bytes: the exceeding bytes are discarded */
+    paste_limit: usize,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self {
+            sequences: vec![
+                // Ctrl+X Ctrl+E
+                (b"\x18\x05".to_vec(), EditAction::EditInEditor),
+                // Ctrl+X (, Ctrl+X ) and Ctrl+X e
+                (b"\x18(".to_vec(), EditAction::StartKbdMacro),
+                (b"\x18)".to_vec(), EditAction::EndKbdMacro),
+                (b"\x18e".to_vec(), EditAction::CallLastKbdMacro),
+            ],
+            pending: Vec::new(),
+            eight_bit_meta: false,
+            paste: None,
+            quoted: false,
+            tab: TabKey::Complete,
+            paste_limit: MAX_LINE_LENGTH * 4,
+        }
+    }
+
+    /** Configure how keys pressed with the Meta modifier are reported */
+    pub fn setmeta(&mut self, meta: MetaKey) {
+        self.eight_bit_meta = meta.eight_bit();
+    }
+
+    /** Configure what the Tab key does */
+    pub fn settab(&mut self, tab: TabKey) {
+        self.tab = tab;
+    }
+
+    /** Set the maximum length of pasted text, in bytes */
+    pub fn setpastelimit(&mut self, limit: usize) {
+        self.paste_limit = limit;
+    }
+
+    /** Bind a custom sequence to an editing action, taking precedence over standard sequences */
+    pub fn bind(&mut self, sequence: &[u8], action: EditAction) {
+        self.sequences.retain(|(seq, _)| seq != sequence);
+        self.sequences.push((sequence.to_vec(), action));
+    }
+
+    /** Return true if the decoder is waiting for the byte following an ESC */
+    pub fn escape_pending(&self) -> bool {
+        self.pending == [0x1B]
+    }
+
+    /** Return true if the decoder is waiting for the next key of a bound chord */
+    pub fn chord_pending(&self) -> bool {
+        !self.pending.is_empty()
+            && !matches!(parse(&self.pending, self.eight_bit_meta), Parse::Incomplete)
+            && self
+                .sequences
+                .iter()
+                .any(|(seq, _)| seq.starts_with(&self.pending))
+    }
+
+    /** Discard the keys of a chord not completed in time */
+    pub fn cancel_chord(&mut self) {
+        self.pending.clear();
+    }
+
+    /** Report a pending ESC, not followed by any byte in time, as the Escape key */
+    pub fn escape(&mut self) -> KeyEvent {
+        self.pending.clear();
+        KeyEvent::Escape
+    }
+
+    /** Feed the decoder with one byte, and return what was decoded, if anything */
+    pub fn feed(&mut self, byte: u8) -> Option<Decoded> {
+        if self.quoted {
+            self.pending.push(byte);
+            let c = match self.pending.as_slice() {
+                [c] if *c < 0x80 || self.eight_bit_meta => *c as char,
+                bytes => match utf8(bytes) {
+                    Parse::Incomplete => return None,
+                    Parse::Key(KeyEvent::Char(c)) => c,
+                    _ => char::REPLACEMENT_CHARACTER,
+                },
+            };
+            self.pending.clear();
+            self.quoted = false;
+            return Some(Decoded::Action(EditAction::Insert(c)));
+        }
+        let decoded = self.decode(byte);
+        self.quoted = decoded
+            .as_ref()
+            .is_some_and(|decoded| decoded.action() == Some(EditAction::QuotedInsert));
+        decoded
+    }
+
+    /** Decode one byte, as keys and bound sequences */
+    fn decode(&mut self, byte: u8) -> Option<Decoded> {
+        if let Some(paste) = &mut self.paste {
+            paste.push(byte);
+            if paste.len() > self.paste_limit + PASTE_END.len() {
+                // Keep looking for the end of the paste, without storing the exceeding bytes
+                paste.remove(self.paste_limit);
+            }
+            let text = paste.strip_suffix(PASTE_END)?;
+            let text = String::from_utf8_lossy(text).into_owned();
+            self.paste = None;
+            return Some(Decoded::Paste(text));
+        }
+        self.pending.push(byte);
+
+        if let Some((_, action)) = self.sequences.iter().find(|(seq, _)| *seq == self.pending) {
+            let action = action.clone();
+            self.pending.clear();
+            return Some(Decoded::Action(action));
+        }
+        if self
+            .sequences
+            .iter()
+            .any(|(seq, _)| seq.starts_with(&self.pending))
+        {
+            return None;
+        }
+
+        let parsed = parse(&self.pending, self.eight_bit_meta);
+        if let (Parse::Unknown, [prefix @ .., _]) = (&parsed, self.pending.as_slice()) {
+            if !prefix.is_empty()
+                && self
+                    .sequences
+                    .iter()
+                    .any(|(seq, _)| seq.starts_with(prefix))
+            {
+                // The key completes no bound chord: the chord is discarded
+                self.pending.clear();
+                return self.decode(byte);
+            }
+        }
+
+        match parsed {
+            Parse::Incomplete => None,
+            Parse::Key(KeyEvent::Tab) if self.tab != TabKey::Complete => {
+                self.pending.clear();
+                match self.tab {
+                    TabKey::Spaces(count) => Some(Decoded::Paste(" ".repeat(count))),
+                    _ => Some(Decoded::Action(EditAction::Insert('\t'))),
+                }
+            }
+            Parse::Key(key) => {
+                self.pending.clear();
+                Some(Decoded::Key(key))
+            }
+            Parse::PasteStart => {
+                self.pending.clear();
+                self.paste = Some(Vec::new());
+                None
+            }
+            Parse::CursorPosition(row, col) => {
+                self.pending.clear();
+                Some(Decoded::CursorPosition(row, col))
+            }
+            Parse::Unknown => Some(Decoded::Unknown(std::mem::take(&mut self.pending))),
+        }
+    }
+}
+
+/** Sequence ending a bracketed paste */
+const PASTE_END: &[u8] = b"\x1B[201~";
+
+/** Parse standard sequences */
+fn parse(bytes: &[u8], eight_bit_meta: bool) -> Parse {
+    match bytes {
+        [0x1B] | [0x1B, b'['] | [0x1B, b'O'] => Parse::Incomplete,
+        [0x1B, b'[', rest @ ..] => csi(rest),
+        [0x1B, b'O', c] => ss3(*c),
+        [0x1B, c, ..] if !eight_bit_meta && *c >= 0x80 => match utf8(&bytes[1..]) {
+            Parse::Key(KeyEvent::Char(c)) => Parse::Key(KeyEvent::Alt(c)),
+            parsed => parsed,
+        },
+        [0x1B, c] => Parse::Key(KeyEvent::Alt(*c as char)),
+        [c] if eight_bit_meta && *c >= 0x80 => Parse::Key(KeyEvent::Alt((*c & 0x7F) as char)),
+        [c] if *c < 0x80 => Parse::Key(byte(*c)),
+        [c, ..] if !eight_bit_meta && *c >= 0x80 => utf8(bytes),
+        _ => Parse::Unknown,
+    }
+}
+
+/** Parse the bytes of a UTF-8 encoded character */
+fn utf8(bytes: &[u8]) -> Parse {
+    let len = match bytes[0] {
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => {
+            return Parse::Unknown;
+        }
+    };
+    if !bytes[1..].iter().all(|b| b & 0xC0 == 0x80) || bytes.len() > len {
+        return Parse::Unknown;
+    }
+    if bytes.len() < len {
+        return Parse::Incomplete;
+    }
+    match std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| s.chars().next())
+    {
+        Some(c) => Parse::Key(KeyEvent::Char(c)),
+        None => Parse::Unknown,
+    }
+}
+
+/** Parse a single byte */
+fn byte(c: u8) -> KeyEvent {
+    match c {
+        b'\t' => KeyEvent::Tab,
+        b'\n' | b'\r' => KeyEvent::Enter,
+        0x7F => KeyEvent::Backspace,
+        0x00..=0x1F => KeyEvent::Ctrl((c + 0x60) as char),
+        _ => KeyEvent::Char(c as char),
+    }
+}
+
+/** Parse a Control Sequence Introducer (ESC [) sequence */
+fn csi(bytes: &[u8]) -> Parse {
+    let (last, params) = match bytes.split_last() {
+        Some((last, params)) => (*last, params),
+        None => {
+            return Parse::Incomplete;
+        }
+    };
+    match last {
+        // Parameter and intermediate bytes
+        0x20..=0x3F => {
+            return Parse::Incomplete;
+        }
+        // Final byte
+        0x40..=0x7E => {}
+        _ => {
+            return Parse::Unknown;
+        }
+    }
+
+    let key = match (params, last) {
+        (_, b'A') => KeyEvent::Up,
+        (_, b'B') => KeyEvent::Down,
+        (_, b'C') => KeyEvent::Right,
+        (_, b'D') => KeyEvent::Left,
+        (_, b'H') => KeyEvent::Home,
+        (_, b'F') => KeyEvent::End,
+        (b"1" | b"7", b'~') => KeyEvent::Home,
+        (b"2", b'~') => KeyEvent::Insert,
+        (b"3", b'~') => KeyEvent::Delete,
+        (b"4" | b"8", b'~') => KeyEvent::End,
+        (b"5", b'~') => KeyEvent::PageUp,
+        (b"6", b'~') => KeyEvent::PageDown,
+        (b"11", b'~') => KeyEvent::F(1),
+        (b"12", b'~') => KeyEvent::F(2),
+        (b"13", b'~') => KeyEvent::F(3),
+        (b"14", b'~') => KeyEvent::F(4),
+        (b"15", b'~') => KeyEvent::F(5),
+        (b"17", b'~') => KeyEvent::F(6),
+        (b"18", b'~') => KeyEvent::F(7),
+        (b"19", b'~') => KeyEvent::F(8),
+        (b"20", b'~') => KeyEvent::F(9),
+        (b"21", b'~') => KeyEvent::F(10),
+        (b"23", b'~') => KeyEvent::F(11),
+        (b"24", b'~') => KeyEvent::F(12),
+        (b"200", b'~') => {
+            return Parse::PasteStart;
+        }
+        (_, b'R') => {
+            // Cursor Position Report: ESC [ row ; col R
+            let position = std::str::from_utf8(params)
+                .ok()
+                .and_then(|params| params.split_once(';'))
+                .and_then(|(row, col)| Some((row.parse().ok()?, col.parse().ok()?)));
+            return match position {
+                Some((row, col)) => Parse::CursorPosition(row, col),
+                None => Parse::Unknown,
+            };
+        }
+        _ => {
+            return Parse::Unknown;
+        }
+    };
+    Parse::Key(key)
+}
+
+/**
+ * Parse a Single Shift Three (ESC O) sequence.
+ *
+ * These sequences are sent for cursor keys and keypad keys in application mode.
+ */
+fn ss3(c: u8) -> Parse {
+    let key = match c {
+        b'A' => KeyEvent::Up,
+        b'B' => KeyEvent::Down,
+        b'C' => KeyEvent::Right,
+        b'D' => KeyEvent::Left,
+        b'H' => KeyEvent::Home,
+        b'F' => KeyEvent::End,
+        b'P' => KeyEvent::F(1),
+        b'Q' => KeyEvent::F(2),
+        b'R' => KeyEvent::F(3),
+        b'S' => KeyEvent::F(4),
+        b'M' => KeyEvent::Enter,
+        b'X' => KeyEvent::Char('='),
+        // Keypad: * + , - . / 0-9
+        b'j'..=b'y' => KeyEvent::Char((c - b'j' + b'*') as char),
+        _ => {
+            return Parse::Unknown;
+        }
+    };
+    Parse::Key(key)
+}
@@ -4,18 +4,170 @@
 //! interactive Command Line Interface in an Unix spirit.
 //!
 //! APIs are async and thus can be easily integrated in a Tokio crate.
-use eyre::Result;
-use termios::*;
-use tokio::io::{stdin, AsyncReadExt, BufReader, Stdin};
+pub use altscreen::AltScreen;
+pub use builder::CliBuilder;
+#[cfg(feature = "clap")]
+pub use clapadapter::ClapAdapter;
+pub use compat::Editor;
+pub use completion::{edit_distance, Completer, FileCompleter, Matcher};
+#[cfg(feature = "config")]
+pub use config::{CliConfig, ColorsConfig, CompletionConfig, HistoryConfig};
+use countdown::{Countdown, OnExpire};
+pub use editor::LineEditor;
+pub use error::{BoxError, Error, Result};
+pub use highlight::Highlighter;
+pub use hint::Hinter;
+use history::History;
+pub use history::{HistoryEntry, HistorySave, HistoryScope};
+use inputrc::Directive;
+use keys::{Decoded, Decoder};
+pub use keys::{EditAction, KeyEvent, MetaKey, TabKey};
+pub use mock::MockTerminal;
+pub use plugin::{LineBuffer, Plugin};
+pub use printer::{Capture, Printer, PrinterWriter};
+use printer::{Screen, SharedScreen};
+pub use progress::Progress;
+use recorder::Recorder;
+pub use recorder::{RecordKind, RecordedEvent, Recording};
+pub use repl::{CommandFuture, Repl};
+use script::{Frame, Statement};
+use sensitive::{glob, wipe};
+#[cfg(unix)]
+pub use server::UnixServer;
+pub use spec::{ArgHint, CommandSpec};
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::ops::Range;
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+pub use stream::ActionStream;
+use style::{controls, display_width, strip_colors, text_width, wrap};
+pub use style::{Color, ColorSupport, PromptBuilder, Style, Theme};
+pub use suspend::Suspend;
+use terminal::Output;
+#[cfg(unix)]
+use terminal::Tty;
+pub use terminal::{OutputTarget, Terminal};
+use tokenizer::is_varchar;
+pub use tokenizer::{DefaultTokenizer, ShellTokenizer, Tokenizer};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+pub use validation::{IncompletePolicy, Validation, Validator};
+
+/** Write to the output of the terminal, like eprint! */
+macro_rules! out {
+    ($output:expr, $($arg:tt)*) => {
+        $output.write(true, format_args!($($arg)*))
+    };
+}
+
+/** Write to the output of the terminal, like eprintln! */
+macro_rules! outln {
+    ($output:expr) => {
+        $output.write(true, format_args!("\n"))
+    };
+    ($output:expr, $($arg:tt)*) => {
+        $output.write(true, format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+mod altscreen;
+mod builder;
+#[cfg(feature = "clap")]
+mod clapadapter;
+mod compat;
+mod completion;
+#[cfg(feature = "config")]
+mod config;
+mod countdown;
+mod dialog;
+mod editor;
+mod error;
+mod highlight;
+mod hint;
+mod history;
+mod inputrc;
+mod keys;
+mod mock;
+mod plugin;
+mod printer;
+mod progress;
+mod recorder;
+mod repl;
+mod script;
+mod sensitive;
+#[cfg(unix)]
+mod server;
+mod spec;
+mod stream;
+mod style;
+mod suspend;
+mod terminal;
+mod tokenizer;
+mod validation;
 
 /** An Action performed by the user: execute a command or auto-complete the current command. */
 pub enum Action {
-    /** User demand to execute the following command (Command Name + Arguments). */
-    Command(Vec<String>),
-    /** User demand to auto-complete the following command (Command Name + Arguments). */
+    /** User demand to execute a command. */
+    Command {
+        /** Command line as entered, for applications parsing it by themselves. */
+        line: String,
+        /** Command Name + Arguments. */
+        args: Vec<String>,
+    },
+    /**
+     * User demand to auto-complete the following command (Command Name + Arguments).
+     *
+     * Only the arguments before the cursor are reported: the last argument is the beginning of
+     * the word under the cursor, which is the word to auto-complete.
+     */
     AutoComplete(Vec<String>),
     /** getaction stopped without any actions to report (e.g. EOT was received, on an empty line). */
     NoAction,
+    /**
+     * A key bound to no editing action was pressed (e.g. F5), for the application to
+     * implement its own shortcuts. The command line being edited is kept.
+     */
+    Key(KeyEvent),
+    /**
+     * No input was received within the timeout of [`Cli::getaction_timeout`]. The command
+     * line being edited is kept.
+     */
+    Timeout,
+}
+
+/** How the user is notified of an ambiguous or impossible completion */
+pub enum Bell {
+    /** Do not notify the user. */
+    None,
+    /** Ring the terminal bell (BEL character). */
+    Audible,
+    /** Call the provided function. */
+    Custom(Box<dyn Fn() + Send>),
+}
+
+/** A prompt evaluated each time it is displayed for a new command */
+pub trait Prompt {
+    /** Return the text of the prompt */
+    fn prompt(&self) -> String;
+}
+
+impl Prompt for String {
+    fn prompt(&self) -> String {
+        self.clone()
+    }
+}
+
+impl<F: Fn() -> String> Prompt for F {
+    fn prompt(&self) -> String {
+        self()
+    }
 }
 
 /** Human-readable ANSI Escape Sequences */
@@ -30,6 +182,19 @@ enum EscSeq {
     EraseInLineFromCursorToEnd,
     EraseInLineFromCursorToBegining,
     EraseInLineAll,
+    Dim,
+    ReverseVideo,
+    ResetAttributes,
+    KeypadTransmit,
+    KeypadLocal,
+    BracketedPasteOn,
+    BracketedPasteOff,
+    DeviceStatusReport,
+    AltScreenOn,
+    AltScreenOff,
+    CursorHome,
+    HideCursor,
+    ShowCursor,
 }
 
 impl std::fmt::Display for EscSeq {
@@ -44,20 +209,177 @@ impl std::fmt::Display for EscSeq {
             Self::EraseInLineFromCursorToEnd => write!(f, "\x1B[0K"),
             Self::EraseInLineFromCursorToBegining => write!(f, "\x1B[1K"),
             Self::EraseInLineAll => write!(f, "\x1B[2K"),
+            Self::Dim => write!(f, "\x1B[2m"),
+            Self::ReverseVideo => write!(f, "\x1B[7m"),
+            Self::ResetAttributes => write!(f, "\x1B[0m"),
+            Self::KeypadTransmit => write!(f, "\x1B[?1h\x1B="),
+            Self::KeypadLocal => write!(f, "\x1B[?1l\x1B>"),
+            Self::BracketedPasteOn => write!(f, "\x1B[?2004h"),
+            Self::BracketedPasteOff => write!(f, "\x1B[?2004l"),
+            Self::DeviceStatusReport => write!(f, "\x1B[6n"),
+            Self::AltScreenOn => write!(f, "\x1B[?1049h"),
+            Self::AltScreenOff => write!(f, "\x1B[?1049l"),
+            Self::CursorHome => write!(f, "\x1B[H"),
+            Self::HideCursor => write!(f, "\x1B[?25l"),
+            Self::ShowCursor => write!(f, "\x1B[?25h"),
         }
     }
 }
 
+/** Maximum time to wait for the terminal to report the cursor position */
+const CURSOR_SYNC_TIMEOUT: Duration = Duration::from_millis(100);
+
+/** Commands handled by the Cli itself when variables are enabled */
+const BUILTINS: [&str; 3] = ["echo", "set", "unset"];
+
+/** Default time to wait for the next key of a chord */
+const CHORD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/** Maximum time to wait for the byte following an ESC, before reporting the Escape key */
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/** Maximum depth of the files included by an inputrc file */
+const INPUTRC_DEPTH: usize = 8;
+
+/** Default maximum length of the command line, in characters */
+const MAX_LINE_LENGTH: usize = 65536;
+
+/** Maximum number of candidates displayed at once by the completion menu */
+const MENU_ROWS: usize = 10;
+
+/** Default maximum number of commands queued by [`Cli::run`] */
+const QUEUE_LIMIT: usize = 10;
+
+/** State of the interactive completion menu */
+struct Menu {
+    /** Possible words for the current input */
+    words: Vec<String>,
+    /** Index of the word currently inserted in the command line */
+    selected: Option<usize>,
+    /** Command line, as it was before any candidate was inserted */
+    anchor: String,
+    /** Cursor position in the command line where candidates are inserted */
+    position: usize,
+    /** Word typed before the cursor when candidates are inserted */
+    typed: String,
+    /** Number of rows currently rendered below the prompt */
+    rows: usize,
+}
+
+/** State of the incremental history search */
+struct Search {
+    /** Text searched in the history entries */
+    query: String,
+    /** Search towards the most recent entries */
+    forward: bool,
+    /** Tell if no entry matches the query */
+    failed: bool,
+    /** Command line, cursor and history index when the search started */
+    saved: (String, usize, Option<usize>),
+}
+
+/** A context of the Cli saved by [`Cli::push_context`], restored by [`Cli::pop_context`] */
+struct Context {
+    prompt: Box<dyn Prompt + Send>,
+    commands: Vec<String>,
+    completer: Option<Box<dyn Completer + Send>>,
+    /** History of the context, if the nested context has its own history */
+    history: Option<History>,
+}
+
+/** State of the completion pager */
+enum Pager {
+    /** Waiting for the user to confirm the display of all the lines */
+    Query(Vec<String>),
+    /** Waiting for the user to display more lines, from the provided index */
+    More(Vec<String>, usize),
+}
+
+/** Function restoring the terminal, called by the panic hook while the Cli is alive */
+type PanicRestorer = Arc<Mutex<Option<Box<dyn Fn() + Send>>>>;
+
 /** Provide APIs to interact with the Command Line Interface */
 pub struct Cli {
-    saved_termios: Termios,
-    reader: BufReader<Stdin>,
+    terminal: Box<dyn Terminal>,
+    panic_restorer: Option<PanicRestorer>,
+    /** Tell if the terminal is configured in character mode */
+    raw: bool,
+    output: Output,
+    reader: BufReader<Box<dyn AsyncRead + Send + Unpin>>,
+    decoder: Decoder,
+    chord_timeout: Duration,
+    /** Maximum length of the command line, in characters */
+    max_line: usize,
+    /** Glob patterns of the command lines kept out of the history and of the recordings */
+    sensitive: Vec<String>,
     do_reset: bool,
-    prompt: String,
-    cmd: String,
-    cursor: usize,
-    history: Vec<String>,
-    history_idx: Option<usize>,
+    prompt: Box<dyn Prompt + Send>,
+    prompt_text: String,
+    rprompt: Option<Box<dyn Prompt + Send>>,
+    rprompt_text: String,
+    rprompt_visible: Cell<bool>,
+    editor: LineEditor,
+    pending: String,
+    continuation_prompt: String,
+    history_expansion: bool,
+    comments: bool,
+    completion_menu: bool,
+    history_indicator: bool,
+    completion_sort: bool,
+    matcher: Matcher,
+    menu_threshold: usize,
+    tab_pending: bool,
+    /** History entry and range of the last argument inserted by the previous Alt+. */
+    yank_last: Option<(usize, Range<usize>)>,
+    bell: Bell,
+    commands: Vec<String>,
+    completer: Option<Box<dyn Completer + Send>>,
+    contexts: Vec<Context>,
+    validator: Option<Box<dyn Validator + Send>>,
+    incomplete_policy: IncompletePolicy,
+    menu: Option<Menu>,
+    completion_query: usize,
+    plugins: Vec<Box<dyn Plugin + Send>>,
+    /** The command line last seen by the plugins, to report its changes */
+    plugins_line: String,
+    keypad_mode: bool,
+    bracketed_paste: bool,
+    pager: Option<Pager>,
+    search: Option<Search>,
+    search_wraparound: bool,
+    variables: BTreeMap<String, String>,
+    aliases: BTreeMap<String, String>,
+    abbreviations: BTreeMap<String, String>,
+    variables_enabled: bool,
+    environment: bool,
+    status: Option<i32>,
+    script: Vec<Frame>,
+    script_echo: bool,
+    highlighter: Option<Box<dyn Highlighter + Send>>,
+    hinter: Option<Box<dyn Hinter + Send>>,
+    transient_prompt: Option<String>,
+    theme: Theme,
+    color_support: ColorSupport,
+    /** Tell if the command line was submitted: it is repainted without the hint */
+    submitted: bool,
+    tokenizer: Option<Box<dyn Tokenizer + Send>>,
+    typeahead: VecDeque<Decoded>,
+    /** The keys of the keyboard macro being recorded */
+    kbd_recording: Option<Vec<Decoded>>,
+    /** The keys of the last keyboard macro recorded */
+    kbd_macro: Vec<Decoded>,
+    /** Input injected by the application, decoded as if typed on the terminal */
+    injector: UnboundedSender<Vec<u8>>,
+    injected: UnboundedReceiver<Vec<u8>>,
+    responsive: Option<bool>,
+    cursor_sync: bool,
+    screen: SharedScreen,
+    softwrap: bool,
+    wrap_marker: String,
+    wrap_rows: Cell<(usize, usize)>,
+    countdowns: Vec<Countdown>,
+    tick: tokio::time::Instant,
+    queue_limit: usize,
 }
 
 impl Cli {
@@ -65,348 +387,3315 @@ impl Cli {
      * Create a new Command Line Interface.
      *
      * Note that it configures the terminal in character mode.
+     *
+     * Keys are read from the standard input if it is a terminal. Otherwise (e.g. data piped
+     * into the application), keys are read from the controlling terminal (/dev/tty) so the
+     * standard input remains available to the application. On Windows, the standard input
+     * must be a console.
+     *
+     * The readline configuration file of the user is not loaded: see
+     * [`Cli::load_user_inputrc`].
      */
     pub fn new() -> Result<Self> {
-        let fd = 0;
-        let saved = Termios::from_fd(fd)?;
-        let mut termios = saved;
-        termios.c_lflag &= !(ECHO | ECHONL | ICANON);
-        tcsetattr(fd, TCSANOW, &termios)?;
+        let (reader, terminal) = terminal::open()?;
+        Self::from_parts(reader, Output::default(), terminal)
+    }
+
+    /**
+     * Create a Command Line Interface reading keys from `reader` and writing to `writer`,
+     * e.g. a telnet socket, an SSH channel or a PTY.
+     *
+     * The `terminal` is configured in character mode, and back in its original mode when
+     * the Cli is dropped. Note that the writer is driven by a spawned task: this function
+     * must be called from a Tokio runtime.
+     */
+    pub fn with_backend(
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+        terminal: impl Terminal + 'static,
+    ) -> Result<Self> {
+        let output = Output::spawn(writer, true);
+        Self::from_parts(Box::new(reader), output, Box::new(terminal))
+    }
+
+    /**
+     * Create a Command Line Interface on a terminal device other than the one of the
+     * process, e.g. the slave side of a PTY.
+     *
+     * Only the settings of this terminal are changed, so several Cli can run on different
+     * terminals in one process. Note that the writer is driven by a spawned task: this
+     * function must be called from a Tokio runtime.
+     */
+    #[cfg(unix)]
+    pub fn with_tty(tty: std::fs::File) -> Result<Self> {
+        let reader = tokio::fs::File::from_std(tty.try_clone()?);
+        let writer = tokio::fs::File::from_std(tty.try_clone()?);
+        let terminal = Tty::new(tty.as_raw_fd(), Some(tty), false)?;
+        let output = Output::spawn(writer, false);
+        Self::from_parts(Box::new(reader), output, Box::new(terminal))
+    }
+
+    fn from_parts(
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        output: Output,
+        mut terminal: Box<dyn Terminal>,
+    ) -> Result<Self> {
+        terminal.raw()?;
+        let screen = SharedScreen::default();
+        printer::lock(&screen).output = output.clone();
+        let (injector, injected) = unbounded_channel();
 
         Ok(Self {
-            saved_termios: saved,
-            reader: BufReader::new(stdin()),
+            terminal,
+            panic_restorer: None,
+            raw: true,
+            output,
+            reader: BufReader::new(reader),
+            decoder: Decoder::new(),
+            chord_timeout: CHORD_TIMEOUT,
+            max_line: MAX_LINE_LENGTH,
+            sensitive: Vec::new(),
             do_reset: true,
-            prompt: String::from("> "),
-            cmd: String::new(),
-            cursor: 0,
-            history: Vec::<String>::new(),
-            history_idx: None,
+            prompt: Box::new(String::from("> ")),
+            prompt_text: String::from("> "),
+            rprompt: None,
+            rprompt_text: String::new(),
+            rprompt_visible: Cell::new(false),
+            editor: LineEditor::new(),
+            pending: String::new(),
+            continuation_prompt: String::from("... "),
+            history_expansion: false,
+            comments: false,
+            completion_menu: false,
+            history_indicator: false,
+            completion_sort: false,
+            matcher: Matcher::Prefix,
+            menu_threshold: usize::MAX,
+            tab_pending: false,
+            yank_last: None,
+            bell: Bell::Audible,
+            commands: Vec::<String>::new(),
+            completer: None,
+            contexts: Vec::new(),
+            validator: None,
+            incomplete_policy: IncompletePolicy::Submit,
+            menu: None,
+            completion_query: 100,
+            plugins: Vec::new(),
+            plugins_line: String::new(),
+            keypad_mode: false,
+            bracketed_paste: false,
+            pager: None,
+            search: None,
+            search_wraparound: false,
+            variables: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            abbreviations: BTreeMap::new(),
+            variables_enabled: false,
+            environment: false,
+            status: None,
+            script: Vec::new(),
+            script_echo: false,
+            highlighter: None,
+            hinter: None,
+            transient_prompt: None,
+            theme: Theme::default(),
+            color_support: ColorSupport::detect(),
+            submitted: false,
+            tokenizer: None,
+            typeahead: VecDeque::new(),
+            kbd_recording: None,
+            kbd_macro: Vec::new(),
+            injector,
+            injected,
+            responsive: None,
+            cursor_sync: false,
+            screen,
+            softwrap: false,
+            wrap_marker: String::from("\x1B[2m↪\x1B[0m "),
+            wrap_rows: Cell::new((0, 0)),
+            countdowns: Vec::new(),
+            tick: tokio::time::Instant::now(),
+            queue_limit: QUEUE_LIMIT,
         })
     }
 
+    /** Return a builder creating a Cli with its options */
+    pub fn builder() -> CliBuilder {
+        CliBuilder::new()
+    }
+
     fn cmd2args(&self) -> Vec<String> {
-        let mut args = Vec::<String>::new();
-        let mut arg = String::new();
-        let mut is_string = false;
-        let mut is_escaped = false;
-        for c in self.cmd.chars() {
-            if is_escaped {
-                arg.push(c);
-                is_escaped = false;
-                continue;
-            }
-            match c {
-                '\\' => {
-                    is_escaped = true;
-                }
-                '"' => {
-                    is_string = !is_string;
-                }
-                ' ' => {
-                    match is_string {
-                        true => {
-                            arg.push(c);
-                        }
-                        false => {
-                            args.push(arg.clone());
-                            arg.clear();
-                        }
-                    };
-                }
-                _ => {
-                    arg.push(c);
-                }
-            }
+        self.split(&self.editor.line, self.variables_enabled).0
+    }
+
+    /**
+     * Split the command line before the cursor in arguments, the last one being the word to
+     * complete, and tell if it ends inside a string
+     */
+    fn tokenize(&self, cmd: &str) -> (Vec<String>, bool) {
+        let (args, is_string) = self.with_tokenizer(|tokenizer| tokenizer.tokenize_partial(cmd));
+        (command_args(args), is_string)
+    }
+
+    /**
+     * Split a command line in arguments with the Tokenizer, optionally replacing the
+     * references to variables ($name or ${name}) by their values.
+     */
+    fn split(&self, cmd: &str, expand: bool) -> (Vec<String>, bool) {
+        let variables = expand.then(|| self.expansions());
+        let variables = variables.as_ref();
+        let (args, is_string) = self.with_tokenizer(|tokenizer| tokenizer.tokenize(cmd, variables));
+        (command_args(args), is_string)
+    }
+
+    /** Call a function with the Tokenizer of the Cli */
+    fn with_tokenizer<T>(&self, f: impl FnOnce(&dyn Tokenizer) -> T) -> T {
+        match &self.tokenizer {
+            Some(tokenizer) => f(tokenizer.as_ref()),
+            None => f(&DefaultTokenizer {
+                comments: self.comments,
+            }),
         }
-        args.push(arg);
-        args
     }
 
-    fn clear_line(&self) -> Result<()> {
-        eprint!("{}{}", EscSeq::EraseInLineAll, EscSeq::HorizontalAbs(0));
-        Ok(())
+    /**
+     * Return the values of the variables which can be referenced: the environment variables
+     * if enabled, overridden by the session variables, and the status of the last command.
+     */
+    fn expansions(&self) -> BTreeMap<String, String> {
+        let mut variables = BTreeMap::new();
+        if self.environment {
+            variables.extend(std::env::vars());
+        }
+        variables.extend(self.variables.clone());
+        if let Some(status) = self.status {
+            variables.insert("?".into(), status.to_string());
+        }
+        variables
     }
 
-    fn reset(&mut self) -> Result<()> {
-        self.cmd.clear();
-        self.cursor = 0;
-        self.history_idx = None;
-        eprint!("{}", self.prompt);
+    /**
+     * Quote a text to be appended to a command line, so it is parsed back as is by cmd2args.
+     *
+     * A completed word closes the string the command line ends inside, if any.
+     */
+    fn quote(&self, cmd: &str, text: &str, completed: bool) -> String {
+        self.with_tokenizer(|tokenizer| tokenizer.quote(cmd, text, completed))
+    }
+
+    fn clear_line(&self) -> Result<()> {
+        out!(
+            self.output,
+            "{}{}",
+            EscSeq::EraseInLineAll,
+            EscSeq::HorizontalAbs(0)
+        );
         Ok(())
     }
 
-    fn history_restore(&mut self) -> Result<()> {
-        let word = match self.history_idx {
-            Some(idx) => &self.history[idx],
-            None => {
-                return Ok(());
+    /** Return the prompt as displayed, with its optional decorations */
+    fn display_prompt(&self) -> String {
+        let transient = self.submitted && self.pending.is_empty();
+        if let Some(prompt) = self.transient_prompt.as_ref().filter(|_| transient) {
+            return self.styled(Style::new(), prompt);
+        }
+        if let Some(search) = &self.search {
+            return format!(
+                "({}{}i-search)`{}': ",
+                if search.failed { "failed " } else { "" },
+                if search.forward { "" } else { "reverse-" },
+                search.query
+            );
+        }
+        if !self.pending.is_empty() {
+            return self.styled(self.theme.prompt, &self.continuation_prompt);
+        }
+        let prompt = self.styled(self.theme.prompt, &self.prompt_text);
+        match self.editor.history_idx {
+            Some(idx) if self.history_indicator => {
+                format!("[{}] {}", self.editor.history.number(idx), prompt)
             }
-        };
+            _ => prompt,
+        }
+    }
 
-        self.cmd = word.clone();
-        self.cursor = match self.cmd.len() {
-            0 => 0,
-            len => len,
-        };
-        self.clear_line()?;
-        eprint!("{}{}", self.prompt, self.cmd);
+    /**
+     * Return the text styled, with the colors supported by the terminal. The colors of the
+     * text itself (e.g. of a prompt built with [`PromptBuilder`]) are removed if the
+     * terminal does not support colors.
+     */
+    fn styled(&self, style: Style, text: &str) -> String {
+        let style = style.downgrade(self.color_support);
+        match self.color_support {
+            ColorSupport::None => style.paint(&strip_colors(text)),
+            _ => style.paint(text),
+        }
+    }
 
-        Ok(())
+    /** Return the column of the cursor on the terminal, starting at 1 */
+    fn cursor_column(&self) -> usize {
+        display_width(&self.display_prompt())
+            + text_width(&self.editor.line[..self.editor.cursor])
+            + 1
     }
 
-    fn history_prev(&mut self) -> Result<()> {
-        self.history_idx = match self.history_idx {
-            Some(idx) => match idx {
-                0 => Some(idx),
-                idx => Some(idx - 1),
-            },
-            None => match self.history.len() {
-                0 => None,
-                idx => Some(idx - 1),
-            },
+    /** Return a text of the command line as displayed, control characters in caret notation */
+    fn display_text(&self, text: &str) -> String {
+        controls(text, self.theme.control.downgrade(self.color_support))
+    }
+
+    /** Return the command line as displayed, styled by the highlighter */
+    fn display_cmd(&self) -> String {
+        match &self.highlighter {
+            Some(highlighter) => {
+                // The control characters are highlighted in caret notation, as plain text
+                let highlighted = highlighter.highlight(&controls(&self.editor.line, Style::new()));
+                format!(
+                    "{}{}",
+                    self.styled(Style::new(), &highlighted),
+                    EscSeq::ResetAttributes
+                )
+            }
+            None => self.display_text(&self.editor.line),
+        }
+    }
+
+    /**
+     * Return the hint of the Hinter if it can be displayed: the cursor is at the end of the
+     * command line, and the hint fits on the row.
+     */
+    fn hint(&self) -> Option<String> {
+        let hinter = self
+            .hinter
+            .as_ref()
+            .filter(|_| !self.submitted && self.search.is_none())?;
+        if self.editor.cursor() < self.editor.line().len() {
+            return None;
+        }
+        let hint = hinter.hint(self.editor.line())?;
+        let column = match self.softwrap {
+            true => self.wrap_layout().1,
+            false => self.cursor_column() - 1,
         };
+        let fits = !hint.contains('\n') && column + display_width(&hint) < self.terminal.size().0;
+        fits.then_some(hint)
+    }
 
-        self.history_restore()
+    /** Return the hint as displayed after the cursor, followed by the sequence moving back */
+    fn display_hint(&self) -> String {
+        match self.hint() {
+            Some(hint) => format!(
+                "{}{}",
+                self.styled(self.theme.hint, &hint),
+                EscSeq::Left(display_width(&hint))
+            ),
+            None => String::new(),
+        }
     }
 
-    fn history_next(&mut self) -> Result<()> {
-        self.history_idx = match self.history_idx {
-            Some(idx) => {
-                if (idx + 1) < self.history.len() {
-                    Some(idx + 1)
-                } else {
-                    None
+    /** Record the command line as displayed, for printers to write it back */
+    fn snapshot(&self, screen: &mut Screen, active: bool) {
+        screen.active = active;
+        if !active {
+            // The status row was erased when leaving the command line
+            screen.status_shown = false;
+            return;
+        }
+        screen.erase = self.erase_line();
+        screen.width = self.terminal.size().0;
+        (screen.below, screen.column) = match self.softwrap {
+            true => {
+                let (row, column, last) = self.wrap_layout();
+                (last - row, column + 1)
+            }
+            false => (0, self.cursor_column()),
+        };
+        screen.line = match self.softwrap {
+            true => self.render_wrapped().0,
+            false => {
+                let mut line = self.display_prompt() + &self.display_cmd() + &self.display_hint();
+                if self.editor.cursor < self.editor.line.len() {
+                    let right = text_width(&self.editor.line[self.editor.cursor..]);
+                    line += &EscSeq::Left(right).to_string();
                 }
+                line
             }
-            None => None,
         };
+    }
 
-        self.history_restore()
+    /** Tell if the command line must be fully repainted after each modification */
+    fn repaint(&self) -> bool {
+        self.highlighter.is_some() || self.hinter.is_some() || self.softwrap
     }
 
-    fn cursor_reset(&mut self) -> Result<()> {
-        eprint!("{}", EscSeq::Left(self.cursor));
-        self.cursor = 0;
-        Ok(())
+    /** Tell if the command line must be repainted after a cursor move */
+    fn repaint_cursor(&self) -> bool {
+        self.hinter.is_some() || self.softwrap
     }
 
-    fn cursor_left(&mut self) -> Result<()> {
-        if self.cursor > 0 {
-            eprint!("{}", EscSeq::Left(1));
-            self.cursor -= 1;
+    /** Move the cursor to a new line below the command line, which may span multiple rows */
+    fn newline(&self) {
+        let (row, last) = self.wrap_rows.get();
+        if last > row {
+            out!(self.output, "{}", EscSeq::Down(last - row));
         }
-        Ok(())
+        // Erase the status row, if any
+        out!(self.output, "\n{}", EscSeq::EraseInLineAll);
+        self.wrap_rows.set((0, 0));
     }
 
-    fn cursor_right(&mut self) -> Result<()> {
-        if self.cursor < self.cmd.len() {
-            eprint!("{}", EscSeq::Right(1));
-            self.cursor += 1;
+    /** Return the sequence erasing the command line, from the current cursor position */
+    fn erase_line(&self) -> String {
+        match self.wrap_rows.get() {
+            (0, _) => format!("{}{}", EscSeq::EraseInLineAll, EscSeq::HorizontalAbs(0)),
+            (row, _) => format!("{}\r{}", EscSeq::Up(row), EscSeq::EraseInDisplay(0)),
         }
-        Ok(())
     }
 
-    async fn escape(&mut self) -> Result<()> {
-        let c = self.reader.read_u8().await?;
-        if c != 0x5B {
-            return Ok(());
+    /**
+     * Return the layout of the command line wrapped on multiple rows: the row and the column
+     * of the cursor, and the row of the end of the command line, starting at 0.
+     */
+    fn wrap_layout(&self) -> (usize, usize, usize) {
+        let width = self.terminal.size().0;
+        let capacity = width
+            .saturating_sub(display_width(&self.wrap_marker))
+            .max(1);
+        let gutter = width - capacity;
+        let position = |x: usize| match x < width {
+            true => (0, x),
+            false => (1 + (x - width) / capacity, gutter + (x - width) % capacity),
+        };
+        let start = display_width(&self.display_prompt());
+        let (row, column) = position(start + text_width(&self.editor.line[..self.editor.cursor]));
+        let (last, _) = position(start + text_width(&self.editor.line));
+        (row, column, last)
+    }
+
+    /**
+     * Render the command line wrapped on multiple rows, with a marker in the gutter, followed
+     * by the sequence moving the cursor at its position. The rows of the cursor and of the end
+     * of the command line are returned along with the rendering.
+     */
+    fn render_wrapped(&self) -> (String, (usize, usize)) {
+        let (row, column, last) = self.wrap_layout();
+        let text = self.display_prompt() + &self.display_cmd();
+        let mut rendering = wrap(&text, self.terminal.size().0, &self.wrap_marker, last);
+        if last > row {
+            rendering += &EscSeq::Up(last - row).to_string();
         }
-        let c = self.reader.read_u8().await?;
-        match c {
-            0x33 => {
-                // SUPPR
-                self.suppr().await?;
-            }
-            0x41 => {
-                // UP
-                self.history_prev()?;
-            }
-            0x42 => {
-                // LOW
-                self.history_next()?;
-            }
-            0x43 => {
-                // RIGHT
-                self.cursor_right()?;
-            }
-            0x44 => {
-                // LEFT
-                self.cursor_left()?;
-            }
-            _ => {
-                eprintln!("Unhandled ANSI Escape Sequence: {}", c);
-            }
+        rendering += &EscSeq::HorizontalAbs(column + 1).to_string();
+        rendering += &self.display_hint();
+        (rendering, (row, last))
+    }
+
+    /** Repaint the command line, wrapped on multiple rows */
+    fn refresh_wrapped(&self) -> Result<()> {
+        let (rendering, rows) = self.render_wrapped();
+        out!(self.output, "{}{}", self.erase_line(), rendering);
+        self.wrap_rows.set(rows);
+        self.rprompt_visible.set(false);
+        if rows.1 == 0 {
+            self.rprompt_render();
         }
         Ok(())
     }
 
-    fn addchar(&mut self, c: char) -> Result<()> {
-        if self.cursor < self.cmd.len() {
-            let right = &self.cmd[self.cursor..];
-            eprint!("{}{}{}", c, right, EscSeq::Left(right.len()));
-        } else {
-            eprint!("{}", c);
+    fn refresh_line(&self) -> Result<()> {
+        if self.softwrap {
+            return self.refresh_wrapped();
         }
-
-        self.cmd.insert(self.cursor, c);
-        self.cursor += 1;
+        self.clear_line()?;
+        self.rprompt_visible.set(false);
+        out!(
+            self.output,
+            "{}{}{}",
+            self.display_prompt(),
+            self.display_cmd(),
+            self.display_hint()
+        );
+        if self.editor.cursor < self.editor.line.len() {
+            out!(
+                self.output,
+                "{}",
+                EscSeq::Left(text_width(&self.editor.line[self.editor.cursor..]))
+            );
+        }
+        self.rprompt_render();
         Ok(())
     }
 
-    fn backspace(&mut self) -> Result<()> {
-        if self.cursor == 0 {
-            return Ok(());
-        }
+    /**
+     * Display the right prompt if it fits on the line after the command,
+     * or erase it if the command reached it.
+     */
+    fn rprompt_render(&self) {
+        let width = self.terminal.size().0;
+        let rwidth = display_width(&self.rprompt_text);
+        let hint = self.hint().map_or(0, |hint| display_width(&hint));
+        let end = display_width(&self.display_prompt()) + text_width(&self.editor.line) + hint;
+        let transient = self.submitted && self.transient_prompt.is_some();
+        let fits = rwidth > 0 && end + 1 + rwidth < width && !transient;
+        let column = width.saturating_sub(rwidth);
 
-        let right = &self.cmd[self.cursor..];
-        self.cursor -= 1;
-        eprint!("\x08{} {}", right, EscSeq::Left(right.len() + 1));
-        self.cmd.remove(self.cursor);
+        match (self.rprompt_visible.get(), fits) {
+            (false, true) => {
+                out!(
+                    self.output,
+                    "{}{}{}",
+                    EscSeq::HorizontalAbs(column),
+                    self.styled(self.theme.prompt, &self.rprompt_text),
+                    EscSeq::HorizontalAbs(self.cursor_column())
+                );
+            }
+            (true, false) => {
+                out!(
+                    self.output,
+                    "{}{}{}",
+                    EscSeq::HorizontalAbs(column),
+                    EscSeq::EraseInLineFromCursorToEnd,
+                    EscSeq::HorizontalAbs(self.cursor_column())
+                );
+            }
+            _ => {}
+        }
+        self.rprompt_visible.set(fits);
+    }
 
+    fn reset(&mut self) -> Result<()> {
+        if !self.sensitive.is_empty() {
+            // The command line abandoned without being submitted may be sensitive
+            self.output.release_record(false);
+        }
+        wipe(&mut self.pending);
+        wipe(&mut self.editor.line);
+        wipe(&mut self.plugins_line);
+        self.editor.clear();
+        self.submitted = false;
+        self.keypad_transmit();
+        self.prompt_text = self.prompt.prompt();
+        out!(self.output, "{}", self.display_prompt());
+        self.rprompt_text = match &self.rprompt {
+            Some(rprompt) => rprompt.prompt(),
+            None => String::new(),
+        };
+        self.rprompt_visible.set(false);
+        self.rprompt_render();
+        if !self.sensitive.is_empty() {
+            // Left out of the recording if the command line turns out to be sensitive
+            self.output.hold_record();
+        }
         Ok(())
     }
 
-    async fn suppr(&mut self) -> Result<()> {
-        let c = self.reader.read_u8().await? as char;
-        if c != '~' {
-            eprintln!("Unexpect character {}", c);
-            return Ok(());
+    /** Configure the cursor keys and keypad in application mode, and the bracketed paste */
+    fn keypad_transmit(&self) {
+        if self.keypad_mode {
+            out!(self.output, "{}", EscSeq::KeypadTransmit);
         }
-        if self.cursor + 1 < self.cmd.len() {
-            let right = &self.cmd[self.cursor + 1..];
-            eprint!("{} {}", right, EscSeq::Left(right.len() + 1));
-            self.cmd.remove(self.cursor);
+        if self.bracketed_paste {
+            out!(self.output, "{}", EscSeq::BracketedPasteOn);
         }
-        Ok(())
     }
 
-    fn eol(&mut self) -> Result<Vec<String>> {
-        eprintln!();
-        let args = self.cmd2args();
-        if !args[0].is_empty() {
-            self.history.push(self.cmd.clone());
+    /** Configure back the cursor keys and keypad in normal mode, without bracketed paste */
+    fn keypad_local(&self) {
+        if self.keypad_mode {
+            out!(self.output, "{}", EscSeq::KeypadLocal);
+        }
+        if self.bracketed_paste {
+            out!(self.output, "{}", EscSeq::BracketedPasteOff);
+        }
+    }
+
+    /** Render the command line after a history navigation */
+    fn history_restore(&mut self) -> Result<()> {
+        if self.editor.history_index().is_none() && !self.history_indicator {
+            return Ok(());
         }
-        Ok(args)
+        // Also removes the history index from the prompt when leaving the history
+        self.refresh_line()
+    }
+
+    fn history_prev(&mut self) -> Result<()> {
+        self.editor.history_prev();
+        self.history_restore()
+    }
+
+    fn history_next(&mut self) -> Result<()> {
+        self.editor.history_next();
+        self.history_restore()
+    }
+
+    /** Start an incremental history search, or find the next match in the provided direction */
+    fn search_start(&mut self, forward: bool) -> Result<()> {
+        let skip = match &mut self.search {
+            Some(search) => {
+                search.forward = forward;
+                true
+            }
+            None => {
+                let editor = &self.editor;
+                self.search = Some(Search {
+                    query: String::new(),
+                    forward,
+                    failed: false,
+                    saved: (editor.line.clone(), editor.cursor, editor.history_idx),
+                });
+                false
+            }
+        };
+        self.search_update(skip)
     }
 
     /**
-     * Return an Action demanded by the user in CLI.
+     * Edit the history entry matching the query, skipping the entry being edited if asked.
+     * The bell is rung if no further entry matches.
      */
-    pub async fn getaction(&mut self) -> Result<Action> {
-        if self.do_reset {
-            self.reset()?;
-            self.do_reset = false;
+    fn search_update(&mut self, skip: bool) -> Result<()> {
+        let Some(search) = &mut self.search else {
+            return Ok(());
+        };
+        search.failed = false;
+        if !search.query.is_empty() {
+            let (query, forward) = (search.query.clone(), search.forward);
+            let wraparound = self.search_wraparound;
+            let found = self
+                .editor
+                .history_search(&query, forward, skip, wraparound);
+            if let Some(search) = &mut self.search {
+                search.failed = !found;
+            }
+            if !found {
+                self.bell();
+            }
         }
-        loop {
-            let c = self.reader.read_u8().await?;
+        self.refresh_line()
+    }
 
-            match c {
-                0x01 | 0x02 => {
-                    self.cursor_reset()?;
-                }
-                0x04 => {
-                    if self.cmd.len() == 0 {
-                        return Ok(Action::NoAction)
-                    }
-                }
-                0x1B => {
-                    // ESC (escap)
-                    self.escape().await?;
-                }
-                0x7F => {
-                    // DEL
-                    self.backspace()?;
-                }
-                b'\n' => {
-                    self.do_reset = true;
-                    return Ok(Action::Command(self.eol()?));
-                }
-                b'\t' => {
-                    return Ok(Action::AutoComplete(self.cmd2args()));
-                }
-                _ => {
-                    self.addchar(c as char)?;
-                }
+    /**
+     * Handle an editing action during the history search, and return true if it was consumed.
+     *
+     * Other actions end the search, keeping the matching entry, and are then performed.
+     */
+    fn search_key(&mut self, action: &EditAction) -> Result<bool> {
+        let Some(search) = &mut self.search else {
+            return Ok(false);
+        };
+        match action {
+            EditAction::Insert(c) => {
+                search.query.push(*c);
+                self.search_update(false)?;
+            }
+            EditAction::BackwardDeleteChar => {
+                search.query.pop();
+                self.search_update(false)?;
+            }
+            EditAction::ReverseSearchHistory => self.search_start(false)?,
+            EditAction::ForwardSearchHistory => self.search_start(true)?,
+            EditAction::Abort => {
+                let (line, cursor, history_idx) = search.saved.clone();
+                self.search = None;
+                self.editor.setline(&line).setcursor(cursor);
+                self.editor.history_idx = history_idx;
+                self.refresh_line()?;
+            }
+            _ => {
+                self.search = None;
+                self.refresh_line()?;
+                return Ok(false);
             }
         }
+        Ok(true)
     }
 
     /**
-     * Auto-complete the current command with the provided list of possible words
-     *
-     * <div class="warning">The word list should only contains possible words for the current
-     * input. This function does not filter out the word list, and expect all words in the list to
-     * start with current input.</div>
+     * Insert the last argument of the previous history entry at the cursor, or replace the
+     * argument inserted by the previous Alt+. with the one of the entry before.
      */
-    pub fn autocomplete(&mut self, words: &Vec<String>) -> Result<()> {
-        if words.is_empty() {
-            // Nothing to do
+    fn yank_last_arg(&mut self) -> Result<()> {
+        let (from, inserted) = match self.yank_last.take() {
+            Some((idx, range)) => (idx, Some(range)),
+            None => (self.editor.history.len(), None),
+        };
+        let first = self.editor.history.first(self.editor.history_scope);
+        let found = (first..from).rev().find_map(|idx| {
+            let entry = self.editor.history.get(idx)?;
+            let args = self.tokenize(&entry.text).0;
+            let arg = args.into_iter().rev().find(|arg| !arg.is_empty())?;
+            Some((idx, arg))
+        });
+        let Some((idx, arg)) = found else {
+            self.bell();
+            self.yank_last = inserted.map(|range| (from, range));
             return Ok(());
+        };
+        if let Some(range) = inserted {
+            self.editor.cursor = range.start;
+            self.editor.line.replace_range(range, "");
         }
+        let start = self.editor.cursor;
+        let text = self.quote(&self.editor.line[..start], &arg, false);
+        self.editor.insert_str(&text);
+        self.yank_last = Some((idx, start..self.editor.cursor));
+        self.refresh_line()
+    }
 
-        // Retrieve common word
-        let mut common = words[0].as_str();
-        for word in words {
-            common = common_chars(word, common);
+    /** Expand the abbreviation typed as the command name, before the cursor */
+    fn abbreviate(&mut self) -> Result<()> {
+        if self.abbreviations.is_empty() || !self.pending.is_empty() {
+            return Ok(());
         }
+        let before = &self.editor.line[..self.editor.cursor];
+        let name = before.trim_start();
+        let after = &self.editor.line[self.editor.cursor..];
+        if name.contains(char::is_whitespace)
+            || !(after.is_empty() || after.starts_with(char::is_whitespace))
+        {
+            return Ok(());
+        }
+        let Some(value) = self.abbreviations.get(name) else {
+            return Ok(());
+        };
+        if value.chars().count() > self.room() + name.chars().count() {
+            return Ok(());
+        }
+        let start = before.len() - name.len();
+        let value = value.clone();
+        self.editor
+            .line
+            .replace_range(start..self.editor.cursor, &value);
+        self.editor.cursor = start + value.len();
+        self.refresh_line()
+    }
 
-        // Get completion word from common word
-        let args = self.cmd2args();
-        let lastarg = args.last().unwrap();
-        let complete = &common[lastarg.len()..];
-
-        if words.len() == 1 {
-            // Complete current line
-            self.cmd += complete;
-            self.cursor += complete.len();
-            eprint!("{}", complete);
-        } else {
-            // Display all possibilites
-            eprintln!();
-            for word in words {
-                eprint!("{} ", word);
+    fn cursor_reset(&mut self) -> Result<()> {
+        let cursor = self.editor.cursor();
+        if self.editor.move_home() {
+            match self.repaint_cursor() {
+                true => self.refresh_line()?,
+                false => out!(
+                    self.output,
+                    "{}",
+                    EscSeq::Left(text_width(&self.editor.line[..cursor]))
+                ),
             }
-            // Write back partially completed command
-            self.cmd += complete;
-            self.cursor += complete.len();
-            eprint!("\n{}{}", self.prompt, self.cmd);
         }
-
         Ok(())
     }
 
-    /** Set the name of the prompt */
-    pub fn setprompt(&mut self, prompt: &str) -> &mut Self {
-        self.prompt = prompt.into();
-        self
+    fn cursor_end(&mut self) -> Result<()> {
+        let cursor = self.editor.cursor();
+        if self.editor.move_end() {
+            match self.repaint_cursor() {
+                true => self.refresh_line()?,
+                false => out!(
+                    self.output,
+                    "{}",
+                    EscSeq::Right(text_width(&self.editor.line[cursor..]))
+                ),
+            }
+        }
+        Ok(())
     }
-}
 
-impl Drop for Cli {
-    /**
-     * Release Cli ressources and configure back the terminal in its orignal state.
+    fn cursor_left(&mut self) -> Result<()> {
+        let cursor = self.editor.cursor();
+        if self.editor.move_left() {
+            match self.repaint_cursor() {
+                true => self.refresh_line()?,
+                false => out!(
+                    self.output,
+                    "{}",
+                    EscSeq::Left(text_width(&self.editor.line[self.editor.cursor..cursor]))
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    fn cursor_right(&mut self) -> Result<()> {
+        let cursor = self.editor.cursor();
+        if self.editor.move_right() {
+            match self.repaint_cursor() {
+                true => self.refresh_line()?,
+                false => out!(
+                    self.output,
+                    "{}",
+                    EscSeq::Right(text_width(&self.editor.line[cursor..self.editor.cursor]))
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /** Return the number of characters which can still be inserted in the command line */
+    fn room(&self) -> usize {
+        let length = self.pending.chars().count() + self.editor.line.chars().count();
+        self.max_line.saturating_sub(length)
+    }
+
+    fn addchar(&mut self, c: char) -> Result<()> {
+        if self.room() == 0 {
+            self.bell();
+            return Ok(());
+        }
+        if !self.repaint() {
+            let shown = self.display_text(c.encode_utf8(&mut [0; 4]));
+            let right = &self.editor.line()[self.editor.cursor()..];
+            match right.is_empty() {
+                true => out!(self.output, "{}", shown),
+                false => out!(
+                    self.output,
+                    "{}{}{}",
+                    shown,
+                    self.display_text(right),
+                    EscSeq::Left(text_width(right))
+                ),
+            }
+        }
+        self.editor.insert(c);
+        match self.repaint() {
+            true => self.refresh_line(),
+            false => Ok(()),
+        }
+    }
+
+    /** Insert pasted text at the cursor: each line break continues the command on a new line */
+    fn paste(&mut self, text: &str) -> Result<()> {
+        let text = text
+            .replace("\r\n", "\n")
+            .replace('\r', "\n")
+            .replace('\t', " ");
+        let mut text = text.replace(|c: char| c.is_control() && c != '\n', "");
+        if let Some((end, _)) = text.char_indices().nth(self.room()) {
+            text.truncate(end);
+            self.bell();
+        }
+        let mut lines = text.split('\n');
+        let first = lines.next().unwrap_or_default();
+        self.editor.insert_str(first);
+        for line in lines {
+            let rest = self.editor.line.split_off(self.editor.cursor);
+            self.refresh_line()?;
+            self.newline();
+            self.pending += &std::mem::take(&mut self.editor.line);
+            self.pending.push('\n');
+            self.editor.setline(&rest).setcursor(0).insert_str(line);
+        }
+        self.refresh_line()
+    }
+
+    fn backspace(&mut self) -> Result<()> {
+        let Some(c) = self.editor.delete_backward() else {
+            return Ok(());
+        };
+        if self.repaint() {
+            return self.refresh_line();
+        }
+        let width = text_width(c.encode_utf8(&mut [0; 4]));
+        let right = &self.editor.line()[self.editor.cursor()..];
+        out!(
+            self.output,
+            "{}{}{}{}",
+            EscSeq::Left(width),
+            self.display_text(right),
+            " ".repeat(width),
+            EscSeq::Left(text_width(right) + width)
+        );
+        Ok(())
+    }
+
+    fn suppr(&mut self) -> Result<()> {
+        let Some(c) = self.editor.delete_forward() else {
+            return Ok(());
+        };
+        if self.repaint() {
+            return self.refresh_line();
+        }
+        let width = text_width(c.encode_utf8(&mut [0; 4]));
+        let right = &self.editor.line()[self.editor.cursor()..];
+        out!(
+            self.output,
+            "{}{}{}",
+            self.display_text(right),
+            " ".repeat(width),
+            EscSeq::Left(text_width(right) + width)
+        );
+        Ok(())
+    }
+
+    /**
+     * Perform an action of the LineEditor transforming the text around the cursor, and
+     * repaint the changed part of the command line. The bell rings if there was nothing to
+     * transform.
+     */
+    fn transform(&mut self, action: &EditAction) -> Result<()> {
+        let (line, cursor) = (self.editor.line.clone(), self.editor.cursor);
+        match self.editor.apply(action) {
+            true => self.repaint_change(&line, cursor),
+            false => {
+                self.bell();
+                Ok(())
+            }
+        }
+    }
+
+    /**
+     * Repaint the command line after a range of it was replaced: only the text from the
+     * first changed character is written back, unless the whole line must be repainted.
+     */
+    fn repaint_change(&self, old_line: &str, old_cursor: usize) -> Result<()> {
+        if self.repaint() {
+            return self.refresh_line();
+        }
+        let line = &self.editor.line;
+        let start = old_line
+            .char_indices()
+            .zip(line.chars())
+            .find(|((_, old), new)| old != new)
+            .map_or(
+                std::cmp::min(old_line.len(), line.len()),
+                |((idx, _), _)| idx,
+            );
+        let mut rendering = match old_cursor.cmp(&start) {
+            std::cmp::Ordering::Greater => {
+                EscSeq::Left(text_width(&old_line[start..old_cursor])).to_string()
+            }
+            std::cmp::Ordering::Less => {
+                EscSeq::Right(text_width(&old_line[old_cursor..start])).to_string()
+            }
+            std::cmp::Ordering::Equal => String::new(),
+        };
+        let tail = &line[start..];
+        let erase = text_width(&old_line[start..]).saturating_sub(text_width(tail));
+        rendering += &self.display_text(tail);
+        rendering += &" ".repeat(erase);
+        let back = text_width(&line[self.editor.cursor..]) + erase;
+        if back > 0 {
+            rendering += &EscSeq::Left(back).to_string();
+        }
+        out!(self.output, "{}", rendering);
+        Ok(())
+    }
+
+    /** Display a warning below the command line, and write back the command line */
+    fn warn(&self, msg: &str) -> Result<()> {
+        self.newline();
+        self.print_error(msg);
+        self.refresh_line()
+    }
+
+    /** Print an error message, styled as set by the theme */
+    pub(crate) fn print_error(&self, msg: &str) {
+        outln!(self.output, "{}", self.styled(self.theme.error, msg));
+    }
+
+    /** Validate the command line on Enter and return its arguments if it can be submitted */
+    fn submit(&mut self) -> Result<Option<Vec<String>>> {
+        let mut line = self.pending.clone() + &self.editor.line;
+        // The rewritten command line is echoed, so the user sees what runs
+        let mut rewritten = false;
+        if self.history_expansion {
+            match self.editor.history.expand(&line) {
+                Ok(text) => {
+                    rewritten = text != line;
+                    line = text;
+                }
+                Err(event) => {
+                    self.warn(&format!("{}: event not found", event))?;
+                    return Ok(None);
+                }
+            }
+        }
+        let validation = match &self.validator {
+            Some(validator) => validator.validate(&line),
+            None => Validation::Valid,
+        };
+        let validation = match validation {
+            Validation::Valid if self.tokenize(&line).1 => Validation::Incomplete,
+            validation => validation,
+        };
+
+        match validation {
+            Validation::Valid => {}
+            Validation::Incomplete => match self.incomplete_policy {
+                IncompletePolicy::Submit => {}
+                IncompletePolicy::Continue => {
+                    self.newline();
+                    self.pending = line + "\n";
+                    self.editor.line.clear();
+                    self.editor.cursor = 0;
+                    out!(self.output, "{}", self.display_prompt());
+                    return Ok(None);
+                }
+                IncompletePolicy::Warn => {
+                    self.warn("Incomplete command: unterminated string")?;
+                    return Ok(None);
+                }
+            },
+            Validation::Invalid(msg) => {
+                self.warn(&msg)?;
+                return Ok(None);
+            }
+        }
+
+        if !self.plugins.is_empty() {
+            let mut buffer = LineBuffer::new(line.clone(), line.len());
+            for plugin in &mut self.plugins {
+                plugin.on_accept(&mut buffer);
+            }
+            if buffer.modified() {
+                let (text, _) = buffer.into_parts();
+                rewritten |= text != line;
+                line = text;
+            }
+        }
+        for plugin in &mut self.plugins {
+            plugin.on_submit(&line);
+        }
+        // The rewritten sensitive command line is not echoed: it would be recorded
+        let rewritten = rewritten && !self.is_sensitive(&line);
+        let args = self.eol(line)?;
+        if rewritten {
+            outln!(self.output, "{}", self.display_text(&self.editor.line));
+        }
+        Ok(Some(args))
+    }
+
+    /** Tell if a command line matches a pattern of the sensitive command lines */
+    fn is_sensitive(&self, line: &str) -> bool {
+        self.sensitive.iter().any(|pattern| glob(pattern, line))
+    }
+
+    /** Leave the command line submitted as `line`, and return its arguments */
+    fn eol(&mut self, line: String) -> Result<Vec<String>> {
+        // Repaint the last row of the command line with the transient prompt, without hint
+        let repaint = self.transient_prompt.is_some() || self.hint().is_some();
+        self.submitted = true;
+        if repaint {
+            self.refresh_line()?;
+        }
+        let sensitive = self.is_sensitive(&line);
+        if !self.sensitive.is_empty() {
+            self.output.release_record(!sensitive);
+        }
+        wipe(&mut std::mem::replace(&mut self.editor.line, line));
+        wipe(&mut self.pending);
+        self.newline();
+        self.keypad_local();
+        let args = self.cmd2args();
+        // A line holding only a comment is kept in history, though it has no argument
+        let comment = self.comments && !self.editor.line.trim().is_empty();
+        if (!args[0].is_empty() || comment) && !sensitive {
+            self.editor.add_history(self.editor.line.clone());
+        }
+        Ok(self.alias(args))
+    }
+
+    /** Replace the command name by its alias definition */
+    fn alias(&self, mut args: Vec<String>) -> Vec<String> {
+        if let Some(value) = self.aliases.get(&args[0]) {
+            let mut expanded = self.split(value, self.variables_enabled).0;
+            expanded.retain(|arg| !arg.is_empty());
+            if !expanded.is_empty() {
+                expanded.extend(args.drain(1..));
+                return expanded;
+            }
+        }
+        args
+    }
+
+    /** Return the end position of the word under the cursor */
+    fn word_end(&self) -> usize {
+        let line = &self.editor.line;
+        self.with_tokenizer(|tokenizer| tokenizer.word_range(line, self.editor.cursor).end)
+    }
+
+    /** Return the start position of the word under the cursor */
+    fn word_start(&self) -> usize {
+        let line = &self.editor.line;
+        self.with_tokenizer(|tokenizer| tokenizer.word_range(line, self.editor.cursor).start)
+    }
+
+    /**
+     * Replace the word under the cursor, typed as `typed` so far, with a completion word.
+     *
+     * The completion is appended to the typed word when possible, otherwise the typed word
+     * is replaced. When the completion is final, it also replaces the rest of the word under
+     * the cursor.
+     */
+    fn replace_word(&mut self, typed: &str, word: &str, completed: bool) {
+        let text = match word.strip_prefix(typed) {
+            Some(suffix) => suffix,
+            None => {
+                let start = self.word_start();
+                self.editor
+                    .line
+                    .replace_range(start..self.editor.cursor, "");
+                self.editor.cursor = start;
+                word
+            }
+        };
+        let text = self.quote(&self.editor.line[..self.editor.cursor], text, completed);
+        let end = match completed {
+            true => self.word_end(),
+            false => self.editor.cursor,
+        };
+        self.editor
+            .line
+            .replace_range(self.editor.cursor..end, &text);
+        self.editor.cursor += text.len();
+    }
+
+    /** Replace the word under the cursor with a completion word and render it */
+    fn complete_word(&mut self, typed: &str, word: &str, completed: bool) -> Result<()> {
+        let cmd = self.editor.line.clone();
+        let cursor = self.editor.cursor;
+        self.replace_word(typed, word, completed);
+        if cursor == cmd.len() && self.editor.line.starts_with(&cmd) && !self.repaint() {
+            out!(
+                self.output,
+                "{}",
+                self.display_text(&self.editor.line[cursor..])
+            );
+            Ok(())
+        } else {
+            self.refresh_line()
+        }
+    }
+
+    /** Handle a key while the completion pager is displayed */
+    fn pager_key(&mut self, action: EditAction) -> Result<()> {
+        let (_, rows) = self.terminal.size();
+        let page = std::cmp::max(rows, 2) - 1;
+        match self.pager.take() {
+            Some(Pager::Query(lines)) => match action {
+                EditAction::Insert('y' | 'Y' | ' ') => self.pager_show(lines, 0, page),
+                EditAction::Insert('n' | 'N' | 'q' | 'Q') | EditAction::EndOfFile => {
+                    outln!(self.output);
+                    self.refresh_line()
+                }
+                _ => {
+                    self.pager = Some(Pager::Query(lines));
+                    Ok(())
+                }
+            },
+            Some(Pager::More(lines, from)) => match action {
+                EditAction::Insert('y' | 'Y' | ' ') => self.pager_show(lines, from, page),
+                EditAction::AcceptLine => self.pager_show(lines, from, 1),
+                EditAction::Insert('n' | 'N' | 'q' | 'Q') | EditAction::EndOfFile => {
+                    self.clear_line()?;
+                    self.refresh_line()
+                }
+                _ => {
+                    self.pager = Some(Pager::More(lines, from));
+                    Ok(())
+                }
+            },
+            None => Ok(()),
+        }
+    }
+
+    /** Display `count` lines of the pager from the provided index */
+    fn pager_show(&mut self, lines: Vec<String>, from: usize, count: usize) -> Result<()> {
+        self.clear_line()?;
+        let end = std::cmp::min(from + count, lines.len());
+        for line in &lines[from..end] {
+            outln!(self.output, "{}", line);
+        }
+        if end < lines.len() {
+            out!(self.output, "--More--");
+            self.pager = Some(Pager::More(lines, end));
+            Ok(())
+        } else {
+            self.refresh_line()
+        }
+    }
+
+    fn bell(&self) {
+        match &self.bell {
+            Bell::None => {}
+            Bell::Audible => out!(self.output, "\x07"),
+            Bell::Custom(bell) => bell(),
+        }
+    }
+
+    fn menu_render(&mut self) -> Result<()> {
+        let selection = self.theme.selection.downgrade(self.color_support);
+        let menu = match &mut self.menu {
+            Some(menu) => menu,
+            None => {
+                return Ok(());
+            }
+        };
+
+        // Scroll the displayed window to keep the selected word visible
+        let first = match menu.selected {
+            Some(selected) if selected >= MENU_ROWS => selected + 1 - MENU_ROWS,
+            _ => 0,
+        };
+        let rows = std::cmp::min(menu.words.len(), MENU_ROWS);
+
+        for (i, word) in menu.words.iter().enumerate().skip(first).take(rows) {
+            out!(self.output, "\n{}", EscSeq::EraseInLineAll);
+            if menu.selected == Some(i) {
+                out!(self.output, "{}", selection.paint(word));
+            } else {
+                out!(self.output, "{}", word);
+            }
+        }
+        menu.rows = rows;
+
+        // Move back to the cursor position in the command line
+        if rows > 0 {
+            out!(self.output, "{}", EscSeq::Up(rows));
+        }
+        out!(
+            self.output,
+            "{}",
+            EscSeq::HorizontalAbs(self.cursor_column())
+        );
+        Ok(())
+    }
+
+    fn menu_select(&mut self, selected: usize) -> Result<()> {
+        let menu = match &mut self.menu {
+            Some(menu) => menu,
+            None => {
+                return Ok(());
+            }
+        };
+
+        menu.selected = Some(selected);
+        let word = menu.words[selected].clone();
+        let typed = menu.typed.clone();
+        self.editor.line = menu.anchor.clone();
+        self.editor.cursor = menu.position;
+        self.replace_word(&typed, &word, !word.ends_with('/'));
+        self.refresh_line()?;
+        self.menu_render()
+    }
+
+    fn menu_next(&mut self) -> Result<()> {
+        let selected = match &self.menu {
+            Some(menu) => match menu.selected {
+                Some(selected) => (selected + 1) % menu.words.len(),
+                None => 0,
+            },
+            None => {
+                return Ok(());
+            }
+        };
+        self.menu_select(selected)
+    }
+
+    fn menu_prev(&mut self) -> Result<()> {
+        let selected = match &self.menu {
+            Some(menu) => match menu.selected {
+                Some(0) | None => menu.words.len() - 1,
+                Some(selected) => selected - 1,
+            },
+            None => {
+                return Ok(());
+            }
+        };
+        self.menu_select(selected)
+    }
+
+    fn menu_close(&mut self) -> Result<()> {
+        let menu = match self.menu.take() {
+            Some(menu) => menu,
+            None => {
+                return Ok(());
+            }
+        };
+
+        if menu.rows > 0 {
+            out!(
+                self.output,
+                "{}{}{}{}{}",
+                EscSeq::Down(1),
+                EscSeq::HorizontalAbs(0),
+                EscSeq::EraseInDisplay(0),
+                EscSeq::Up(1),
+                EscSeq::HorizontalAbs(self.cursor_column())
+            );
+        }
+        Ok(())
+    }
+
+    /**
+     * Return the possible words for the current command, when the completion is handled
+     * by the Cli itself rather than by the application.
+     */
+    fn completions(&self, args: &[String]) -> Option<Vec<String>> {
+        if self.variables_enabled {
+            let word = args.last().map(String::as_str).unwrap_or_default();
+            if word.starts_with('$') {
+                let words = self.variables.keys().map(|name| format!("${}", name));
+                return Some(words.collect());
+            }
+            if args.len() == 2 && (args[0] == "set" || args[0] == "unset") {
+                return Some(self.variables.keys().cloned().collect());
+            }
+        }
+        if let Some(completer) = &self.completer {
+            return Some(completer.completions(args));
+        }
+        if args.len() == 1 && !(self.commands.is_empty() && self.aliases.is_empty()) {
+            let mut commands = self.commands.clone();
+            commands.extend(self.aliases.keys().cloned());
+            if self.variables_enabled {
+                commands.extend(BUILTINS.iter().map(|name| name.to_string()));
+            }
+            commands.sort();
+            commands.dedup();
+            return Some(commands);
+        }
+        None
+    }
+
+    /** Return the next command line of the script being executed */
+    fn script_next(&mut self) -> Result<Option<String>> {
+        while let Some(frame) = self.script.last_mut() {
+            if frame.pc == frame.body.len() {
+                if let Some((var, words)) = &mut frame.iteration {
+                    if let Some(word) = words.pop() {
+                        frame.pc = 0;
+                        self.variables.insert(var.clone(), word);
+                        continue;
+                    }
+                }
+                self.script.pop();
+                continue;
+            }
+            let statement = frame.body[frame.pc].clone();
+            frame.pc += 1;
+            match statement {
+                Statement::Command(line) => {
+                    return Ok(Some(line));
+                }
+                Statement::If(condition, then, otherwise) => {
+                    let args = self.split(&condition, true).0;
+                    let value = match script::condition(&args) {
+                        Ok(value) => value,
+                        Err(err) => {
+                            self.script.clear();
+                            return Err(err);
+                        }
+                    };
+                    let body = if value { then } else { otherwise };
+                    self.script.push(Frame::new(body));
+                }
+                Statement::For(var, words, body) => {
+                    let words = self.split(&words, true).0;
+                    let words = words.into_iter().filter(|word| !word.is_empty()).collect();
+                    self.script.push(Frame::iteration(body, var, words));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /**
+     * Query the position of the cursor (Row, Column) to the terminal.
+     *
+     * Keys pressed while waiting for the reply are kept for the next call to getaction.
+     */
+    async fn query_position(&mut self, timeout: Duration) -> Result<Option<(usize, usize)>> {
+        out!(self.output, "{}", EscSeq::DeviceStatusReport);
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            match tokio::time::timeout_at(deadline, self.read_input()).await {
+                Ok(input) => input?,
+                Err(_) => return Ok(None),
+            }
+            let reply = self
+                .typeahead
+                .iter()
+                .position(|decoded| matches!(decoded, Decoded::CursorPosition(_, _)));
+            if let Some(Decoded::CursorPosition(row, col)) =
+                reply.and_then(|idx| self.typeahead.remove(idx))
+            {
+                return Ok(Some((row, col)));
+            }
+        }
+    }
+
+    /**
+     * Read all the bytes available from the terminal, or injected by the application, and
+     * queue what was decoded.
+     *
+     * A block of pasted text is processed at once, instead of waking up for each byte.
+     */
+    async fn read_input(&mut self) -> Result<()> {
+        let bytes = tokio::select! {
+            read = self.reader.fill_buf() => match read {
+                Ok(bytes) => bytes.to_vec(),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {
+                    return Err(Error::Interrupted)
+                }
+                Err(err) => return Err(err.into()),
+            },
+            Some(bytes) = self.injected.recv() => {
+                self.decode(&bytes);
+                return Ok(());
+            }
+        };
+        if bytes.is_empty() {
+            return Err(Error::Eof);
+        }
+        self.reader.consume(bytes.len());
+        self.decode(&bytes);
+        Ok(())
+    }
+
+    /** Decode input bytes, and queue what was decoded */
+    fn decode(&mut self, bytes: &[u8]) {
+        self.output.record(RecordKind::Input, bytes);
+        for &byte in bytes {
+            if let Some(decoded) = self.decoder.feed(byte) {
+                self.typeahead.push_back(decoded);
+            }
+        }
+    }
+
+    /**
+     * Inject input as if typed by the user on the terminal, e.g. to pre-type a suggested
+     * command for the user to edit. The input is processed by the next call to
+     * [`Cli::getaction`], and may contain control characters and escape sequences (e.g.
+     * "\r" to submit the line).
+     */
+    pub fn feed_input(&mut self, input: &str) -> &mut Self {
+        self.decode(input.as_bytes());
+        self
+    }
+
+    /**
+     * Return a sender injecting input as if typed by the user on the terminal, to drive
+     * the Cli from another task or event source (see [`Cli::feed_input`]).
+     */
+    pub fn input_sender(&self) -> UnboundedSender<Vec<u8>> {
+        self.injector.clone()
+    }
+
+    /**
+     * Start or stop recording a keyboard macro, or replay the last one: its keys are queued
+     * back as if typed again.
+     */
+    fn kbd_macro_action(&mut self, action: &EditAction) {
+        match (action, &self.kbd_recording) {
+            (EditAction::StartKbdMacro, None) => self.kbd_recording = Some(Vec::new()),
+            (EditAction::EndKbdMacro, Some(_)) => {
+                self.kbd_macro = self.kbd_recording.take().unwrap_or_default();
+            }
+            (EditAction::CallLastKbdMacro, None) if !self.kbd_macro.is_empty() => {
+                for decoded in self.kbd_macro.iter().rev() {
+                    self.typeahead.push_front(decoded.clone());
+                }
+            }
+            _ => self.bell(),
+        }
+    }
+
+    /**
+     * Edit the command line with the editor of the user ($VISUAL or $EDITOR), in a temporary
+     * file, while the Cli is suspended. The command line is replaced by the saved text.
+     */
+    async fn edit_in_editor(&mut self) -> Result<()> {
+        let default = if cfg!(windows) { "notepad" } else { "vi" };
+        let editor = ["VISUAL", "EDITOR"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| default.into());
+        // The editor may be provided with arguments, e.g. "code --wait"
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or(default);
+        let text = self.pending.clone() + &self.editor.line;
+        let file = create_temp(&(text + "\n")).await?;
+
+        self.keypad_local();
+        let status = {
+            // If this future is dropped, the editor is killed before the Cli is resumed
+            let _suspend = self.suspend()?;
+            tokio::process::Command::new(program)
+                .args(words)
+                .arg(&file.0)
+                .kill_on_drop(true)
+                .status()
+                .await
+        };
+        let text = tokio::fs::read_to_string(&file.0).await;
+        drop(file);
+        let text = match (status, text) {
+            (Ok(status), _) if !status.success() => Err(status.to_string()),
+            (Err(err), _) | (_, Err(err)) => Err(err.to_string()),
+            (Ok(_), Ok(text)) => Ok(text),
+        };
+        match text {
+            Ok(text) => {
+                self.pending.clear();
+                self.editor.clear();
+                self.paste(text.trim_end_matches(['\r', '\n']))
+            }
+            Err(err) => self.warn(&format!("{}: {}", program, err)),
+        }
+    }
+
+    /**
+     * Make sure the prompt is displayed at the beginning of a line, even if the output
+     * written to the terminal since the last command did not end with a newline.
+     */
+    async fn sync_cursor(&mut self) -> Result<()> {
+        if self.responsive == Some(false) {
+            return Ok(());
+        }
+        match self.query_position(CURSOR_SYNC_TIMEOUT).await? {
+            Some((_, col)) if col > 1 => outln!(self.output),
+            Some(_) => {}
+            None => self.responsive = Some(false),
+        }
+        Ok(())
+    }
+
+    /** Return the help of the command if it ends with --help or -h, and a help is available */
+    fn help(&self, args: &[String]) -> Option<String> {
+        let (flag, command) = args.split_last()?;
+        if command.is_empty() || (flag != "--help" && flag != "-h") {
+            return None;
+        }
+        self.completer.as_ref()?.help(command)
+    }
+
+    /** Return the countdowns as displayed in the status row */
+    fn countdowns_display(&self, now: tokio::time::Instant) -> String {
+        let countdowns = self.countdowns.iter();
+        let countdowns = countdowns.map(|countdown| countdown.display(now));
+        countdowns.collect::<Vec<String>>().join("  ")
+    }
+
+    /**
+     * Refresh the countdowns in the status row, and call the expiration function of the
+     * expired countdowns. The Action returned by an expiration function, if any, is returned.
+     */
+    fn countdowns_tick(&mut self, screen: &mut Screen) -> Option<Action> {
+        let now = tokio::time::Instant::now();
+        self.tick = now + countdown::TICK;
+        let mut action = None;
+        let (expired, countdowns) = std::mem::take(&mut self.countdowns)
+            .into_iter()
+            .partition::<Vec<_>, _>(|countdown| countdown.deadline <= now);
+        self.countdowns = countdowns;
+        for countdown in expired {
+            if let Some(expired) = (countdown.on_expire)() {
+                action.get_or_insert(expired);
+            }
+        }
+
+        screen.countdowns = self.countdowns_display(now);
+        if self.menu.is_none() {
+            out!(self.output, "{}", screen.status());
+        }
+        if let Some(Action::Command { .. }) = action {
+            // The command line being typed is abandoned
+            self.newline();
+            self.snapshot(screen, false);
+            self.do_reset = true;
+        }
+        action
+    }
+
+    /** Execute the command if it is a built-in command and tell if it was */
+    fn builtin(&mut self, args: &[String]) -> bool {
+        match args[0].as_str() {
+            "set" if args.len() == 1 => {
+                for (name, value) in &self.variables {
+                    self.output
+                        .write(false, format_args!("{}={}\n", name, value));
+                }
+            }
+            "set" => {
+                let name = &args[1];
+                if name.is_empty() || !name.chars().all(is_varchar) {
+                    self.print_error(&format!("set: invalid variable name: {}", name));
+                } else {
+                    self.variables.insert(name.clone(), args[2..].join(" "));
+                }
+            }
+            "unset" => {
+                for name in &args[1..] {
+                    self.variables.remove(name);
+                }
+            }
+            "echo" => {
+                self.output
+                    .write(false, format_args!("{}\n", args[1..].join(" ")));
+            }
+            _ => {
+                return false;
+            }
+        }
+        true
+    }
+
+    /**
+     * Return an Action demanded by the user in CLI.
+     */
+    pub async fn getaction(&mut self) -> Result<Action> {
+        self.getaction_idle(None).await
+    }
+
+    /**
+     * Return an Action demanded by the user in CLI, or [`Action::Timeout`] if no input is
+     * received during the timeout, e.g. to refresh a dynamic prompt or to log out an idle
+     * user. Unlike a timeout around [`Cli::getaction`], the keys partially received (e.g.
+     * the beginning of an escape sequence) are kept for the next call.
+     */
+    pub async fn getaction_timeout(&mut self, timeout: Duration) -> Result<Action> {
+        self.getaction_idle(Some(timeout)).await
+    }
+
+    /** Return an Action demanded by the user, or a timeout after `idle` without input */
+    async fn getaction_idle(&mut self, idle: Option<Duration>) -> Result<Action> {
+        self.enable_raw_mode()?;
+        let screen = self.screen.clone();
+        while let Some(line) = self.script_next()? {
+            if self.script_echo {
+                outln!(self.output, "{}{}", self.prompt_text, line);
+            }
+            let args = self.alias(self.split(&line, true).0);
+            if self.variables_enabled && self.builtin(&args) {
+                continue;
+            }
+            return Ok(Action::Command { line, args });
+        }
+        if self.do_reset {
+            // The prompt replaces the progress displayed on its own line
+            printer::lock(&screen).progress_erase();
+            if self.cursor_sync {
+                self.sync_cursor().await?;
+            }
+            let mut screen = printer::lock(&screen);
+            let _batch = self.output.batch();
+            self.reset()?;
+            self.snapshot(&mut screen, true);
+            out!(self.output, "{}", screen.status());
+            self.do_reset = false;
+        }
+        // Output of the keys decoded from the same input, written at once
+        let mut batch = None;
+        // Time at which the chord being typed is discarded, or the ESC reported as a key
+        let mut chord_deadline = None;
+        // Time at which the timeout is reported, if no input is received before
+        let mut idle_deadline = None;
+        loop {
+            let decoded = match self.typeahead.pop_front() {
+                Some(decoded) => Some(decoded),
+                None => {
+                    batch = None;
+                    let ticking = !self.countdowns.is_empty();
+                    let tick = self.tick;
+                    let chord = self.decoder.chord_pending();
+                    let escape = self.decoder.escape_pending();
+                    let timeout = match (chord, escape) {
+                        (true, _) => *chord_deadline.get_or_insert_with(|| {
+                            tokio::time::Instant::now() + self.chord_timeout
+                        }),
+                        (false, true) => *chord_deadline
+                            .get_or_insert_with(|| tokio::time::Instant::now() + ESCAPE_TIMEOUT),
+                        (false, false) => tick,
+                    };
+                    let idle_timeout = idle.map(|idle| {
+                        *idle_deadline.get_or_insert_with(|| tokio::time::Instant::now() + idle)
+                    });
+                    let input = tokio::select! {
+                        input = self.read_input() => Some(input?),
+                        _ = tokio::time::sleep_until(tick), if ticking => None,
+                        _ = tokio::time::sleep_until(timeout), if chord || escape => None,
+                        _ = tokio::time::sleep_until(idle_timeout.unwrap_or(tick)),
+                            if idle_timeout.is_some() => None,
+                    };
+                    if input.is_some() {
+                        chord_deadline = None;
+                        idle_deadline = None;
+                    } else if chord && tokio::time::Instant::now() >= timeout {
+                        chord_deadline = None;
+                        self.decoder.cancel_chord();
+                        self.bell();
+                    } else if escape && tokio::time::Instant::now() >= timeout {
+                        // A lone ESC: the Escape key was pressed
+                        chord_deadline = None;
+                        let key = self.decoder.escape();
+                        self.typeahead.push_back(Decoded::Key(key));
+                    }
+                    if input.is_none() && ticking && tokio::time::Instant::now() >= tick {
+                        let mut screen = printer::lock(&screen);
+                        if let Some(action) = self.countdowns_tick(&mut screen) {
+                            return Ok(action);
+                        }
+                    }
+                    let now = tokio::time::Instant::now();
+                    if input.is_none() && idle_timeout.is_some_and(|timeout| now >= timeout) {
+                        return Ok(Action::Timeout);
+                    }
+                    continue;
+                }
+            };
+            if let (Some(recording), Some(decoded)) = (&mut self.kbd_recording, &decoded) {
+                let action = decoded.action();
+                if !matches!(
+                    action,
+                    Some(EditAction::StartKbdMacro)
+                        | Some(EditAction::EndKbdMacro)
+                        | Some(EditAction::CallLastKbdMacro)
+                ) {
+                    recording.push(decoded.clone());
+                }
+            }
+            let action = match decoded {
+                Some(Decoded::Action(action)) => action,
+                Some(Decoded::Key(key)) => match key.action() {
+                    Some(action) => action,
+                    None => {
+                        return Ok(Action::Key(key));
+                    }
+                },
+                Some(Decoded::CursorPosition(_, _)) => {
+                    // Late reply to a cursor position query
+                    continue;
+                }
+                Some(Decoded::Paste(text)) => {
+                    let mut screen = printer::lock(&screen);
+                    batch.get_or_insert_with(|| self.output.batch());
+                    self.paste(&text)?;
+                    self.plugins_render();
+                    self.snapshot(&mut screen, true);
+                    out!(self.output, "{}", screen.status());
+                    continue;
+                }
+                Some(Decoded::Unknown(sequence)) => {
+                    return Ok(Action::Key(KeyEvent::Unknown(sequence)));
+                }
+                None => {
+                    continue;
+                }
+            };
+            if action == EditAction::EditInEditor && !self.terminal.local() {
+                // The editor would run on the terminal of the process, not of the user
+                self.bell();
+                continue;
+            }
+            if action == EditAction::EditInEditor {
+                batch = None;
+                self.edit_in_editor().await?;
+                let mut screen = printer::lock(&screen);
+                self.snapshot(&mut screen, true);
+                out!(self.output, "{}", screen.status());
+                continue;
+            }
+            let mut screen = printer::lock(&screen);
+            batch.get_or_insert_with(|| self.output.batch());
+            if action == EditAction::ToggleDoNotDisturb {
+                let hold = !screen.hold;
+                screen.sethold(hold);
+                continue;
+            }
+            if matches!(
+                action,
+                EditAction::StartKbdMacro | EditAction::EndKbdMacro | EditAction::CallLastKbdMacro
+            ) {
+                self.kbd_macro_action(&action);
+                continue;
+            }
+            let action = self.edit(action)?;
+            let active = !matches!(
+                action,
+                Some(Action::Command { .. }) | Some(Action::NoAction)
+            );
+            self.snapshot(&mut screen, active);
+            if active && self.menu.is_none() {
+                out!(self.output, "{}", screen.status());
+            }
+            if let Some(action) = action {
+                return Ok(action);
+            }
+        }
+    }
+
+    /**
+     * Run the command line loop: each command is executed by the handler, while the user
+     * keeps typing the next commands.
+     *
+     * A command submitted while the previous one is still running is queued, and displayed
+     * below the command line until it is executed. A command submitted while the queue is
+     * full (see [`Cli::setqueuelimit`]) is dropped.
+     *
+     * The output of the commands should be written with a [`Printer`], so it does not mess
+     * up the command line being typed. The loop stops when the handler returns false or
+     * fails ([`Error::Command`]), or when EOT is received on an empty line. The completion
+     * demands reported as [`Action::AutoComplete`] are ignored: a [`Completer`] should be
+     * used instead.
+     */
+    pub async fn run<F, Fut>(&mut self, mut handler: F) -> Result<()>
+    where
+        F: FnMut(Vec<String>) -> Fut,
+        Fut: Future<Output = std::result::Result<bool, BoxError>>,
+    {
+        let screen = self.screen.clone();
+        let mut queue = VecDeque::<Vec<String>>::new();
+        let mut running: Option<Pin<Box<Fut>>> = None;
+        loop {
+            // The Action being read is not cancelled when a command completes: getaction is
+            // not cancel-safe, e.g. while the command line is edited in an editor
+            let action = {
+                let mut getaction = std::pin::pin!(self.getaction());
+                loop {
+                    tokio::select! {
+                        action = &mut getaction => break action?,
+                        next = async { running.as_mut().unwrap().await }, if running.is_some() => {
+                            running = None;
+                            if !next.map_err(Error::Command)? {
+                                return Ok(());
+                            }
+                            if let Some(cmd) = queue.pop_front() {
+                                running = Some(Box::pin(handler(cmd)));
+                                queue_display(&screen, &queue);
+                            }
+                        }
+                    }
+                }
+            };
+            match action {
+                Action::Command { args: cmd, .. } if cmd[0].is_empty() => {}
+                Action::Command { args: cmd, .. } if running.is_none() => {
+                    running = Some(Box::pin(handler(cmd)));
+                }
+                Action::Command { args: cmd, .. } if queue.len() < self.queue_limit => {
+                    queue.push_back(cmd);
+                    queue_display(&screen, &queue);
+                }
+                Action::Command { args: cmd, .. } => {
+                    self.print_error(&format!("Queue full: {} dropped", cmd.join(" ")));
+                }
+                Action::AutoComplete(_) | Action::Key(_) | Action::Timeout => {}
+                Action::NoAction => break,
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Print the bytes received for each key pressed, with the decoded key and the editing
+     * action bound to it, until EOT (Ctrl+D) is received. Keys are not edited meanwhile.
+     *
+     * It helps to report the keys not supported on a terminal, and to find the sequences to
+     * bind with [`Cli::bindsequence`].
+     */
+    pub async fn debugkeys(&mut self) -> Result<()> {
+        outln!(
+            self.output,
+            "Press keys to display their sequence, Ctrl+D to stop."
+        );
+        let mut bytes = Vec::<u8>::new();
+        loop {
+            let c = self.reader.read_u8().await?;
+            bytes.push(c);
+            let decoded = match self.decoder.feed(c) {
+                Some(decoded) => decoded,
+                None => {
+                    continue;
+                }
+            };
+            let (key, action) = match decoded {
+                Decoded::Key(key) => (format!("{:?}", key), key.action()),
+                Decoded::Action(action) => (String::from("(bound sequence)"), Some(action)),
+                Decoded::CursorPosition(row, col) => (format!("(cursor {};{})", row, col), None),
+                Decoded::Unknown(_) => (String::from("(unknown)"), None),
+                Decoded::Paste(text) => (format!("(paste {:?})", text), None),
+            };
+            let hex = bytes.iter().map(|byte| format!("{:02x}", byte));
+            outln!(
+                self.output,
+                "{:<20} {:<16} {:<16} {}",
+                hex.collect::<Vec<String>>().join(" "),
+                String::from_utf8_lossy(&bytes).escape_debug().to_string(),
+                key,
+                action
+                    .as_ref()
+                    .map_or(String::from("-"), |action| format!("{:?}", action))
+            );
+            bytes.clear();
+            if action == Some(EditAction::EndOfFile) {
+                return Ok(());
+            }
+        }
+    }
+
+    /**
+     * Turn the Cli into a [`Stream`](futures_core::Stream) of the Actions performed by the
+     * user, to select them along with other event sources.
+     */
+    pub fn into_stream(self) -> ActionStream {
+        ActionStream::new(self)
+    }
+
+    /** Perform an editing action and return the Action to report to the application, if any */
+    fn edit(&mut self, action: EditAction) -> Result<Option<Action>> {
+        if action != EditAction::Complete {
+            self.tab_pending = false;
+        }
+        if action != EditAction::YankLastArg {
+            self.yank_last = None;
+        }
+
+        if self.pager.is_some() {
+            self.pager_key(action)?;
+            return Ok(None);
+        }
+
+        if self.menu.is_some() {
+            match action {
+                EditAction::Complete | EditAction::ForwardChar | EditAction::NextHistory => {
+                    self.menu_next()?;
+                    self.plugins_render();
+                    return Ok(None);
+                }
+                EditAction::BackwardChar | EditAction::PreviousHistory => {
+                    self.menu_prev()?;
+                    self.plugins_render();
+                    return Ok(None);
+                }
+                _ => {
+                    self.menu_close()?;
+                }
+            }
+        }
+
+        if self.search_key(&action)? {
+            self.plugins_render();
+            return Ok(None);
+        }
+
+        if self.plugins_key(&action)? {
+            self.plugins_render();
+            return Ok(None);
+        }
+
+        match action {
+            EditAction::Insert(c) => {
+                if c == ' ' {
+                    self.abbreviate()?;
+                }
+                self.addchar(c)?
+            }
+            EditAction::BeginningOfLine => self.cursor_reset()?,
+            EditAction::EndOfLine => self.cursor_end()?,
+            EditAction::ForwardChar => self.cursor_right()?,
+            EditAction::BackwardChar => self.cursor_left()?,
+            EditAction::PreviousHistory => self.history_prev()?,
+            EditAction::NextHistory => self.history_next()?,
+            EditAction::ToggleHistoryScope => self.editor.toggle_history_scope(),
+            EditAction::ReverseSearchHistory => self.search_start(false)?,
+            EditAction::ForwardSearchHistory => self.search_start(true)?,
+            EditAction::Abort => {}
+            EditAction::YankLastArg => self.yank_last_arg()?,
+            EditAction::BackwardDeleteChar => self.backspace()?,
+            EditAction::DeleteChar => self.suppr()?,
+            EditAction::TransposeChars
+            | EditAction::TransposeWords
+            | EditAction::UpcaseWord
+            | EditAction::DowncaseWord
+            | EditAction::CapitalizeWord => self.transform(&action)?,
+            EditAction::Complete => {
+                let args = self.tokenize(&self.editor.line[..self.editor.cursor]).0;
+                match self.completions(&args) {
+                    Some(words) => self.complete(&words)?,
+                    None => {
+                        return Ok(Some(Action::AutoComplete(args)));
+                    }
+                }
+            }
+            EditAction::AcceptLine => {
+                if let Some(args) = self.submit()? {
+                    if self.variables_enabled && self.builtin(&args) {
+                        self.reset()?;
+                        return Ok(None);
+                    }
+                    if let Some(help) = self.help(&args) {
+                        self.output.write(false, format_args!("{}\n", help));
+                        self.reset()?;
+                        return Ok(None);
+                    }
+                    self.do_reset = true;
+                    let line = self.editor.line.clone();
+                    return Ok(Some(Action::Command { line, args }));
+                }
+            }
+            EditAction::EndOfFile => {
+                if self.editor.line.is_empty() {
+                    self.keypad_local();
+                    return Ok(Some(Action::NoAction));
+                }
+            }
+            EditAction::ToggleDoNotDisturb | EditAction::EditInEditor => {
+                // Handled by getaction, which owns the screen
+            }
+            EditAction::QuotedInsert => {
+                // Handled by the decoder, which reports the next byte as an insertion
+            }
+            EditAction::StartKbdMacro | EditAction::EndKbdMacro | EditAction::CallLastKbdMacro => {
+                // Handled by getaction, which owns the decoded keys
+            }
+        }
+        self.rprompt_render();
+        self.plugins_render();
+        Ok(None)
+    }
+
+    /** Let plugins handle an editing action, and return true if one of them handled it */
+    fn plugins_key(&mut self, action: &EditAction) -> Result<bool> {
+        if self.plugins.is_empty() {
+            return Ok(false);
+        }
+
+        let mut line = LineBuffer::new(std::mem::take(&mut self.editor.line), self.editor.cursor);
+        let handled = self
+            .plugins
+            .iter_mut()
+            .any(|plugin| plugin.on_key(&mut line, action));
+        let modified = line.modified();
+        (self.editor.line, self.editor.cursor) = line.into_parts();
+        if modified {
+            self.refresh_line()?;
+        }
+        Ok(handled)
+    }
+
+    /** Report the changes of the command line and its rendering to the plugins */
+    fn plugins_render(&mut self) {
+        if self.plugins.is_empty() {
+            return;
+        }
+
+        let mut line = LineBuffer::new(self.editor.line.clone(), self.editor.cursor);
+        if self.editor.line != self.plugins_line {
+            wipe(&mut self.plugins_line);
+            self.plugins_line.push_str(&self.editor.line);
+            for plugin in &mut self.plugins {
+                plugin.on_change(&line);
+            }
+        }
+        for plugin in &mut self.plugins {
+            plugin.on_render(&line);
+        }
+        line.wipe();
+    }
+
+    /**
+     * Auto-complete the current command with the provided list of possible words
+     *
+     * The inserted text is escaped, or closed with a double quote when completing inside a
+     * string, so the command is parsed back with the selected word as is.
+     *
+     * The word list is filtered and ranked according to the [`Matcher`] of the Cli, so it may
+     * contain words which do not match the current input.
+     */
+    pub fn autocomplete(&mut self, words: &[String]) -> Result<()> {
+        let screen = self.screen.clone();
+        let mut screen = printer::lock(&screen);
+        self.complete(words)?;
+        self.snapshot(&mut screen, true);
+        if self.menu.is_none() {
+            out!(self.output, "{}", screen.status());
+        }
+        Ok(())
+    }
+
+    /** Auto-complete the current command, see [`Cli::autocomplete`] */
+    fn complete(&mut self, words: &[String]) -> Result<()> {
+        if words.is_empty() {
+            // Nothing to complete
+            self.bell();
+            return Ok(());
+        }
+
+        // Filter and rank possible words
+        let args = self.tokenize(&self.editor.line[..self.editor.cursor]).0;
+        let lastarg = args.last().unwrap().clone();
+        let mut words = words.to_vec();
+        for plugin in &mut self.plugins {
+            plugin.on_complete(&args, &mut words);
+        }
+        let words = self.matcher.filter(&lastarg, &words);
+        if words.is_empty() {
+            self.bell();
+            return Ok(());
+        }
+
+        // Retrieve common word, as long as it still matches the typed word
+        let mut common = words[0].as_str();
+        for word in &words {
+            common = common_chars(word, common);
+        }
+        let typed = match self.matcher.score(&lastarg, common) {
+            Some(_) => common.to_string(),
+            None => lastarg.clone(),
+        };
+
+        if words.len() == 1 {
+            // Complete current line
+            let completed = !typed.ends_with('/');
+            self.complete_word(&lastarg, &typed, completed)?;
+        } else if self.completion_menu && words.len() > self.menu_threshold && !self.tab_pending {
+            // Too many possibilities: a second Tab is required to open the menu
+            self.complete_word(&lastarg, &typed, false)?;
+            self.bell();
+            self.tab_pending = true;
+        } else if self.completion_menu {
+            // Complete current line with common word and open the menu
+            self.tab_pending = false;
+            self.complete_word(&lastarg, &typed, false)?;
+            self.menu = Some(Menu {
+                words,
+                selected: None,
+                anchor: self.editor.line.clone(),
+                position: self.editor.cursor,
+                typed,
+                rows: 0,
+            });
+            self.menu_render()?;
+        } else {
+            // Display all possibilites
+            let mut words = words;
+            if self.completion_sort {
+                words.sort();
+            }
+            let text = columns(&words, self.terminal.size().0);
+            // Write back partially completed command
+            self.replace_word(&lastarg, &typed, false);
+            if words.len() > self.completion_query {
+                // Ask the user before displaying too many possibilities
+                self.newline();
+                out!(
+                    self.output,
+                    "Display all {} possibilities? (y or n)",
+                    words.len()
+                );
+                self.pager = Some(Pager::Query(text.lines().map(String::from).collect()));
+            } else {
+                self.newline();
+                out!(self.output, "{}", text);
+                self.refresh_line()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Set the [`Matcher`] used to filter and rank the possible words of a completion.
+     *
+     * Default is [`Matcher::Prefix`].
+     */
+    pub fn setmatcher(&mut self, matcher: Matcher) -> &mut Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /**
+     * Set the name of the prompt
+     *
+     * The prompt may contain ANSI escape sequences (e.g. colors), see [`PromptBuilder`].
+     */
+    pub fn setprompt(&mut self, prompt: &str) -> &mut Self {
+        self.setdynamicprompt(String::from(prompt))
+    }
+
+    /**
+     * Set a dynamic prompt, evaluated each time the prompt is displayed for a new command.
+     *
+     * Any `Fn() -> String` closure can be used as a [`Prompt`], allowing to display the current
+     * time, directory or connection state without calling [`Cli::setprompt`] before each
+     * [`Cli::getaction`].
+     */
+    pub fn setdynamicprompt(&mut self, prompt: impl Prompt + Send + 'static) -> &mut Self {
+        self.prompt_text = prompt.prompt();
+        self.prompt = Box::new(prompt);
+        self
+    }
+
+    /**
+     * Set a prompt displayed on the right edge of the terminal (e.g. clock, status).
+     *
+     * The right prompt disappears when the command line reaches it.
+     */
+    pub fn setrightprompt(&mut self, prompt: &str) -> &mut Self {
+        self.setdynamicrightprompt(String::from(prompt))
+    }
+
+    /** Set a dynamic right prompt, evaluated each time the prompt is displayed for a new command */
+    pub fn setdynamicrightprompt(&mut self, prompt: impl Prompt + Send + 'static) -> &mut Self {
+        self.rprompt_text = prompt.prompt();
+        self.rprompt = Some(Box::new(prompt));
+        self
+    }
+
+    /** Set the prompt displayed when a command line is continued on a new line */
+    pub fn setcontinuationprompt(&mut self, prompt: &str) -> &mut Self {
+        self.continuation_prompt = prompt.into();
+        self
+    }
+
+    /**
+     * Set a minimal prompt (e.g. "> ") replacing the prompt of a submitted command line, to
+     * keep the scrollback compact. The right prompt is also erased from the command line.
+     */
+    pub fn settransientprompt(&mut self, prompt: &str) -> &mut Self {
+        self.transient_prompt = Some(prompt.into());
+        self
+    }
+
+    /**
+     * Set the policy applied when Enter is pressed on an incomplete command line.
+     *
+     * A command line is incomplete when it ends inside a string (unterminated double quote)
+     * or when the [`Validator`] reports it as [`Validation::Incomplete`].
+     * Default policy is [`IncompletePolicy::Submit`].
+     */
+    pub fn setincompletepolicy(&mut self, policy: IncompletePolicy) -> &mut Self {
+        self.incomplete_policy = policy;
+        self
+    }
+
+    /** Set a [`Validator`] checking the command line before its submission */
+    pub fn setvalidator(&mut self, validator: impl Validator + Send + 'static) -> &mut Self {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+
+    /**
+     * Enable or disable comments in entered lines.
+     *
+     * When enabled, an unquoted `#` starting a word begins a comment: the rest of the line
+     * is ignored when splitting the command into arguments. The raw line, including its
+     * comment, is still kept in history.
+     */
+    pub fn setcomments(&mut self, enable: bool) -> &mut Self {
+        self.comments = enable;
+        self
+    }
+
+    /**
+     * Set a [`Tokenizer`] splitting the command line in arguments, instead of the
+     * [`DefaultTokenizer`].
+     *
+     * Comments are then handled by the tokenizer: [`Cli::setcomments`] has no effect.
+     */
+    pub fn settokenizer(&mut self, tokenizer: impl Tokenizer + Send + 'static) -> &mut Self {
+        self.tokenizer = Some(Box::new(tokenizer));
+        self
+    }
+
+    /**
+     * Enable or disable the interactive completion menu.
+     *
+     * When enabled and multiple words are possible, [`Cli::autocomplete`] displays them in a
+     * menu below the prompt. Repeated Tab (or arrows) then cycle through the candidates,
+     * inserting the selected one in the command line. Any other key closes the menu.
+     */
+    pub fn setcompletionmenu(&mut self, enable: bool) -> &mut Self {
+        self.completion_menu = enable;
+        self
+    }
+
+    /**
+     * Set the maximum number of possible words for which the completion menu is opened
+     * immediately.
+     *
+     * Above this threshold, the bell is rung on the first Tab and a second Tab is required to
+     * open the menu. By default, the menu is always opened immediately.
+     */
+    pub fn setmenuthreshold(&mut self, threshold: usize) -> &mut Self {
+        self.menu_threshold = threshold;
+        self
+    }
+
+    /**
+     * Set how the user is notified when a completion is ambiguous or impossible.
+     *
+     * Default is [`Bell::Audible`].
+     */
+    pub fn setbell(&mut self, bell: Bell) -> &mut Self {
+        self.bell = bell;
+        self
+    }
+
+    /**
+     * Set the number of possible words above which the user is asked before listing them.
+     *
+     * When the user accepts, the possible words are displayed screenful by screenful:
+     * Space displays the next screen, Enter the next line and `q` stops the listing.
+     * Default is 100.
+     */
+    pub fn setcompletionquery(&mut self, items: usize) -> &mut Self {
+        self.completion_query = items;
+        self
+    }
+
+    /**
+     * Enable or disable the sorting of the possible words listed by [`Cli::autocomplete`].
+     */
+    pub fn setcompletionsort(&mut self, enable: bool) -> &mut Self {
+        self.completion_sort = enable;
+        self
+    }
+
+    /**
+     * Enable or disable the history index indicator.
+     *
+     * When enabled, the prompt is decorated with the index of the history entry being
+     * recalled while navigating the history (e.g. `[123] > cmd`).
+     * Indexes start at 1, and are kept when the oldest entries are evicted (see
+     * [`Cli::sethistorymax`]).
+     */
+    pub fn sethistoryindicator(&mut self, enable: bool) -> &mut Self {
+        self.history_indicator = enable;
+        self
+    }
+
+    /**
+     * Add a [`Plugin`] extending the line editor.
+     *
+     * Plugins are stacked: their hooks are called in the order they were added.
+     */
+    pub fn addplugin(&mut self, plugin: impl Plugin + Send + 'static) -> &mut Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /**
+     * Enable or disable the application mode of the cursor keys and keypad while prompting.
+     *
+     * In application mode, the terminal sends SS3 sequences (e.g. `ESC O A`) for the cursor
+     * keys and distinct sequences for the keypad keys. Such sequences are always decoded, but
+     * some terminals only send them in application mode. The terminal is configured back in
+     * normal mode when a command is submitted.
+     */
+    pub fn setkeypadmode(&mut self, enable: bool) -> &mut Self {
+        self.keypad_mode = enable;
+        self
+    }
+
+    /**
+     * Configure the terminal in character mode (no echo, no line buffering), as done when
+     * the Cli is created.
+     *
+     * It is also done by [`Cli::getaction`] if needed: the raw mode can be disabled between
+     * the prompts, e.g. to run another program using the terminal.
+     */
+    pub fn enable_raw_mode(&mut self) -> Result<()> {
+        if !self.raw {
+            self.terminal.raw()?;
+            self.raw = true;
+        }
+        Ok(())
+    }
+
+    /**
+     * Configure back the terminal in its original mode, until the raw mode is enabled again
+     * (see [`Cli::enable_raw_mode`]). It should not be called while prompting.
+     *
+     * A Cli created early can disable the raw mode until the first prompt:
+     *
+     * ```no_run
+     * # async fn example() -> eyre::Result<()> {
+     * let mut cli = tokiocli::Cli::new()?;
+     * cli.disable_raw_mode()?;
+     * // The terminal is in its original mode until getaction is called
+     * let action = cli.getaction().await?;
+     * # Ok(())
+     * # }
+     * ```
+     */
+    pub fn disable_raw_mode(&mut self) -> Result<()> {
+        if self.raw {
+            self.keypad_local();
+            self.terminal.restore()?;
+            self.raw = false;
+        }
+        Ok(())
+    }
+
+    /**
+     * Configure back the terminal in its original state, as done when the Cli is dropped.
+     *
+     * It should be called before exiting with [`std::process::exit`], which does not run the
+     * destructors. The Cli should not be used anymore afterwards.
+     */
+    pub fn restore(&mut self) -> Result<()> {
+        self.disable_raw_mode()
+    }
+
+    /**
+     * Install a panic hook configuring back the terminal in its original state, before the
+     * panic message is printed by the previous hook, while the Cli is alive.
+     *
+     * Otherwise, the terminal is only restored when the Cli is dropped while unwinding, after
+     * the panic message, and never if the panics abort the process.
+     */
+    pub fn restore_on_panic(&mut self) -> &mut Self {
+        let restorer = match self.terminal.restorer() {
+            Some(restorer) => Arc::new(Mutex::new(Some(restorer))),
+            None => return self,
+        };
+        self.panic_restorer = Some(restorer.clone());
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if let Some(restore) = restorer
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .take()
+            {
+                restore();
+            }
+            previous(info);
+        }));
+        self
+    }
+
+    /**
+     * Enable or disable the bracketed paste mode while prompting, disabled by default.
+     *
+     * The terminal then marks the text pasted by the user, which is inserted as is in the
+     * command line: a line break continues the command on a new line instead of submitting
+     * it, and the whole text is written at once.
+     */
+    pub fn setbracketedpaste(&mut self, enable: bool) -> &mut Self {
+        self.bracketed_paste = enable;
+        self
+    }
+
+    /**
+     * Configure how the terminal reports keys pressed with the Meta (Alt) modifier.
+     *
+     * Default is [`MetaKey::Escape`].
+     */
+    pub fn setmetakey(&mut self, meta: MetaKey) -> &mut Self {
+        self.decoder.setmeta(meta);
+        self
+    }
+
+    /**
+     * Set the maximum length of the command line, in characters, 65536 by default.
+     *
+     * The bell rings when a key is typed while the command line is full, and pasted text
+     * is truncated. It protects a Cli exposed to untrusted clients (e.g. by a
+     * [`UnixServer`]) from pathological input, such as megabytes of pasted data.
+     */
+    pub fn setmaxlinelength(&mut self, max: usize) -> &mut Self {
+        self.max_line = max;
+        self.decoder.setpastelimit(max.saturating_mul(4));
+        self
+    }
+
+    /**
+     * Configure what the Tab key does: auto-complete the word under the cursor, or insert
+     * a tab or spaces, e.g. for commands taking tab-separated data. A literal tab can also
+     * be entered with a quoted insert (Ctrl+V Tab).
+     *
+     * Default is [`TabKey::Complete`]. A sequence bound to the Tab key with
+     * [`Cli::bindsequence`] takes precedence.
+     */
+    pub fn settabkey(&mut self, tab: TabKey) -> &mut Self {
+        self.decoder.settab(tab);
+        self
+    }
+
+    /**
+     * Bind a custom input sequence to an editing action.
+     *
+     * This allows to support keys sent by niche terminals (e.g. proprietary escape sequences)
+     * without modifying the decoder. Custom sequences take precedence over standard sequences.
+     *
+     * The sequence may also be a chord of several keys, e.g. `b"\x18\x05"` for Ctrl+X Ctrl+E.
+     * A chord is discarded if its next key is not pressed in time (see
+     * [`Cli::setchordtimeout`]), or if the key pressed completes no bound chord.
+     */
+    pub fn bindsequence(&mut self, sequence: &[u8], action: EditAction) -> &mut Self {
+        self.decoder.bind(sequence, action);
+        self
+    }
+
+    /**
+     * Load the settings and key bindings of a readline configuration file (inputrc), so the
+     * users can customize the applications built on the Cli.
+     *
+     * The following subset of the format is supported:
+     * - key bindings to readline functions, e.g. `"\C-x\C-e": edit-and-execute-command` or
+     *   `Control-t: transpose-chars`, for the functions matching an [`EditAction`].
+     * - `set bell-style none|audible|visible` (the visible bell rings the terminal bell),
+     *   `set completion-ignore-case on|off`,
+     *   `set completion-query-items N`, `set convert-meta on|off`,
+     *   `set enable-bracketed-paste on|off`, `set history-size N` and
+     *   `set keyseq-timeout MS`. Only the emacs editing mode is available.
+     * - `$if mode=...`, `$if term=...`, `$else`, `$endif` and `$include path`.
+     *
+     * The other lines (e.g. macros, vi mode bindings) are ignored, as readline does.
+     */
+    pub fn load_inputrc(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.inputrc_load(path.as_ref(), 0)
+    }
+
+    /**
+     * Load the readline configuration file of the user (`$INPUTRC` or `~/.inputrc`), if it
+     * exists, see [`Cli::load_inputrc`].
+     */
+    pub fn load_user_inputrc(&mut self) -> Result<()> {
+        let Some(path) = inputrc::default_path() else {
+            return Ok(());
+        };
+        match self.load_inputrc(path) {
+            Err(Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    /** Load an inputrc file, included by another one at the provided depth */
+    fn inputrc_load(&mut self, path: &Path, depth: usize) -> Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        for directive in inputrc::parse(&text) {
+            match directive {
+                Directive::Set(name, value) => self.inputrc_set(&name, &value),
+                Directive::Bind(sequence, action) => {
+                    self.bindsequence(&sequence, action);
+                }
+                Directive::Include(path) if depth < INPUTRC_DEPTH => {
+                    let _ = self.inputrc_load(&path, depth + 1);
+                }
+                Directive::Include(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /** Apply a variable set in an inputrc file, if it is supported */
+    fn inputrc_set(&mut self, name: &str, value: &str) {
+        let on = value.eq_ignore_ascii_case("on") || value == "1";
+        let number = value.parse::<usize>().ok();
+        match (name, number) {
+            ("bell-style", _) => {
+                let bell = match value {
+                    "none" | "off" => Bell::None,
+                    _ => Bell::Audible,
+                };
+                self.setbell(bell);
+            }
+            ("completion-ignore-case", _) => {
+                let matcher = match on {
+                    true => Matcher::PrefixIgnoreCase,
+                    false => Matcher::Prefix,
+                };
+                self.setmatcher(matcher);
+            }
+            ("completion-query-items", Some(items)) => {
+                self.setcompletionquery(items);
+            }
+            ("convert-meta", _) => {
+                let meta = match on {
+                    true => MetaKey::EightBit,
+                    false => MetaKey::Escape,
+                };
+                self.setmetakey(meta);
+            }
+            ("enable-bracketed-paste", _) => {
+                self.setbracketedpaste(on);
+            }
+            ("history-size", Some(max)) => {
+                self.sethistorymax(max);
+            }
+            ("keyseq-timeout", Some(timeout)) => {
+                self.setchordtimeout(Duration::from_millis(timeout as u64));
+            }
+            _ => {}
+        }
+    }
+
+    /** Set the time to wait for the next key of a chord, one second by default */
+    pub fn setchordtimeout(&mut self, timeout: Duration) -> &mut Self {
+        self.chord_timeout = timeout;
+        self
+    }
+
+    /**
+     * Set the entries reachable while navigating the history.
+     *
+     * Default is [`HistoryScope::All`]. The scope can also be toggled with ALT+H.
+     */
+    pub fn sethistoryscope(&mut self, scope: HistoryScope) -> &mut Self {
+        self.editor.sethistoryscope(scope);
+        self
+    }
+
+    /**
+     * Continue the incremental history search (Ctrl+R backward, Ctrl+S forward) from the
+     * other end of the history when no further entry matches, disabled by default.
+     *
+     * Otherwise, the bell is rung and the search is reported as failed in the prompt.
+     */
+    pub fn setsearchwraparound(&mut self, enable: bool) -> &mut Self {
+        self.search_wraparound = enable;
+        self
+    }
+
+    /**
+     * Enable or disable the history expansion, disabled by default.
+     *
+     * When enabled, the references to history entries are replaced on Enter: `!!` repeats
+     * the last command, `!n` the entry n (as numbered by [`Cli::sethistoryindicator`]), `!-n`
+     * the n-th last command and `!prefix` the last command starting with prefix. As in bash,
+     * nothing is expanded within single quotes or after a backslash. The expanded command
+     * is echoed and pushed to history.
+     */
+    pub fn sethistoryexpansion(&mut self, enable: bool) -> &mut Self {
+        self.history_expansion = enable;
+        self
+    }
+
+    /**
+     * Set how the history is saved to a file which other sessions may have saved to.
+     *
+     * Default is [`HistorySave::Merge`].
+     */
+    pub fn sethistorysave(&mut self, strategy: HistorySave) -> &mut Self {
+        self.editor.history.strategy = strategy;
+        self
+    }
+
+    /**
+     * Add a glob pattern of sensitive command lines (e.g. `login * --password *`), where `*`
+     * matches any sequence of characters and `?` any character.
+     *
+     * The command lines matching a pattern are not added to the history, and are left out
+     * of the session recording (see [`Cli::record`]) with all the events recorded while
+     * they were typed. The buffers of the command lines are zeroized after use with the
+     * `zeroize` feature. Passwords are read with [`Cli::read_password`].
+     */
+    pub fn addsensitivepattern(&mut self, pattern: &str) -> &mut Self {
+        self.sensitive.push(pattern.to_string());
+        self
+    }
+
+    /**
+     * Set the maximum number of entries of the history, e.g. for a long-lived daemon. The
+     * oldest entries are evicted beyond, in memory and when the history file is rewritten
+     * (see [`HistorySave`]).
+     */
+    pub fn sethistorymax(&mut self, max: usize) -> &mut Self {
+        self.editor.history.max = Some(max);
+        self.editor.history.evict();
+        self.editor.history_idx = None;
+        self
+    }
+
+    /**
+     * Load the history from a file, one entry per line.
+     *
+     * Loaded entries are considered as persisted entries, out of the current session.
+     */
+    pub async fn load_history(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.editor.history.load(path.as_ref()).await
+    }
+
+    /**
+     * Save the history to a file, one entry per line.
+     *
+     * The entries saved to the file by other sessions are kept, according to the strategy
+     * set with [`Cli::sethistorysave`].
+     */
+    pub async fn save_history(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let strategy = self.editor.history.strategy;
+        self.editor.history.save(path.as_ref(), strategy).await
+    }
+
+    /**
+     * Append the entries of the session not saved yet to a history file shared with other
+     * sessions, whatever the strategy set with [`Cli::sethistorysave`].
+     *
+     * The file is locked while it is written (on Unix), so that concurrent instances (e.g.
+     * several processes, or the connections of a [`UnixServer`]) can append to it safely.
+     * The entries appended by the other sessions are loaded with [`Cli::reload_history`].
+     */
+    pub async fn append_history(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.editor
+            .history
+            .save(path.as_ref(), HistorySave::Append)
+            .await
+    }
+
+    /**
+     * Load the entries saved to a history file by the other sessions since the last load,
+     * reload or save, so that their commands become reachable in this session. The entries
+     * are inserted before the entries of the current session. If another session rewrote
+     * the file meanwhile (e.g. truncated to the maximum history size), all of its entries
+     * are loaded again.
+     */
+    pub async fn reload_history(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.editor.history_idx = None;
+        self.editor.history.reload(path.as_ref()).await
+    }
+
+    /**
+     * Set the session recorded in the history entries entered from now on, e.g. to tell
+     * apart the commands of the different instances sharing a history file. The session
+     * should not contain a newline.
+     */
+    pub fn sethistorysession(&mut self, session: &str) -> &mut Self {
+        self.editor.history.session = Some(session.into());
+        self
+    }
+
+    /** Return the entries of the history, from the oldest to the most recent */
+    pub fn history(&self) -> &[HistoryEntry] {
+        self.editor.history.entries()
+    }
+
+    /**
+     * Return the entries of the history entered during the last `duration`, e.g. the
+     * commands run in the last hour. The entries without timestamp are not returned.
+     */
+    pub fn history_since(&self, duration: Duration) -> impl Iterator<Item = &HistoryEntry> {
+        let since = std::time::SystemTime::now()
+            .checked_sub(duration)
+            .unwrap_or(std::time::UNIX_EPOCH);
+        self.history()
+            .iter()
+            .filter(move |entry| entry.timestamp.is_some_and(|timestamp| timestamp >= since))
+    }
+
+    /**
+     * Enable session variables.
+     *
+     * References to variables ($name or ${name}) are replaced by their values in the
+     * arguments reported to the application, and the following commands are handled by the
+     * Cli itself:
+     * - `set`: list the variables.
+     * - `set name value...`: set a variable.
+     * - `unset name...`: remove variables.
+     * - `echo args...`: print the arguments.
+     *
+     * A backslash prevents the expansion of a reference (`\$name`). See also
+     * [`Cli::setenvironment`] and [`Cli::setstatus`].
+     */
+    pub fn setvariables(&mut self, enable: bool) -> &mut Self {
+        self.variables_enabled = enable;
+        self
+    }
+
+    /**
+     * Also replace the references to environment variables, when the session variables are
+     * enabled. A session variable overrides an environment variable with the same name.
+     */
+    pub fn setenvironment(&mut self, enable: bool) -> &mut Self {
+        self.environment = enable;
+        self
+    }
+
+    /**
+     * Report the status of the last command, referenced by `$?` when the session variables
+     * are enabled. The status is also recorded in the history entry of the last command.
+     */
+    pub fn setstatus(&mut self, status: i32) -> &mut Self {
+        self.status = Some(status);
+        self.editor.history.setstatus(status);
+        self
+    }
+
+    /** Set the value of a session variable */
+    pub fn setvar(&mut self, name: &str, value: &str) -> &mut Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /** Return the value of a session variable */
+    pub fn getvar(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(String::as_str)
+    }
+
+    /**
+     * Execute a script: the commands of the script are reported by the next calls to
+     * [`Cli::getaction`], before any command entered by the user.
+     *
+     * The script contains one command per line, and references to session variables are
+     * always expanded. Empty lines and lines starting with '#' are ignored. The script can use
+     * the following constructs:
+     *
+     * ```text
+     * if <word> | <word> == <word> | <word> != <word> (optionally prefixed with '!')
+     *     ...
+     * else
+     *     ...
+     * end
+     *
+     * for <name> in <words...>
+     *     ...
+     * end
+     * ```
+     *
+     * The script is fully parsed before its execution: an error is returned on syntax errors.
+     * The commands are not displayed, unless enabled with [`Cli::setscriptecho`].
+     *
+     * It can be used to execute an initialization file before the interactive session:
+     *
+     * ```no_run
+     * # async fn example(cli: &mut tokiocli::Cli) -> eyre::Result<()> {
+     * let rc = std::path::Path::new(&std::env::var("HOME")?).join(".democlirc");
+     * if rc.exists() {
+     *     cli.run_script(rc).await?;
+     * }
+     * # Ok(())
+     * # }
+     * ```
+     */
+    pub async fn run_script(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let text = tokio::fs::read_to_string(path).await?;
+        let body = script::parse(&text)?;
+        self.script.push(Frame::new(body));
+        Ok(())
+    }
+
+    /** Display the commands executed from a script after the prompt, as if entered */
+    pub fn setscriptecho(&mut self, enable: bool) -> &mut Self {
+        self.script_echo = enable;
+        self
+    }
+
+    /**
+     * Set the Highlighter styling the command line while the user types.
+     *
+     * The command line is fully repainted after each modification.
+     */
+    pub fn sethighlighter(&mut self, highlighter: impl Highlighter + Send + 'static) -> &mut Self {
+        self.highlighter = Some(Box::new(highlighter));
+        self
+    }
+
+    /**
+     * Set the Hinter suggesting a placeholder text after the command line, e.g. the
+     * expected arguments of the typed command.
+     *
+     * The command line is fully repainted after each modification.
+     */
+    pub fn sethinter(&mut self, hinter: impl Hinter + Send + 'static) -> &mut Self {
+        self.hinter = Some(Box::new(hinter));
+        self
+    }
+
+    /**
+     * Check that the terminal responds to CSI queries.
+     *
+     * A Device Status Report is sent to the terminal, and the terminal is considered responsive
+     * if it reports the cursor position (Row, Column) before the timeout. The reported position
+     * is returned, or None if the terminal did not respond. Keys pressed in the meantime are
+     * kept and handled by the next call to [`Cli::getaction`].
+     */
+    pub async fn probe(&mut self, timeout: Duration) -> Result<Option<(usize, usize)>> {
+        let position = self.query_position(timeout).await?;
+        self.responsive = Some(position.is_some());
+        Ok(position)
+    }
+
+    /**
+     * Query the cursor position to the terminal before displaying a new prompt.
+     *
+     * When the output written to the terminal (by the application or by any other process)
+     * does not end with a newline, the prompt is moved to the next line instead of being
+     * displayed after this output. Querying is disabled if the terminal does not respond.
+     */
+    pub fn setcursorsync(&mut self, enable: bool) -> &mut Self {
+        self.cursor_sync = enable;
+        self
+    }
+
+    /** Tell if the terminal responded to the last [`Cli::probe`], if any */
+    pub fn isresponsive(&self) -> Option<bool> {
+        self.responsive
+    }
+
+    /**
+     * Return a Printer, which can be used from any task to print messages while
+     * [`Cli::getaction`] is pending, without messing up the command line being typed.
+     */
+    pub fn printer(&self) -> Printer {
+        Printer::new(self.screen.clone())
+    }
+
+    /**
+     * Set the destination of the interactive output (prompt, command line, completions and
+     * messages of the Printers), the standard error by default.
+     *
+     * The Cli created with [`Cli::with_backend`] or [`Cli::with_tty`] already write to
+     * their terminal. Note that a [`OutputTarget::Writer`] is driven by a spawned task: this
+     * function must then be called from a Tokio runtime.
+     */
+    pub fn setoutput(&mut self, target: OutputTarget) -> &mut Self {
+        let recorder = self.output.setrecorder(None);
+        self.output = Output::target(target);
+        self.output.setrecorder(recorder);
+        printer::lock(&self.screen).output = self.output.clone();
+        self
+    }
+
+    /**
+     * Set the styles of the prompt, hints, completion menu selection and error messages.
+     */
+    pub fn settheme(&mut self, theme: Theme) -> &mut Self {
+        self.theme = theme;
+        self
+    }
+
+    /**
+     * Set the colors supported by the terminal: the colors of the theme are downgraded to
+     * the nearest supported ones, or removed. If the terminal supports no colors, the colors
+     * of the prompt, of the highlighted command line and of the hints are also removed.
+     *
+     * The colors supported are detected from the environment by default (see
+     * [`ColorSupport::detect`]), `NO_COLOR` being honored.
+     */
+    pub fn setcolorsupport(&mut self, support: ColorSupport) -> &mut Self {
+        self.color_support = support;
+        self
+    }
+
+    /** Return the colors supported by the terminal, see [`Cli::setcolorsupport`] */
+    pub fn color_support(&self) -> ColorSupport {
+        self.color_support
+    }
+
+    /**
+     * Record the session to a file: the bytes read from the terminal and written to it,
+     * with their time. The recording can be attached to a bug report about a rendering
+     * glitch, or replayed with [`Recording::replay`].
+     *
+     * The file is replaced if it exists. The recording stops when the Cli is dropped, or
+     * with [`Cli::stop_recording`].
+     */
+    pub fn record(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let recorder = Recorder::create(path.as_ref())?;
+        self.output.setrecorder(Some(recorder));
+        Ok(())
+    }
+
+    /**
+     * Record the session to a file in the asciinema format (asciicast v2), to publish a
+     * demo of the application. See [`Cli::record`].
+     */
+    pub fn record_cast(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let (cols, rows) = self.terminal.size();
+        let recorder = Recorder::create_cast(path.as_ref(), cols, rows)?;
+        self.output.setrecorder(Some(recorder));
+        Ok(())
+    }
+
+    /** Stop the recording of the session started with [`Cli::record`] */
+    pub fn stop_recording(&mut self) {
+        self.output.setrecorder(None);
+    }
+
+    /**
+     * Set the toolbar displayed below the command line, e.g. a mode indicator, the status
+     * of a connection or key hints. An empty toolbar is erased.
+     *
+     * The toolbar shares the status row with the held messages, queued commands,
+     * countdowns and progress, and is truncated to the width of the terminal. It can also
+     * be updated from other tasks with [`Printer::settoolbar`].
+     */
+    pub fn settoolbar(&mut self, toolbar: &str) -> &mut Self {
+        printer::lock(&self.screen).settoolbar(toolbar.into());
+        self
+    }
+
+    /** Display a progress bar or a spinner, until the returned [`Progress`] is dropped */
+    pub fn progress(&self, label: &str) -> Progress {
+        Progress::new(self.screen.clone(), label)
+    }
+
+    /**
+     * Wrap long command lines on multiple rows.
+     *
+     * Instead of relying on the terminal to wrap the command line, the Cli wraps it itself and
+     * displays a marker in the gutter of the continuation rows. The command line is then fully
+     * repainted after each modification.
+     */
+    pub fn setsoftwrap(&mut self, enable: bool) -> &mut Self {
+        self.softwrap = enable;
+        self
+    }
+
+    /** Set the marker displayed in the gutter of wrapped rows (Default: a dimmed "↪ ") */
+    pub fn setwrapmarker(&mut self, marker: &str) -> &mut Self {
+        self.wrap_marker = marker.into();
+        self
+    }
+
+    /**
+     * Enable the do-not-disturb mode: the messages printed with a [`Printer`] while the prompt
+     * is displayed are held, and their count is displayed below the command line. The held
+     * messages are printed when the mode is disabled.
+     *
+     * The mode can also be toggled with [`EditAction::ToggleDoNotDisturb`].
+     */
+    pub fn setdonotdisturb(&mut self, enable: bool) -> &mut Self {
+        printer::lock(&self.screen).sethold(enable);
+        self
+    }
+
+    /**
+     * Suspend the Cli, so another program can use the terminal.
+     *
+     * The terminal is configured back in its original state and the command line is erased,
+     * until the returned guard is dropped.
+     */
+    pub fn suspend(&mut self) -> Result<Suspend<'_>> {
+        Suspend::new(self)
+    }
+
+    /**
+     * Switch the terminal to its alternate screen, to display a full-screen view.
+     *
+     * The command line is erased, until the returned guard is dropped: the main screen is
+     * then displayed back with its content intact.
+     */
+    pub fn enter_alt_screen(&mut self) -> AltScreen<'_> {
+        AltScreen::new(self)
+    }
+
+    /** Hide the cursor of the terminal */
+    pub fn hide_cursor(&self) {
+        out!(self.output, "{}", EscSeq::HideCursor);
+    }
+
+    /** Show the cursor of the terminal */
+    pub fn show_cursor(&self) {
+        out!(self.output, "{}", EscSeq::ShowCursor);
+    }
+
+    /**
+     * Display a countdown below the command line, refreshed every second.
+     *
+     * The provided function is called when the countdown reaches zero, while
+     * [`Cli::getaction`] is pending. The Action it returns, if any, is reported by getaction
+     * (the command line being typed is abandoned if a command is reported).
+     */
+    pub fn addcountdown(
+        &mut self,
+        label: &str,
+        duration: Duration,
+        on_expire: impl FnOnce() -> Option<Action> + Send + 'static,
+    ) -> &mut Self {
+        let on_expire: OnExpire = Box::new(on_expire);
+        self.countdowns.push(Countdown {
+            label: label.into(),
+            deadline: tokio::time::Instant::now() + duration,
+            on_expire,
+        });
+        self.tick = tokio::time::Instant::now();
+        self
+    }
+
+    /** Remove the countdowns with the provided label */
+    pub fn removecountdown(&mut self, label: &str) -> &mut Self {
+        self.countdowns.retain(|countdown| countdown.label != label);
+        let countdowns = self.countdowns_display(tokio::time::Instant::now());
+        printer::lock(&self.screen).countdowns = countdowns;
+        self
+    }
+
+    /** Set the maximum number of commands queued by [`Cli::run`] while a command is running */
+    pub fn setqueuelimit(&mut self, limit: usize) -> &mut Self {
+        self.queue_limit = limit;
+        self
+    }
+
+    /**
+     * Define an alias: when the first word of a command is the alias name, it is replaced by
+     * the words of the definition (e.g. `cli.setalias("ll", "list --long")`).
+     *
+     * The alias names are auto-completed as the registered commands.
+     */
+    pub fn setalias(&mut self, name: &str, value: &str) -> &mut Self {
+        self.aliases.insert(name.into(), value.into());
+        self
+    }
+
+    /** Remove an alias */
+    pub fn unsetalias(&mut self, name: &str) -> &mut Self {
+        self.aliases.remove(name);
+        self
+    }
+
+    /**
+     * Define an abbreviation: when the abbreviation is typed as the command name and followed
+     * by a space, it is replaced by its definition in the command line (e.g.
+     * `cli.setabbreviation("gco", "git checkout")`).
+     *
+     * Unlike an alias, the expansion is visible and can be edited before the command is
+     * submitted.
+     */
+    pub fn setabbreviation(&mut self, name: &str, value: &str) -> &mut Self {
+        self.abbreviations.insert(name.into(), value.into());
+        self
+    }
+
+    /** Remove an abbreviation */
+    pub fn unsetabbreviation(&mut self, name: &str) -> &mut Self {
+        self.abbreviations.remove(name);
+        self
+    }
+
+    /**
+     * Register a command name (or alias).
+     *
+     * When at least one command is registered, the first word of the command line is
+     * auto-completed from the registered commands, without reporting an
+     * [`Action::AutoComplete`] to the application. Completion of the arguments is still
+     * reported to the application.
+     */
+    pub fn addcommand(&mut self, name: &str) -> &mut Self {
+        if !self.commands.iter().any(|command| command == name) {
+            self.commands.push(name.into());
+            self.commands.sort();
+        }
+        self
+    }
+
+    /**
+     * Return the candidate closest to a mistyped word (e.g. a command name or an argument
+     * value), if any is close enough: within an [`edit_distance`] of a third of the word
+     * length, and at least 1.
+     *
+     * ```
+     * let commands = ["help", "exit", "show"];
+     * assert_eq!(tokiocli::Cli::suggest("hlep", commands).as_deref(), Some("help"));
+     * assert_eq!(tokiocli::Cli::suggest("reboot", commands), None);
+     * ```
+     */
+    pub fn suggest<S: AsRef<str>>(
+        word: &str,
+        candidates: impl IntoIterator<Item = S>,
+    ) -> Option<String> {
+        let max = std::cmp::max(1, word.chars().count() / 3);
+        candidates
+            .into_iter()
+            .map(|candidate| (edit_distance(word, candidate.as_ref()), candidate))
+            .filter(|(distance, _)| (1..=max).contains(distance))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate.as_ref().to_string())
+    }
+
+    /**
+     * Set a [`Completer`] overriding the completion of the whole command line.
+     *
+     * When set, the completer answers all auto-completion demands, including the first word,
+     * and no [`Action::AutoComplete`] is reported to the application anymore.
+     */
+    pub fn setcompleter(&mut self, completer: impl Completer + Send + 'static) -> &mut Self {
+        self.completer = Some(Box::new(completer));
+        self
+    }
+
+    /**
+     * Enter a nested context, e.g. a configuration mode, with its own prompt, commands and
+     * completer, and optionally its own history.
+     *
+     * The context starts with the provided prompt, no command and no completer: its
+     * commands are registered with [`Cli::addcommand`] and its completer is set with
+     * [`Cli::setcompleter`]. If `history` is true, the context also starts with an empty
+     * history: the entries entered in the context are kept in it, and
+     * [`Cli::load_history`] and [`Cli::save_history`] apply to it. The other settings
+     * (e.g. history size, key bindings, aliases and variables) are shared by all the
+     * contexts. The parent context is restored by [`Cli::pop_context`].
+     *
+     * ```no_run
+     * # async fn example() -> eyre::Result<()> {
+     * let mut cli = tokiocli::Cli::new()?;
+     * cli.addcommand("configure").addcommand("exit");
+     * while let tokiocli::Action::Command { args, .. } = cli.getaction().await? {
+     *     match args[0].as_str() {
+     *         "configure" => {
+     *             cli.push_context("(config)> ", true);
+     *             cli.addcommand("hostname").addcommand("end");
+     *         }
+     *         "end" => {
+     *             cli.pop_context();
+     *         }
+     *         "exit" => break,
+     *         _ => {}
+     *     }
+     * }
+     * # Ok(())
+     * # }
+     * ```
+     */
+    pub fn push_context(&mut self, prompt: &str, history: bool) -> &mut Self {
+        let history = history.then(|| {
+            let mut history = History::new();
+            history.configure_like(&self.editor.history);
+            std::mem::replace(&mut self.editor.history, history)
+        });
+        let context = Context {
+            prompt: std::mem::replace(&mut self.prompt, Box::new(String::from(prompt))),
+            commands: std::mem::take(&mut self.commands),
+            completer: self.completer.take(),
+            history,
+        };
+        self.contexts.push(context);
+        self.editor.history_idx = None;
+        self
+    }
+
+    /**
+     * Return to the parent context, restoring its prompt, commands, completer and history.
+     *
+     * Return false if the Cli is not in a nested context.
+     */
+    pub fn pop_context(&mut self) -> bool {
+        let Some(context) = self.contexts.pop() else {
+            return false;
+        };
+        self.prompt = context.prompt;
+        self.commands = context.commands;
+        self.completer = context.completer;
+        if let Some(mut history) = context.history {
+            history.configure_like(&self.editor.history);
+            self.editor.history = history;
+        }
+        self.editor.history_idx = None;
+        true
+    }
+}
+
+impl Drop for Cli {
+    /**
+     * Release Cli ressources and configure back the terminal in its orignal state.
      */
     fn drop(&mut self) {
-        let fd = 0;
-        if let Err(e) = tcsetattr(fd, TCSANOW, &self.saved_termios) {
-            eprintln!("Failed to restore terminal config: {:?}", e);
+        if let Err(e) = self.restore() {
+            outln!(self.output, "Failed to restore terminal config: {:?}", e);
+        }
+        if let Some(restorer) = &self.panic_restorer {
+            restorer
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .take();
+        }
+    }
+}
+
+/** A temporary file, removed when dropped */
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/**
+ * Create a temporary file with the provided content, under a new name and only accessible
+ * by the user
+ */
+async fn create_temp(content: &str) -> Result<TempFile> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or_default();
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let name = format!("tokiocli-{}-{}-{}.txt", std::process::id(), count, nanos);
+        let path = std::env::temp_dir().join(name);
+        let mut options = tokio::fs::OpenOptions::new();
+        // Never open a file (or follow a symbolic link) planted at this path
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        match options.open(&path).await {
+            Ok(mut file) => {
+                let temp = TempFile(path);
+                file.write_all(content.as_bytes()).await?;
+                file.flush().await?;
+                return Ok(temp);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/** Display the queued commands in the status row */
+fn queue_display(screen: &SharedScreen, queue: &VecDeque<Vec<String>>) {
+    let queued = match queue.is_empty() {
+        true => String::new(),
+        false => {
+            let commands = queue.iter().map(|cmd| cmd.join(" "));
+            format!("queued: {}", commands.collect::<Vec<String>>().join("; "))
+        }
+    };
+    printer::lock(screen).setqueued(queued);
+}
+
+/** Return the arguments of a command line, with at least the command name (maybe empty) */
+fn command_args(mut args: Vec<String>) -> Vec<String> {
+    if args.is_empty() {
+        args.push(String::new());
+    }
+    args
+}
+
+/**
+ * Layout words in columns fitting in the provided width, like ls.
+ *
+ * Words are listed from top to bottom, then from left to right.
+ * Each row is terminated by a new line.
+ */
+fn columns(words: &[String], width: usize) -> String {
+    let colwidth = words
+        .iter()
+        .map(|word| word.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let ncols = std::cmp::max(width / colwidth, 1);
+    let nrows = words.len().div_ceil(ncols);
+
+    let mut text = String::new();
+    for row in 0..nrows {
+        let mut line = String::new();
+        for word in words.iter().skip(row).step_by(nrows) {
+            line += &format!("{:<1$}", word, colwidth);
         }
+        text += line.trim_end();
+        text.push('\n');
     }
+    text
 }
 
 fn common_chars<'a>(lstr: &'a str, rstr: &'_ str) -> &'a str {
     let lindices = lstr.char_indices();
     let mut rindices = rstr.char_indices();
+    // Length of the common prefix, in bytes
     let mut common = 0;
 
-    for (_, lchar) in lindices {
+    for (idx, lchar) in lindices {
         match rindices.next() {
             Some((_, rchar)) => {
                 if lchar != rchar {
                     break;
                 }
-                common += 1;
+                common = idx + lchar.len_utf8();
             }
             None => {
                 break;
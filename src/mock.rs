@@ -0,0 +1,119 @@
+//! In-memory terminal, to test the Cli without a real terminal.
+use crate::terminal::{Output, Terminal};
+use crate::Result;
+use crate::{Cli, ColorSupport};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/**
+ * An in-memory terminal: keys are fed by the test, and the text written by the Cli is
+ * captured.
+ *
+ * ```
+ * # #[tokio::main(flavor = "current_thread")]
+ * # async fn main() -> eyre::Result<()> {
+ * use tokiocli::{Action, MockTerminal};
+ *
+ * let (mut cli, terminal) = MockTerminal::new(80, 24)?;
+ * cli.setprompt("> ");
+ * terminal.feed(b"hello world\r");
+ * match cli.getaction().await? {
+ *     Action::Command { line, args } => {
+ *         assert_eq!(line, "hello world");
+ *         assert_eq!(args, ["hello", "world"]);
+ *     }
+ *     _ => panic!("command expected"),
+ * }
+ * assert!(terminal.output().starts_with("> h"));
+ * # Ok(())
+ * # }
+ * ```
+ */
+pub struct MockTerminal {
+    input: UnboundedSender<Vec<u8>>,
+    output: Arc<Mutex<Vec<u8>>>,
+    size: Arc<Mutex<(usize, usize)>>,
+}
+
+impl MockTerminal {
+    /** Create a Cli interacting with an in-memory terminal of the provided size */
+    pub fn new(cols: usize, rows: usize) -> Result<(Cli, Self)> {
+        let (input, receiver) = unbounded_channel();
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let size = Arc::new(Mutex::new((cols, rows)));
+        let reader = Input {
+            receiver,
+            pending: Vec::new(),
+        };
+        let terminal = MockTty { size: size.clone() };
+        let mut cli = Cli::from_parts(
+            Box::new(reader),
+            Output::buffer(output.clone()),
+            Box::new(terminal),
+        )?;
+        // The output does not depend on the environment of the tests
+        cli.setcolorsupport(ColorSupport::TrueColor);
+        let mock = Self {
+            input,
+            output,
+            size,
+        };
+        Ok((cli, mock))
+    }
+
+    /** Feed bytes to the Cli, as if typed on the keyboard */
+    pub fn feed(&self, bytes: &[u8]) {
+        let _ = self.input.send(bytes.to_vec());
+    }
+
+    /** Return the text written by the Cli since the last call, escape sequences included */
+    pub fn output(&self) -> String {
+        let bytes = std::mem::take(&mut *self.output.lock().unwrap());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /** Change the size of the terminal */
+    pub fn resize(&self, cols: usize, rows: usize) {
+        *self.size.lock().unwrap() = (cols, rows);
+    }
+}
+
+struct MockTty {
+    size: Arc<Mutex<(usize, usize)>>,
+}
+
+impl Terminal for MockTty {
+    fn size(&self) -> (usize, usize) {
+        *self.size.lock().unwrap()
+    }
+}
+
+/** Bytes fed by the MockTerminal, read by the Cli */
+struct Input {
+    receiver: UnboundedReceiver<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+impl AsyncRead for Input {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if self.pending.is_empty() {
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(bytes)) => self.pending = bytes,
+                // The MockTerminal was dropped: end of file
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let len = std::cmp::min(buf.remaining(), self.pending.len());
+        buf.put_slice(&self.pending[..len]);
+        self.pending.drain(..len);
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,107 @@
+//! Plugins extending the line editor.
+use crate::EditAction;
+use std::ops::Range;
+
+/** The command line being edited, as seen by plugins */
+pub struct LineBuffer {
+    text: String,
+    cursor: usize,
+    modified: bool,
+}
+
+impl LineBuffer {
+    pub(crate) fn new(text: String, cursor: usize) -> Self {
+        Self {
+            text,
+            cursor,
+            modified: false,
+        }
+    }
+
+    pub(crate) fn modified(&self) -> bool {
+        self.modified
+    }
+
+    /** Erase the text, which may be sensitive */
+    pub(crate) fn wipe(&mut self) {
+        crate::sensitive::wipe(&mut self.text);
+    }
+
+    pub(crate) fn into_parts(self) -> (String, usize) {
+        (self.text, self.cursor)
+    }
+
+    /** Return the text of the command line */
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /** Return the position of the cursor in the command line */
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /** Move the cursor, within the bounds of the command line */
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = std::cmp::min(cursor, self.text.len());
+        self.modified = true;
+    }
+
+    /** Insert text at the cursor position, and move the cursor after it */
+    pub fn insert(&mut self, text: &str) {
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+        self.modified = true;
+    }
+
+    /** Replace a range of the command line, and move the cursor after the replacement */
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        let start = range.start;
+        self.text.replace_range(range, text);
+        self.cursor = start + text.len();
+        self.modified = true;
+    }
+}
+
+/**
+ * A Plugin extending the line editor, installed with [`crate::Cli::addplugin`].
+ *
+ * Plugins are called in the order they were added. All hooks have a default implementation
+ * doing nothing, so a plugin only implements the hooks it needs.
+ */
+pub trait Plugin {
+    /**
+     * Called before an editing action is performed.
+     *
+     * The plugin may modify the command line. Returning true means the action was handled by
+     * the plugin: it is neither passed to the next plugins nor performed by the Cli.
+     */
+    fn on_key(&mut self, _line: &mut LineBuffer, _action: &EditAction) -> bool {
+        false
+    }
+
+    /**
+     * Called when the text of the command line changed (editing action, paste, history or
+     * completion), just before [`Plugin::on_render`]
+     */
+    fn on_change(&mut self, _line: &LineBuffer) {}
+
+    /** Called after an editing action was performed and rendered */
+    fn on_render(&mut self, _line: &LineBuffer) {}
+
+    /**
+     * Called when a complete and valid command line is accepted, before it is split into
+     * arguments.
+     *
+     * The plugin may rewrite the command line (expand an abbreviation, apply a template, fix
+     * the command name, ...): the rewritten command line is echoed below the typed one, then
+     * returned in [`crate::Action::Command`] and added to the history.
+     */
+    fn on_accept(&mut self, _line: &mut LineBuffer) {}
+
+    /** Called when a command line is submitted */
+    fn on_submit(&mut self, _line: &str) {}
+
+    /** Called with the possible words of a completion (Command Name + Arguments), before they are filtered */
+    fn on_complete(&mut self, _args: &[String], _words: &mut Vec<String>) {}
+}
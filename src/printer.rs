@@ -0,0 +1,342 @@
+//! Printing from other tasks while the user is typing a command.
+use crate::style::truncate;
+use crate::terminal::Output;
+use crate::EscSeq;
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/** The command line as currently displayed on the terminal */
+#[derive(Default)]
+pub(crate) struct Screen {
+    /** Tell if the prompt is currently displayed */
+    pub active: bool,
+    /** Sequence erasing the prompt and the command line, from the cursor position */
+    pub erase: String,
+    /** Prompt and command line as rendered, followed by the positioning of the cursor */
+    pub line: String,
+    /** Column of the cursor, starting at 1 */
+    pub column: usize,
+    /** Number of rows of the command line below the cursor */
+    pub below: usize,
+    /** Tell if the messages are held while the prompt is displayed (do-not-disturb mode) */
+    pub hold: bool,
+    /** Messages held, with their destination (standard error or standard output) */
+    pub held: Vec<(bool, String)>,
+    /** Output captured, and whether it is also printed on the terminal */
+    pub capture: Option<(String, bool)>,
+    /** Number of captures started, identifying the current capture */
+    pub captures: u64,
+    /** Commands queued by [`Cli::run`](crate::Cli::run), as displayed in the status row */
+    pub queued: String,
+    /** Countdowns, as displayed in the status row */
+    pub countdowns: String,
+    /** Progress bar or spinner, as displayed in the status row or on its own line */
+    pub progress: String,
+    /** Tell if the progress is displayed on its own line, while the prompt is not */
+    pub progress_shown: bool,
+    /** Toolbar set by the application, as displayed in the status row */
+    pub toolbar: String,
+    /** Width of the terminal, to keep the status row on one row */
+    pub width: usize,
+    /** Tell if the status row is displayed */
+    pub status_shown: bool,
+    /** Destination of the text written to the terminal */
+    pub output: Output,
+}
+
+impl Screen {
+    /**
+     * Return the sequence rendering the status row below the command line (toolbar, number
+     * of held messages, queued commands, countdowns, progress), from the cursor position.
+     * The status row is erased when there is nothing to display anymore.
+     */
+    pub fn status(&mut self) -> String {
+        let mut parts = Vec::<String>::new();
+        if !self.toolbar.is_empty() {
+            parts.push(self.toolbar.clone());
+        }
+        if !self.held.is_empty() {
+            let count = self.held.len();
+            let plural = if count > 1 { "s" } else { "" };
+            parts.push(format!("{} message{} held", count, plural));
+        }
+        if !self.queued.is_empty() {
+            parts.push(self.queued.clone());
+        }
+        if !self.countdowns.is_empty() {
+            parts.push(self.countdowns.clone());
+        }
+        if !self.progress.is_empty() {
+            parts.push(self.progress.clone());
+        }
+        if parts.is_empty() {
+            return self.status_erase();
+        }
+
+        let mut status = String::new();
+        if self.below > 0 {
+            status += &EscSeq::Down(self.below).to_string();
+        }
+        let text = match self.width {
+            0 => parts.join("  "),
+            width => truncate(&parts.join("  "), width - 1),
+        };
+        status += &format!(
+            "\n{}\x1B[2m{}\x1B[0m{}{}",
+            EscSeq::EraseInLineAll,
+            text,
+            EscSeq::Up(self.below + 1),
+            EscSeq::HorizontalAbs(self.column)
+        );
+        self.status_shown = true;
+        status
+    }
+
+    /** Return the sequence erasing the status row, if displayed, from the cursor position */
+    pub fn status_erase(&mut self) -> String {
+        if !self.status_shown {
+            return String::new();
+        }
+        self.status_shown = false;
+        format!(
+            "{}{}{}{}",
+            EscSeq::Down(self.below + 1),
+            EscSeq::EraseInLineAll,
+            EscSeq::Up(self.below + 1),
+            EscSeq::HorizontalAbs(self.column)
+        )
+    }
+
+    /**
+     * Display the progress in the status row if the prompt is displayed, or on its own line
+     * otherwise. An empty progress is erased.
+     */
+    pub fn setprogress(&mut self, progress: String) {
+        self.progress = progress;
+        if self.active {
+            let status = self.status();
+            out!(self.output, "{}", status);
+            return;
+        }
+        self.progress_erase();
+        if !self.progress.is_empty() {
+            out!(self.output, "{}", self.progress);
+            self.progress_shown = true;
+        }
+    }
+
+    /** Erase the progress displayed on its own line, if any */
+    pub fn progress_erase(&mut self) {
+        if self.progress_shown {
+            out!(self.output, "\r{}", EscSeq::EraseInLineAll);
+            self.progress_shown = false;
+        }
+    }
+
+    /** Display the queued commands in the status row, or erase them if empty */
+    pub fn setqueued(&mut self, queued: String) {
+        self.queued = queued;
+        if self.active {
+            let status = self.status();
+            out!(self.output, "{}", status);
+        }
+    }
+
+    /** Display the toolbar in the status row, or erase it if empty */
+    pub fn settoolbar(&mut self, toolbar: String) {
+        self.toolbar = toolbar;
+        if self.active {
+            let status = self.status();
+            out!(self.output, "{}", status);
+        }
+    }
+
+    /** Hold the messages, or print the held messages and write back the command line */
+    pub fn sethold(&mut self, hold: bool) {
+        self.hold = hold;
+        if hold || self.held.is_empty() {
+            return;
+        }
+        let erase = self.status_erase() + &self.erase;
+        out!(self.output, "{}", erase);
+        for (stderr, msg) in self.held.drain(..) {
+            self.output.write(stderr, format_args!("{}\n", msg));
+        }
+        let line = self.line.clone() + &self.status();
+        out!(self.output, "{}", line);
+    }
+}
+
+/** Shared state of the screen, locked while the Cli or a Printer writes to the terminal */
+pub(crate) type SharedScreen = Arc<Mutex<Screen>>;
+
+/** Lock the screen, even if a previous writer panicked */
+pub(crate) fn lock(screen: &SharedScreen) -> MutexGuard<'_, Screen> {
+    screen.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/**
+ * Print messages without messing up the command line being typed.
+ *
+ * A Printer is returned by [`crate::Cli::printer`] and can be cloned and moved to any task.
+ * When the prompt is displayed, the command line is erased, the message is printed, and the
+ * prompt and the command line are written back with the cursor at the same position.
+ */
+#[derive(Clone)]
+pub struct Printer {
+    screen: SharedScreen,
+}
+
+impl Printer {
+    pub(crate) fn new(screen: SharedScreen) -> Self {
+        Self { screen }
+    }
+
+    /** Print a message followed by a newline on the standard output */
+    pub fn println(&self, msg: impl Display) {
+        self.print(false, msg);
+    }
+
+    /** Print a message followed by a newline on the standard error */
+    pub fn eprintln(&self, msg: impl Display) {
+        self.print(true, msg);
+    }
+
+    /**
+     * Return a writer printing on the standard error, line by line, once dropped.
+     *
+     * It can be used with logging libraries expecting a [`std::io::Write`] target.
+     */
+    pub fn writer(&self) -> PrinterWriter {
+        PrinterWriter {
+            printer: self.clone(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /**
+     * Capture the messages printed from now on, until the returned [`Capture`] is finished.
+     *
+     * The messages are also printed on the terminal if `echo` is true. Starting a capture
+     * discards the messages captured by a previous unfinished capture.
+     */
+    pub fn capture(&self, echo: bool) -> Capture {
+        let mut screen = lock(&self.screen);
+        screen.capture = Some((String::new(), echo));
+        screen.captures += 1;
+        Capture {
+            screen: self.screen.clone(),
+            id: screen.captures,
+        }
+    }
+
+    /**
+     * Set the toolbar displayed below the command line (e.g. mode indicator, connection
+     * status), see [`crate::Cli::settoolbar`].
+     */
+    pub fn settoolbar(&self, toolbar: &str) {
+        lock(&self.screen).settoolbar(toolbar.into());
+    }
+
+    fn print(&self, stderr: bool, msg: impl Display) {
+        let mut screen = lock(&self.screen);
+        if let Some((captured, echo)) = &mut screen.capture {
+            *captured += &format!("{}\n", msg);
+            if !*echo {
+                return;
+            }
+        }
+        if screen.active && screen.hold {
+            screen.held.push((stderr, msg.to_string()));
+            let status = screen.status();
+            out!(screen.output, "{}", status);
+            return;
+        }
+        if screen.active {
+            let erase = screen.status_erase() + &screen.erase;
+            out!(screen.output, "{}", erase);
+        }
+        let progress = screen.progress_shown;
+        screen.progress_erase();
+        screen.output.write(stderr, format_args!("{}\n", msg));
+        if screen.active {
+            let status = screen.status();
+            out!(screen.output, "{}{}", screen.line, status);
+        } else if progress {
+            let progress = screen.progress.clone();
+            screen.setprogress(progress);
+        }
+    }
+}
+
+/** Capture of the messages printed with a [`Printer`], started by [`Printer::capture`] */
+pub struct Capture {
+    screen: SharedScreen,
+    id: u64,
+}
+
+impl Capture {
+    /**
+     * Stop the capture and return the captured messages, one per line. Nothing is returned
+     * if a newer capture was started meanwhile: the newer capture is left running.
+     */
+    pub fn finish(self) -> String {
+        self.take().unwrap_or_default()
+    }
+
+    /** Stop the capture and return the captured messages, if it is still the current one */
+    fn take(&self) -> Option<String> {
+        let mut screen = lock(&self.screen);
+        if screen.captures != self.id {
+            return None;
+        }
+        screen.capture.take().map(|(captured, _)| captured)
+    }
+}
+
+impl Drop for Capture {
+    /** Stop the capture if it was not finished, e.g. by an error returned early */
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+/** A writer printing through a [`Printer`] the text written, once flushed or dropped */
+pub struct PrinterWriter {
+    printer: Printer,
+    buffer: Vec<u8>,
+}
+
+impl Write for PrinterWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&self.buffer);
+        self.printer.eprintln(text.trim_end_matches('\n'));
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl Drop for PrinterWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/** Route the output of `tracing_subscriber::fmt` through the Printer */
+#[cfg(feature = "tracing")]
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for Printer {
+    type Writer = PrinterWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.writer()
+    }
+}
@@ -0,0 +1,82 @@
+//! Progress bars and spinners displayed while the prompt remains usable.
+use crate::printer::{lock, SharedScreen};
+
+/** Width of the bar, in characters */
+const BAR_WIDTH: usize = 20;
+
+/** Frames of the spinner */
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/**
+ * A progress bar or a spinner, returned by [`crate::Cli::progress`].
+ *
+ * When the prompt is displayed, the progress is displayed in the status row below the
+ * command line, so the user can keep typing. Otherwise, it is displayed on its own line,
+ * below the messages printed with a [`crate::Printer`]. The progress is erased when the
+ * handle is dropped.
+ *
+ * ```no_run
+ * # async fn example(cli: &mut tokiocli::Cli) -> eyre::Result<()> {
+ * let mut progress = cli.progress("Downloading");
+ * for done in 0..=100 {
+ *     progress.set(done, 100);
+ *     tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+ * }
+ * # Ok(())
+ * # }
+ * ```
+ */
+pub struct Progress {
+    screen: SharedScreen,
+    label: String,
+    frame: usize,
+}
+
+impl Progress {
+    pub(crate) fn new(screen: SharedScreen, label: &str) -> Self {
+        Self {
+            screen,
+            label: label.into(),
+            frame: 0,
+        }
+    }
+
+    /** Display a bar with the amount of work done over the total */
+    pub fn set(&mut self, done: u64, total: u64) {
+        let ratio = match total {
+            0 => 1.0,
+            _ => done.min(total) as f64 / total as f64,
+        };
+        let filled = (ratio * BAR_WIDTH as f64) as usize;
+        let bar = format!(
+            "{} [{}{}] {:>3}%",
+            self.label,
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            (ratio * 100.0) as usize
+        );
+        self.display(bar);
+    }
+
+    /** Display the next frame of a spinner: should be called periodically */
+    pub fn tick(&mut self) {
+        let spinner = format!("{} {}", self.label, SPINNER[self.frame % SPINNER.len()]);
+        self.frame += 1;
+        self.display(spinner);
+    }
+
+    /** Change the label displayed before the bar or the spinner */
+    pub fn setlabel(&mut self, label: &str) {
+        self.label = label.into();
+    }
+
+    fn display(&self, progress: String) {
+        lock(&self.screen).setprogress(progress);
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.display(String::new());
+    }
+}
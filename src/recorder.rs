@@ -0,0 +1,268 @@
+//! Recording of the input and output of a session, and their replay.
+use crate::sensitive::wipe_bytes;
+use crate::Result;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/** Direction of the bytes of a recorded event */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RecordKind {
+    /** Bytes read from the terminal (or injected as input). */
+    Input,
+    /** Bytes written to the terminal. */
+    Output,
+}
+
+/** Bytes read or written at some time of a recorded session */
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RecordedEvent {
+    /** Time elapsed since the start of the recording */
+    pub time: Duration,
+    /** Whether the bytes were read or written */
+    pub kind: RecordKind,
+    /** The bytes, as read or written */
+    pub bytes: Vec<u8>,
+}
+
+/** Recorder of a session to a file, see [`crate::Cli::record`] */
+pub(crate) struct Recorder {
+    file: std::fs::File,
+    start: Instant,
+    /** Record in the asciinema format */
+    cast: bool,
+    /** Events held until it is known whether they may be written, see [`Recorder::hold`] */
+    held: Option<Vec<(Duration, RecordKind, Vec<u8>)>>,
+}
+
+/** The recorder shared by the Output and its clones */
+pub(crate) type SharedRecorder = Arc<Mutex<Option<Recorder>>>;
+
+impl Recorder {
+    /** Record to a file, replacing its content */
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            file: std::fs::File::create(path)?,
+            start: Instant::now(),
+            cast: false,
+            held: None,
+        })
+    }
+
+    /**
+     * Record to a file in the asciinema format (asciicast v2), replacing its content, for a
+     * terminal of the provided size.
+     */
+    pub fn create_cast(path: &Path, cols: usize, rows: usize) -> Result<Self> {
+        let mut file = std::fs::File::create(path)?;
+        file.write_all(cast_header(cols, rows).as_bytes())?;
+        Ok(Self {
+            file,
+            start: Instant::now(),
+            cast: true,
+            held: None,
+        })
+    }
+
+    /**
+     * Write an event to the file, on its own line: the time in seconds, 'i' for input or
+     * 'o' for output, and the bytes escaped as in a Rust byte string.
+     */
+    pub fn record(&mut self, kind: RecordKind, bytes: &[u8]) {
+        let time = self.start.elapsed();
+        match &mut self.held {
+            Some(held) => held.push((time, kind, bytes.to_vec())),
+            None => self.write(time, kind, bytes),
+        }
+    }
+
+    /**
+     * Hold the next events in memory, e.g. while a command line which may be sensitive is
+     * typed, until they are released.
+     */
+    pub fn hold(&mut self) {
+        self.held.get_or_insert_with(Vec::new);
+    }
+
+    /** Write the held events if `keep` is true, or wipe them, and stop holding events */
+    pub fn release(&mut self, keep: bool) {
+        for (time, kind, mut bytes) in self.held.take().unwrap_or_default() {
+            match keep {
+                true => self.write(time, kind, &bytes),
+                false => wipe_bytes(&mut bytes),
+            }
+        }
+    }
+
+    /** Write an event to the file */
+    fn write(&mut self, time: Duration, kind: RecordKind, bytes: &[u8]) {
+        if self.cast {
+            let _ = self
+                .file
+                .write_all(cast_event(time, kind, bytes).as_bytes());
+            return;
+        }
+        let kind = match kind {
+            RecordKind::Input => 'i',
+            RecordKind::Output => 'o',
+        };
+        let line = format!(
+            "{}.{:06} {} {}\n",
+            time.as_secs(),
+            time.subsec_micros(),
+            kind,
+            bytes.escape_ascii()
+        );
+        let _ = self.file.write_all(line.as_bytes());
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // The command line being typed was not submitted: it may be sensitive
+        self.release(false);
+    }
+}
+
+/**
+ * A session recorded with [`crate::Cli::record`], to reproduce a rendering glitch or to
+ * produce a demo.
+ *
+ * ```no_run
+ * # async fn example() -> eyre::Result<()> {
+ * let recording = tokiocli::Recording::load("/tmp/session.rec").await?;
+ * recording.replay(tokio::io::stdout(), 1.0).await?;
+ * # Ok(())
+ * # }
+ * ```
+ */
+pub struct Recording {
+    events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    /** Load a recorded session */
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let mut events = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            let event = parse_event(line).ok_or_else(|| {
+                let message = format!("invalid recorded event at line {}", idx + 1);
+                std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+            })?;
+            events.push(event);
+        }
+        Ok(Self { events })
+    }
+
+    /** Return the recorded events, in order */
+    pub fn events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    /**
+     * Export the recording in the asciinema format (asciicast v2), for a terminal of the
+     * provided size, e.g. to publish a demo. The recording can also be made in this format
+     * directly with [`crate::Cli::record_cast`].
+     */
+    pub async fn save_cast(&self, path: impl AsRef<Path>, cols: usize, rows: usize) -> Result<()> {
+        let mut content = cast_header(cols, rows);
+        for event in &self.events {
+            content += &cast_event(event.time, event.kind, &event.bytes);
+        }
+        tokio::fs::write(path, content).await?;
+        Ok(())
+    }
+
+    /**
+     * Write the recorded output to a terminal, at the pace it was recorded multiplied by
+     * `speed` (e.g. 2.0 to play it twice faster).
+     */
+    pub async fn replay(&self, mut writer: impl AsyncWrite + Unpin, speed: f64) -> Result<()> {
+        let start = tokio::time::Instant::now();
+        let outputs = self
+            .events
+            .iter()
+            .filter(|event| event.kind == RecordKind::Output);
+        for event in outputs {
+            tokio::time::sleep_until(start + event.time.div_f64(speed)).await;
+            writer.write_all(&event.bytes).await?;
+            writer.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/** Return the header line of an asciicast v2 file */
+fn cast_header(cols: usize, rows: usize) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    format!(
+        "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}}}\n",
+        cols, rows, timestamp
+    )
+}
+
+/**
+ * Return the line of an event in an asciicast v2 file: `[time, "o" or "i", data]`, the
+ * invalid UTF-8 sequences of the data being replaced.
+ */
+fn cast_event(time: Duration, kind: RecordKind, bytes: &[u8]) -> String {
+    let kind = match kind {
+        RecordKind::Input => "i",
+        RecordKind::Output => "o",
+    };
+    let mut data = String::new();
+    for c in String::from_utf8_lossy(bytes).chars() {
+        match c {
+            '"' => data += "\\\"",
+            '\\' => data += "\\\\",
+            '\n' => data += "\\n",
+            '\r' => data += "\\r",
+            '\t' => data += "\\t",
+            c if c.is_control() => data += &format!("\\u{:04x}", c as u32),
+            c => data.push(c),
+        }
+    }
+    format!("[{:.6}, \"{}\", \"{}\"]\n", time.as_secs_f64(), kind, data)
+}
+
+/** Parse an event written by the Recorder */
+fn parse_event(line: &str) -> Option<RecordedEvent> {
+    let mut fields = line.splitn(3, ' ');
+    let time = Duration::try_from_secs_f64(fields.next()?.parse().ok()?).ok()?;
+    let kind = match fields.next()? {
+        "i" => RecordKind::Input,
+        "o" => RecordKind::Output,
+        _ => return None,
+    };
+    let bytes = unescape(fields.next()?)?;
+    Some(RecordedEvent { time, kind, bytes })
+}
+
+/** Return the bytes of a text escaped with `escape_ascii` */
+fn unescape(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.bytes();
+    while let Some(c) = chars.next() {
+        if c != b'\\' {
+            bytes.push(c);
+            continue;
+        }
+        let byte = match chars.next()? {
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'x' => {
+                let hex = [chars.next()?, chars.next()?];
+                u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?
+            }
+            c => c,
+        };
+        bytes.push(byte);
+    }
+    Some(bytes)
+}
@@ -0,0 +1,157 @@
+//! Registry of commands, dispatching the commands entered in the Cli to their handlers.
+use crate::{Action, BoxError, Cli, Completer, Result};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+
+/** Future returned by a command handler, which may borrow the Cli */
+pub type CommandFuture<'a> =
+    Pin<Box<dyn Future<Output = std::result::Result<(), BoxError>> + Send + 'a>>;
+
+type Handler = Box<dyn for<'a> FnMut(&'a mut Cli, Vec<String>) -> CommandFuture<'a> + Send>;
+
+/** A registered command */
+struct Command {
+    help: String,
+    handler: Handler,
+}
+
+/**
+ * A Read-Eval-Print Loop built on the [`Cli`], dispatching the commands to the handlers
+ * registered with their name and help.
+ *
+ * The command names are auto-completed, and the `help` and `exit` commands are provided.
+ *
+ * ```no_run
+ * # async fn example() -> eyre::Result<()> {
+ * let mut repl = tokiocli::Repl::new(tokiocli::Cli::new()?);
+ * repl.addcommand("hello", "Print hello world", |_cli, _args| {
+ *     Box::pin(async move {
+ *         println!("Hello World !");
+ *         Ok(())
+ *     })
+ * });
+ * repl.run().await?;
+ * # Ok(())
+ * # }
+ * ```
+ */
+pub struct Repl {
+    cli: Cli,
+    commands: BTreeMap<String, Command>,
+    completer: Option<Box<dyn Completer + Send>>,
+}
+
+impl Repl {
+    /** Create a Repl running the provided Cli */
+    pub fn new(mut cli: Cli) -> Self {
+        cli.addcommand("help").addcommand("exit");
+        Self {
+            cli,
+            commands: BTreeMap::new(),
+            completer: None,
+        }
+    }
+
+    /** Return the Cli, to configure it */
+    pub fn cli(&mut self) -> &mut Cli {
+        &mut self.cli
+    }
+
+    /**
+     * Register a command with its help, and the handler executing it.
+     *
+     * The handler is called with the Cli and the command (Command Name + Arguments), except
+     * when the command ends with `--help` or `-h`: its help is then printed. An error
+     * returned by the handler is printed, and does not stop the loop. The status of the
+     * command (`$?`) is 0 on success and 1 on error.
+     */
+    pub fn addcommand<F>(&mut self, name: &str, help: &str, handler: F) -> &mut Self
+    where
+        F: for<'a> FnMut(&'a mut Cli, Vec<String>) -> CommandFuture<'a> + Send + 'static,
+    {
+        self.cli.addcommand(name);
+        let command = Command {
+            help: help.into(),
+            handler: Box::new(handler),
+        };
+        self.commands.insert(name.into(), command);
+        self
+    }
+
+    /** Set a [`Completer`] completing the arguments of the commands */
+    pub fn setcompleter(&mut self, completer: impl Completer + Send + 'static) -> &mut Self {
+        self.completer = Some(Box::new(completer));
+        self
+    }
+
+    /** Print the help of the commands */
+    fn help(&self) {
+        let width = self.commands.keys().map(String::len).max().unwrap_or(0);
+        let width = std::cmp::max(width, "help".len());
+        let mut help = String::from("Available commands:\n");
+        for (name, command) in &self.commands {
+            help += &format!("  {:<width$}  {}\n", name, command.help, width = width);
+        }
+        help += &format!(
+            "  {:<width$}  {}\n",
+            "help",
+            "Display this help",
+            width = width
+        );
+        help += &format!("  {:<width$}  {}\n", "exit", "Exit", width = width);
+        self.cli.output.write(false, format_args!("{}", help));
+    }
+
+    /** Read and execute the commands until `exit` is entered, or EOT on an empty line */
+    pub async fn run(&mut self) -> Result<()> {
+        loop {
+            match self.cli.getaction().await? {
+                Action::Command { args: cmd, .. } => match cmd[0].clone().as_str() {
+                    "" => {}
+                    "help" => self.help(),
+                    "exit" => break,
+                    name => match self.commands.get_mut(name) {
+                        Some(command) if is_help(&cmd) => {
+                            let help = format!("{}: {}\n", name, command.help);
+                            self.cli.output.write(false, format_args!("{}", help));
+                        }
+                        Some(command) => match (command.handler)(&mut self.cli, cmd).await {
+                            Ok(()) => {
+                                self.cli.setstatus(0);
+                            }
+                            Err(err) => {
+                                self.cli.print_error(&format!("{}: {}", name, err));
+                                self.cli.setstatus(1);
+                            }
+                        },
+                        None => {
+                            let hint = match Cli::suggest(name, &self.cli.commands) {
+                                Some(suggestion) => format!("Did you mean '{}'?", suggestion),
+                                None => "Type 'help' to list available commands.".into(),
+                            };
+                            self.cli
+                                .print_error(&format!("Unknown command '{}'.", name));
+                            self.cli.print_error(&hint);
+                        }
+                    },
+                },
+                Action::AutoComplete(args) => {
+                    let words = match &self.completer {
+                        Some(completer) => completer.completions(&args),
+                        None => Vec::new(),
+                    };
+                    self.cli.autocomplete(&words)?;
+                }
+                Action::Key(_) | Action::Timeout => {}
+                Action::NoAction => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+/** Tell if a command (Command Name + Arguments) ends with `--help` or `-h` */
+fn is_help(cmd: &[String]) -> bool {
+    cmd.len() > 1 && matches!(cmd[cmd.len() - 1].as_str(), "--help" | "-h")
+}
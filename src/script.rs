@@ -0,0 +1,171 @@
+//! Script execution mode, with simple conditional and loop constructs.
+use crate::{Error, Result};
+
+/** A statement of a script */
+#[derive(Clone)]
+pub(crate) enum Statement {
+    /** A command line, reported to the application. */
+    Command(String),
+    /** Execute the first block if the condition is true, the second block otherwise. */
+    If(String, Vec<Statement>, Vec<Statement>),
+    /** Execute the block for each word, with the variable set to the word. */
+    For(String, String, Vec<Statement>),
+}
+
+/** A block of statements being executed */
+pub(crate) struct Frame {
+    /** Statements of the block */
+    pub body: Vec<Statement>,
+    /** Index of the next statement to execute */
+    pub pc: usize,
+    /** Loop variable and words left to iterate over, if the block is a loop body */
+    pub iteration: Option<(String, Vec<String>)>,
+}
+
+impl Frame {
+    pub fn new(body: Vec<Statement>) -> Self {
+        Self {
+            body,
+            pc: 0,
+            iteration: None,
+        }
+    }
+
+    /** A loop body, starting at its end so the first word is picked before any statement */
+    pub fn iteration(body: Vec<Statement>, var: String, mut words: Vec<String>) -> Self {
+        words.reverse();
+        Self {
+            pc: body.len(),
+            body,
+            iteration: Some((var, words)),
+        }
+    }
+}
+
+/**
+ * Parse a script: one command per line, with the following constructs.
+ *
+ * ```text
+ * if <condition>
+ *     ...
+ * else
+ *     ...
+ * end
+ *
+ * for <name> in <words...>
+ *     ...
+ * end
+ * ```
+ *
+ * Empty lines and lines starting with '#' are ignored.
+ */
+pub(crate) fn parse(text: &str) -> Result<Vec<Statement>> {
+    let mut lines = text.lines().enumerate();
+    let (body, end) = parse_block(&mut lines)?;
+    match end {
+        None => Ok(body),
+        Some((n, keyword)) => Err(Error::Syntax(format!(
+            "line {}: unexpected '{}'",
+            n + 1,
+            keyword
+        ))),
+    }
+}
+
+/** Keyword ending a block, with its line index */
+type BlockEnd<'a> = Option<(usize, &'a str)>;
+
+/** Parse statements until the end of a block, and return the keyword ending the block */
+fn parse_block<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Result<(Vec<Statement>, BlockEnd<'a>)> {
+    let mut body = Vec::new();
+    while let Some((n, line)) = lines.next() {
+        let line = line.trim();
+        let (keyword, rest) = match line.split_once(char::is_whitespace) {
+            Some((keyword, rest)) => (keyword, rest.trim()),
+            None => (line, ""),
+        };
+        match keyword {
+            "" => {}
+            _ if keyword.starts_with('#') => {}
+            "else" | "end" => {
+                return Ok((body, Some((n, keyword))));
+            }
+            "if" => {
+                let (then, end) = parse_block(lines)?;
+                let (otherwise, end) = match end {
+                    Some((_, "else")) => parse_block(lines)?,
+                    end => (Vec::new(), end),
+                };
+                match end {
+                    Some((_, "end")) => {}
+                    Some((n, keyword)) => {
+                        return Err(Error::Syntax(format!(
+                            "line {}: unexpected '{}'",
+                            n + 1,
+                            keyword
+                        )));
+                    }
+                    None => {
+                        return Err(Error::Syntax(format!("line {}: 'if' without 'end'", n + 1)));
+                    }
+                }
+                body.push(Statement::If(rest.into(), then, otherwise));
+            }
+            "for" => {
+                let (var, words) = match rest.split_once(" in ") {
+                    Some((var, words)) => (var.trim(), words.trim()),
+                    None if rest.ends_with(" in") => (rest.trim_end_matches(" in").trim(), ""),
+                    None => {
+                        return Err(Error::Syntax(format!(
+                            "line {}: expected 'for <name> in <words>'",
+                            n + 1
+                        )));
+                    }
+                };
+                let (block, end) = parse_block(lines)?;
+                match end {
+                    Some((_, "end")) => {}
+                    Some((n, keyword)) => {
+                        return Err(Error::Syntax(format!(
+                            "line {}: unexpected '{}'",
+                            n + 1,
+                            keyword
+                        )));
+                    }
+                    None => {
+                        return Err(Error::Syntax(format!(
+                            "line {}: 'for' without 'end'",
+                            n + 1
+                        )));
+                    }
+                }
+                body.push(Statement::For(var.into(), words.into(), block));
+            }
+            _ => {
+                body.push(Statement::Command(line.into()));
+            }
+        }
+    }
+    Ok((body, None))
+}
+
+/**
+ * Evaluate a condition from its expanded arguments.
+ *
+ * A single word is true if it is not empty. Two words can be compared with '==' or '!='.
+ * The condition is negated when prefixed with '!'.
+ */
+pub(crate) fn condition(args: &[String]) -> Result<bool> {
+    match args {
+        [not, args @ ..] if not == "!" => Ok(!condition(args)?),
+        [word] => Ok(!word.is_empty()),
+        [lhs, op, rhs] if op == "==" => Ok(lhs == rhs),
+        [lhs, op, rhs] if op == "!=" => Ok(lhs != rhs),
+        _ => Err(Error::Syntax(format!(
+            "invalid condition: {}",
+            args.join(" ")
+        ))),
+    }
+}
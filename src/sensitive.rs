@@ -0,0 +1,50 @@
+//! Sensitive input (e.g. passwords): matching of the sensitive command lines, and wiping of
+//! the buffers holding them.
+
+/**
+ * Return true if the text matches a glob pattern, where `*` matches any sequence of
+ * characters and `?` matches any character
+ */
+pub(crate) fn glob(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let text = text.chars().collect::<Vec<char>>();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last '*' in the pattern, and of the text it matches up to
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    // Let the last '*' match one more character
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/** Erase a text, zeroizing its memory with the `zeroize` feature */
+pub(crate) fn wipe(text: &mut String) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(text);
+    text.clear();
+}
+
+/** Erase bytes, zeroizing their memory with the `zeroize` feature */
+pub(crate) fn wipe_bytes(bytes: &mut Vec<u8>) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(bytes);
+    bytes.clear();
+}
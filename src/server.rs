@@ -0,0 +1,68 @@
+//! Serving a Cli to the clients of a Unix domain socket.
+use crate::Result;
+use crate::{Cli, Terminal};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use tokio::net::UnixListener;
+
+/** A socket connection: no line discipline to configure */
+struct Connection;
+
+impl Terminal for Connection {}
+
+/**
+ * Listen on a Unix domain socket, and return an independent Cli (history, prompt,
+ * completion) for each connection. It can be used as an admin console for a daemon.
+ *
+ * ```no_run
+ * # async fn example() -> eyre::Result<()> {
+ * let server = tokiocli::UnixServer::bind("/tmp/demo.sock")?;
+ * loop {
+ *     let mut cli = server.accept().await?;
+ *     tokio::spawn(async move {
+ *         cli.setprompt("demo> ");
+ *         while let tokiocli::Action::Command { args, .. } = cli.getaction().await? {
+ *             cli.printer().println(format!("{:?}", args));
+ *         }
+ *         eyre::Ok(())
+ *     });
+ * }
+ * # }
+ * ```
+ *
+ * The client should configure its terminal in character mode, e.g. with
+ * `socat -,raw,echo=0 UNIX-CONNECT:/tmp/demo.sock`. The socket file is removed when the
+ * server is dropped.
+ */
+pub struct UnixServer {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl UnixServer {
+    /** Listen on the provided path, replacing a socket file left by a previous instance */
+    pub fn bind(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        // Only a stale socket is removed, never another file at a mistyped path
+        let socket =
+            std::fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket());
+        if socket && std::os::unix::net::UnixStream::connect(&path).is_err() {
+            std::fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        Ok(Self { listener, path })
+    }
+
+    /** Wait for a connection and return the Cli interacting with it */
+    pub async fn accept(&self) -> Result<Cli> {
+        let (stream, _) = self.listener.accept().await?;
+        let (reader, writer) = stream.into_split();
+        Cli::with_backend(reader, writer, Connection)
+    }
+}
+
+impl Drop for UnixServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
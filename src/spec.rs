@@ -0,0 +1,235 @@
+//! Declarative description of commands for auto-completion.
+use crate::{Completer, FileCompleter};
+
+/** Hint on the possible values of an argument */
+pub enum ArgHint {
+    /** The argument takes one of the listed values. */
+    Values(Vec<String>),
+    /** The argument is a filename or a path. */
+    Files,
+    /** The possible values are provided by a [`Completer`]. */
+    Completer(Box<dyn Completer + Send>),
+}
+
+impl ArgHint {
+    /** Return the placeholder of the argument in the usage of a command */
+    fn usage(&self) -> String {
+        match self {
+            Self::Values(values) => format!("<{}>", values.join("|")),
+            Self::Files => String::from("<file>"),
+            Self::Completer(_) => String::from("<arg>"),
+        }
+    }
+
+    fn completions(&self, args: &[String]) -> Vec<String> {
+        match self {
+            Self::Values(values) => values.clone(),
+            Self::Files => FileCompleter::new().completions(args),
+            Self::Completer(completer) => completer.completions(args),
+        }
+    }
+}
+
+/** A flag of a command, optionally taking a value */
+struct Flag {
+    name: String,
+    value: Option<ArgHint>,
+}
+
+/** Position reached in the tree of commands by walking the arguments */
+struct Walk<'a> {
+    /** Names of the commands walked through */
+    path: Vec<&'a str>,
+    /** Command reached */
+    spec: &'a CommandSpec,
+    /** Number of positional arguments */
+    positional: usize,
+    /** Hint on the value expected by the last flag, if any */
+    value: Option<&'a ArgHint>,
+}
+
+/**
+ * Declarative description of a command: its subcommands, flags and arguments.
+ *
+ * A CommandSpec describing the whole command set can be installed as a [`Completer`] with
+ * [`crate::Cli::setcompleter`]: Tab completion is then answered automatically by walking
+ * the tree with the arguments already typed, and a help is generated for each command
+ * ending with `--help` or `-h`.
+ */
+pub struct CommandSpec {
+    name: String,
+    about: String,
+    subcommands: Vec<CommandSpec>,
+    flags: Vec<Flag>,
+    args: Vec<ArgHint>,
+}
+
+impl CommandSpec {
+    /** Create the specification of a command */
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            about: String::new(),
+            subcommands: Vec::new(),
+            flags: Vec::new(),
+            args: Vec::new(),
+        }
+    }
+
+    /** Create the root of a command set, whose subcommands are the available commands */
+    pub fn root() -> Self {
+        Self::new("")
+    }
+
+    /** Return the name of the command */
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /** Set the description of the command, displayed in its help */
+    pub fn about(mut self, about: &str) -> Self {
+        self.about = about.into();
+        self
+    }
+
+    /** Add a subcommand */
+    pub fn subcommand(mut self, subcommand: CommandSpec) -> Self {
+        self.subcommands.push(subcommand);
+        self
+    }
+
+    /** Add a flag taking no value (e.g. `--verbose`) */
+    pub fn flag(mut self, name: &str) -> Self {
+        self.flags.push(Flag {
+            name: name.into(),
+            value: None,
+        });
+        self
+    }
+
+    /** Add a flag taking a value (e.g. `--file <path>`) */
+    pub fn option(mut self, name: &str, value: ArgHint) -> Self {
+        self.flags.push(Flag {
+            name: name.into(),
+            value: Some(value),
+        });
+        self
+    }
+
+    /** Add a positional argument */
+    pub fn arg(mut self, hint: ArgHint) -> Self {
+        self.args.push(hint);
+        self
+    }
+
+    fn find_flag(&self, name: &str) -> Option<&Flag> {
+        self.flags.iter().find(|flag| flag.name == name)
+    }
+
+    /** Walk the tree of commands with the provided arguments */
+    fn walk(&self, args: &[String]) -> Walk<'_> {
+        let mut walk = Walk {
+            path: Vec::new(),
+            spec: self,
+            positional: 0,
+            value: None,
+        };
+        for arg in args {
+            if walk.value.take().is_some() {
+                // Value of the previous flag
+                continue;
+            }
+            if let Some(flag) = walk.spec.find_flag(arg) {
+                walk.value = flag.value.as_ref();
+                continue;
+            }
+            match walk.spec.subcommands.iter().find(|sub| sub.name == *arg) {
+                Some(subcommand) if walk.positional == 0 => {
+                    walk.path.push(&subcommand.name);
+                    walk.spec = subcommand;
+                }
+                _ => {
+                    walk.positional += 1;
+                }
+            }
+        }
+        walk
+    }
+
+    /** Generate the help of the command, invoked with the provided path */
+    fn generate_help(&self, path: &[&str]) -> String {
+        let mut usage = format!("Usage: {}", path.join(" "));
+        if !self.subcommands.is_empty() {
+            usage += " <command>";
+        }
+        for flag in &self.flags {
+            usage += &match &flag.value {
+                Some(value) => format!(" [{} {}]", flag.name, value.usage()),
+                None => format!(" [{}]", flag.name),
+            };
+        }
+        for arg in &self.args {
+            usage += &format!(" {}", arg.usage());
+        }
+
+        let mut help = usage;
+        if !self.about.is_empty() {
+            help += &format!("\n\n{}", self.about);
+        }
+        if !self.subcommands.is_empty() {
+            help += "\n\nCommands:";
+            let width = self.subcommands.iter().map(|sub| sub.name.len()).max();
+            let width = width.unwrap_or_default();
+            for sub in &self.subcommands {
+                help += &format!("\n  {:width$}  {}", sub.name, sub.about, width = width);
+            }
+        }
+        help.lines()
+            .map(str::trim_end)
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+impl Completer for CommandSpec {
+    fn completions(&self, args: &[String]) -> Vec<String> {
+        let (word, previous) = match args.split_last() {
+            Some(split) => split,
+            None => {
+                return Vec::new();
+            }
+        };
+
+        // Walk the tree with the arguments already typed
+        let walk = self.walk(previous);
+        if let Some(hint) = walk.value {
+            return hint.completions(args);
+        }
+        if word.starts_with('-') {
+            return walk
+                .spec
+                .flags
+                .iter()
+                .map(|flag| flag.name.clone())
+                .collect();
+        }
+
+        let mut words = Vec::<String>::new();
+        if walk.positional == 0 {
+            words.extend(walk.spec.subcommands.iter().map(|sub| sub.name.clone()));
+        }
+        if let Some(hint) = walk.spec.args.get(walk.positional) {
+            words.extend(hint.completions(args));
+        }
+        words
+    }
+
+    fn help(&self, args: &[String]) -> Option<String> {
+        let walk = self.walk(args);
+        if walk.path.is_empty() {
+            // Unknown command
+            return None;
+        }
+        Some(walk.spec.generate_help(&walk.path))
+    }
+}
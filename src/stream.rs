@@ -0,0 +1,78 @@
+//! Stream of the Actions performed by the user.
+use crate::Result;
+use crate::{Action, Cli};
+use futures_core::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+type Pending = Pin<Box<dyn Future<Output = (Box<Cli>, Result<Action>)> + Send>>;
+
+enum State {
+    /** No Action is being read */
+    Idle(Box<Cli>),
+    /** An Action is being read by getaction, which owns the Cli until it completes */
+    Pending(Pending),
+    /** The Cli was lost, due to a panic while reading an Action */
+    Gone,
+}
+
+/**
+ * Stream of the Actions performed by the user, returned by [`Cli::into_stream`].
+ *
+ * The stream ends when EOT is received on an empty line ([`Action::NoAction`] is not
+ * reported). Dropping the stream, or a future polling it, while no Action is ready does not
+ * lose the keys already typed: reading continues from where it stopped on the next poll.
+ */
+pub struct ActionStream {
+    state: State,
+}
+
+impl ActionStream {
+    pub(crate) fn new(cli: Cli) -> Self {
+        Self {
+            state: State::Idle(Box::new(cli)),
+        }
+    }
+
+    /**
+     * Return the Cli, to configure it or to answer an [`Action::AutoComplete`].
+     *
+     * The Cli is only available between two Actions: None is returned while an Action is
+     * being read.
+     */
+    pub fn cli(&mut self) -> Option<&mut Cli> {
+        match &mut self.state {
+            State::Idle(cli) => Some(cli),
+            _ => None,
+        }
+    }
+}
+
+impl Stream for ActionStream {
+    type Item = Result<Action>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut pending = match std::mem::replace(&mut self.state, State::Gone) {
+            State::Idle(mut cli) => Box::pin(async move {
+                let action = cli.getaction().await;
+                (cli, action)
+            }),
+            State::Pending(pending) => pending,
+            State::Gone => return Poll::Ready(None),
+        };
+        match pending.as_mut().poll(cx) {
+            Poll::Ready((cli, action)) => {
+                self.state = State::Idle(cli);
+                match action {
+                    Ok(Action::NoAction) => Poll::Ready(None),
+                    action => Poll::Ready(Some(action)),
+                }
+            }
+            Poll::Pending => {
+                self.state = State::Pending(pending);
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,590 @@
+//! Styling of the text displayed by the Cli.
+use crate::{Error, Result};
+use std::str::FromStr;
+
+/** Terminal colors: the standard ones, the 256 colors palette, or RGB (truecolor) */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    /** A color of the 256 colors palette. */
+    Ansi256(u8),
+    /** A color given by its red, green and blue components. */
+    Rgb(u8, u8, u8),
+}
+
+/** The standard colors, in the order of their codes */
+const STANDARD: [Color; 8] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+];
+
+/** Colors supported by a terminal */
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ColorSupport {
+    /** No colors: only the attributes (bold, underline...) are displayed. */
+    None,
+    /** The 8 standard colors. */
+    Standard,
+    /** The 256 colors palette. */
+    Ansi256,
+    /** RGB colors. */
+    TrueColor,
+}
+
+impl ColorSupport {
+    /**
+     * Detect the colors supported by the terminal from the environment: none if `NO_COLOR`
+     * is set or if `TERM` is `dumb`, RGB colors if `COLORTERM` is `truecolor` or `24bit`,
+     * the 256 colors palette if `TERM` contains `256color`.
+     */
+    pub fn detect() -> Self {
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let term = var("TERM");
+        if var("NO_COLOR").is_some() || term.as_deref() == Some("dumb") {
+            return Self::None;
+        }
+        if matches!(var("COLORTERM").as_deref(), Some("truecolor" | "24bit")) {
+            return Self::TrueColor;
+        }
+        match term {
+            Some(term) if term.contains("256color") => Self::Ansi256,
+            Some(_) => Self::Standard,
+            // The Windows console does not set TERM
+            None if cfg!(windows) => Self::TrueColor,
+            None => Self::None,
+        }
+    }
+}
+
+impl Color {
+    /** Return the parameters of the SGR sequence selecting this color, as foreground */
+    fn code(&self) -> String {
+        match self {
+            Self::Ansi256(idx) => format!("38;5;{}", idx),
+            Self::Rgb(r, g, b) => format!("38;2;{};{};{}", r, g, b),
+            standard => {
+                let idx = STANDARD.iter().position(|color| color == standard);
+                format!("{}", 30 + idx.unwrap_or(7))
+            }
+        }
+    }
+
+    /** Return the parameters of the SGR sequence selecting this color, as background */
+    fn bg_code(&self) -> String {
+        match self {
+            Self::Ansi256(idx) => format!("48;5;{}", idx),
+            Self::Rgb(r, g, b) => format!("48;2;{};{};{}", r, g, b),
+            standard => {
+                let idx = STANDARD.iter().position(|color| color == standard);
+                format!("{}", 40 + idx.unwrap_or(0))
+            }
+        }
+    }
+
+    /** Return the nearest color displayable by a terminal, if any */
+    pub fn downgrade(&self, support: ColorSupport) -> Option<Color> {
+        match (*self, support) {
+            (_, ColorSupport::None) => None,
+            (Self::Rgb(..), ColorSupport::TrueColor) => Some(*self),
+            (Self::Rgb(r, g, b), ColorSupport::Ansi256) => Some(Self::Ansi256(ansi256(r, g, b))),
+            (Self::Rgb(r, g, b), ColorSupport::Standard) => Some(standard(r, g, b)),
+            (Self::Ansi256(idx), ColorSupport::Standard) => Some(match idx {
+                0..=7 => STANDARD[idx as usize],
+                8..=15 => STANDARD[idx as usize - 8],
+                16..=231 => {
+                    let level = |value: u8| if value == 0 { 0 } else { value * 40 + 55 };
+                    let idx = idx - 16;
+                    standard(level(idx / 36), level(idx / 6 % 6), level(idx % 6))
+                }
+                _ => match idx >= 244 {
+                    true => Self::White,
+                    false => Self::Black,
+                },
+            }),
+            _ => Some(*self),
+        }
+    }
+
+    /** Return the text wrapped in the ANSI escape sequences displaying it with this color */
+    pub fn paint(&self, text: &str) -> String {
+        format!("\x1B[{}m{}\x1B[0m", self.code(), text)
+    }
+}
+
+/**
+ * Parse a color: its name (e.g. `cyan`), its index in the 256 colors palette (e.g. `208`) or
+ * its RGB components (e.g. `#ff8000`).
+ */
+impl FromStr for Color {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let name = text.to_lowercase();
+        if let Some(color) = STANDARD
+            .iter()
+            .find(|color| format!("{:?}", color).to_lowercase() == name)
+        {
+            return Ok(*color);
+        }
+        if let Ok(idx) = name.parse() {
+            return Ok(Self::Ansi256(idx));
+        }
+        let rgb = name
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.is_ascii())
+            .and_then(|hex| {
+                let component = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).ok();
+                Some(Self::Rgb(component(0)?, component(2)?, component(4)?))
+            });
+        rgb.ok_or_else(|| Error::Syntax(format!("invalid color '{}'", text)))
+    }
+}
+
+/** Return the nearest color of the 256 colors palette (6x6x6 cube) */
+fn ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |value: u8| match value {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (value - 35) / 40,
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+/** Return the nearest standard color */
+fn standard(r: u8, g: u8, b: u8) -> Color {
+    let idx = usize::from(r > 127) | usize::from(g > 127) << 1 | usize::from(b > 127) << 2;
+    STANDARD[idx]
+}
+
+/**
+ * A style of text: colors and attributes.
+ *
+ * ```
+ * use tokiocli::{Color, Style};
+ *
+ * let style = Style::new().fg(Color::Rgb(255, 128, 0)).bold();
+ * assert_eq!(style.paint("warning"), "\x1B[1;38;2;255;128;0mwarning\x1B[0m");
+ * assert_eq!(Style::new().paint("plain"), "plain");
+ * ```
+ */
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Style {
+    /** Color of the text */
+    pub fg: Option<Color>,
+    /** Color of the background */
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub underline: bool,
+    /** Swap the colors of the text and of the background */
+    pub reverse: bool,
+}
+
+impl Style {
+    /** Create a style without colors nor attributes: the text is displayed as is */
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Set the color of the text */
+    pub fn fg(mut self, color: Color) -> Self {
+        self.fg = Some(color);
+        self
+    }
+
+    /** Set the color of the background */
+    pub fn bg(mut self, color: Color) -> Self {
+        self.bg = Some(color);
+        self
+    }
+
+    /** Display the text in bold */
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    /** Display the text dimmed (e.g. grey) */
+    pub fn dim(mut self) -> Self {
+        self.dim = true;
+        self
+    }
+
+    /** Underline the text */
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    /** Swap the colors of the text and of the background */
+    pub fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    /** Return the style with its colors downgraded to the ones displayable by a terminal */
+    pub fn downgrade(self, support: ColorSupport) -> Self {
+        Self {
+            fg: self.fg.and_then(|color| color.downgrade(support)),
+            bg: self.bg.and_then(|color| color.downgrade(support)),
+            ..self
+        }
+    }
+
+    /** Return the text wrapped in the ANSI escape sequences displaying it with this style */
+    pub fn paint(&self, text: &str) -> String {
+        let mut codes = Vec::<String>::new();
+        let attributes = [
+            (self.bold, "1"),
+            (self.dim, "2"),
+            (self.underline, "4"),
+            (self.reverse, "7"),
+        ];
+        for (enabled, code) in attributes {
+            if enabled {
+                codes.push(code.into());
+            }
+        }
+        codes.extend(self.fg.map(|color| color.code()));
+        codes.extend(self.bg.map(|color| color.bg_code()));
+        match codes.is_empty() {
+            true => text.into(),
+            false => format!("\x1B[{}m{}\x1B[0m", codes.join(";"), text),
+        }
+    }
+}
+
+/**
+ * Parse a style: attributes (`bold`, `dim`, `underline`, `reverse`) and colors separated by
+ * spaces, the color following `on` being the background (e.g. `bold yellow on #202020`).
+ * `none` is the style without colors nor attributes.
+ */
+impl FromStr for Style {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut style = Self::new();
+        let mut words = text.split_whitespace();
+        while let Some(word) = words.next() {
+            style = match word.to_lowercase().as_str() {
+                "none" => style,
+                "bold" => style.bold(),
+                "dim" => style.dim(),
+                "underline" => style.underline(),
+                "reverse" => style.reverse(),
+                "on" => match words.next() {
+                    Some(color) => style.bg(color.parse()?),
+                    None => {
+                        let message = format!("missing background color in style '{}'", text);
+                        return Err(Error::Syntax(message));
+                    }
+                },
+                _ => style.fg(word.parse()?),
+            };
+        }
+        Ok(style)
+    }
+}
+
+/**
+ * The styles of the text displayed by the Cli, see [`crate::Cli::settheme`].
+ *
+ * The colors are downgraded to the ones supported by the terminal (see
+ * [`crate::Cli::setcolorsupport`]).
+ *
+ * ```no_run
+ * # fn example() -> eyre::Result<()> {
+ * use tokiocli::{Color, Style, Theme};
+ *
+ * let mut cli = tokiocli::Cli::new()?;
+ * cli.settheme(Theme {
+ *     prompt: Style::new().fg(Color::Cyan).bold(),
+ *     error: Style::new().fg(Color::Red),
+ *     ..Theme::default()
+ * });
+ * # Ok(())
+ * # }
+ * ```
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Theme {
+    /** The prompt and the continuation prompt, none by default */
+    pub prompt: Style,
+    /** The hints displayed after the command line, dimmed by default */
+    pub hint: Style,
+    /** The selected candidate of the completion menu and of the dialogs, reversed by default */
+    pub selection: Style,
+    /** The error messages displayed by the Cli (e.g. unknown command), none by default */
+    pub error: Style,
+    /**
+     * The control characters of the command line, displayed in caret notation (e.g. `^[`),
+     * reversed by default
+     */
+    pub control: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            prompt: Style::new(),
+            hint: Style::new().dim(),
+            selection: Style::new().reverse(),
+            error: Style::new(),
+            control: Style::new().reverse(),
+        }
+    }
+}
+
+/**
+ * Build a prompt made of styled segments.
+ *
+ * The built prompt contains ANSI escape sequences, which are not taken into account by
+ * the Cli when computing the width of the prompt.
+ */
+#[derive(Default)]
+pub struct PromptBuilder {
+    prompt: String,
+}
+
+impl PromptBuilder {
+    /** Create an empty prompt */
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Append unstyled text */
+    pub fn text(mut self, text: &str) -> Self {
+        self.prompt += text;
+        self
+    }
+
+    /** Append colored text */
+    pub fn color(mut self, text: &str, color: Color) -> Self {
+        self.prompt += &color.paint(text);
+        self
+    }
+
+    /** Append styled text */
+    pub fn style(mut self, text: &str, style: Style) -> Self {
+        self.prompt += &style.paint(text);
+        self
+    }
+
+    /** Append bold text */
+    pub fn bold(mut self, text: &str) -> Self {
+        self.prompt += &format!("\x1B[1m{}\x1B[0m", text);
+        self
+    }
+
+    /** Return the built prompt, to be passed to [`crate::Cli::setprompt`] */
+    pub fn build(self) -> String {
+        self.prompt
+    }
+}
+
+/** Remove the colors from the SGR sequences of a text, keeping the other attributes */
+pub(crate) fn strip_colors(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '\x1B' => sequence_len(rest),
+            _ => c.len_utf8(),
+        };
+        let sequence = &rest[..len];
+        rest = &rest[len..];
+        let params = sequence
+            .strip_prefix("\x1B[")
+            .and_then(|sequence| sequence.strip_suffix('m'))
+            .filter(|params| !params.is_empty());
+        let Some(params) = params else {
+            stripped += sequence;
+            continue;
+        };
+        let mut kept = Vec::new();
+        let mut params = params.split(';');
+        while let Some(param) = params.next() {
+            match param.parse::<u32>().unwrap_or(0) {
+                // Extended color: 5;index or 2;r;g;b
+                38 | 48 => {
+                    let skip = match params.next() {
+                        Some("2") => 3,
+                        _ => 1,
+                    };
+                    params.nth(skip - 1);
+                }
+                30..=37 | 39..=47 | 49 | 90..=97 | 100..=107 => {}
+                _ => kept.push(param),
+            }
+        }
+        if !kept.is_empty() {
+            stripped += &format!("\x1B[{}m", kept.join(";"));
+        }
+    }
+    stripped
+}
+
+/**
+ * Return the width of a text once displayed on the terminal.
+ *
+ * CSI sequences (e.g. colors) and OSC sequences (e.g. window title) are not displayed.
+ */
+pub(crate) fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '\x1B' => sequence_len(rest),
+            _ => {
+                width += 1;
+                c.len_utf8()
+            }
+        };
+        rest = &rest[len..];
+    }
+    width
+}
+
+/**
+ * Return the caret notation of a control character (e.g. `^[` for ESC), as displayed in
+ * the command line. C1 control characters are prefixed with `M-` (e.g. `M-^[`).
+ */
+pub(crate) fn caret(c: char) -> Option<String> {
+    match c {
+        '\x00'..='\x1F' => Some(format!("^{}", (c as u8 + 0x40) as char)),
+        '\x7F' => Some(String::from("^?")),
+        '\u{80}'..='\u{9F}' => Some(format!("M-^{}", (c as u8 - 0x40) as char)),
+        _ => None,
+    }
+}
+
+/** Return the width of a text of the command line once displayed, see [`controls`] */
+pub(crate) fn text_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| caret(c).map_or(1, |caret| caret.len()))
+        .sum()
+}
+
+/**
+ * Return a text of the command line as displayed: its control characters are replaced by
+ * their caret notation, painted with the provided style, so they do not mess up the display.
+ */
+pub(crate) fn controls(text: &str, style: Style) -> String {
+    let mut displayed = String::with_capacity(text.len());
+    for c in text.chars() {
+        match caret(c) {
+            Some(caret) => displayed += &style.paint(&caret),
+            None => displayed.push(c),
+        }
+    }
+    displayed
+}
+
+/** Truncate a text to the provided display width, keeping its escape sequences */
+pub(crate) fn truncate(text: &str, width: usize) -> String {
+    let mut truncated = String::with_capacity(text.len());
+    let mut columns = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '\x1B' => sequence_len(rest),
+            _ if columns < width => {
+                columns += 1;
+                c.len_utf8()
+            }
+            _ => {
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+        };
+        truncated += &rest[..len];
+        rest = &rest[len..];
+    }
+    truncated
+}
+
+/**
+ * Wrap a text on rows of the provided width, starting each continuation row with a marker.
+ *
+ * Rows are added until the requested number of continuation rows is reached, so the cursor
+ * can be moved at the beginning of an empty last row.
+ */
+pub(crate) fn wrap(text: &str, width: usize, marker: &str, rows: usize) -> String {
+    let capacity = width.saturating_sub(display_width(marker)).max(1);
+    let mut wrapped = String::with_capacity(text.len());
+    let mut row = 0;
+    let mut column = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '\x1B' => sequence_len(rest),
+            _ => {
+                let row_width = if row == 0 { width } else { capacity };
+                if column == row_width {
+                    wrapped += "\r\n";
+                    wrapped += marker;
+                    row += 1;
+                    column = 0;
+                }
+                column += 1;
+                c.len_utf8()
+            }
+        };
+        wrapped += &rest[..len];
+        rest = &rest[len..];
+    }
+    while row < rows {
+        wrapped += "\r\n";
+        wrapped += marker;
+        row += 1;
+    }
+    wrapped
+}
+
+/** Return the length of the escape sequence at the beginning of the text */
+fn sequence_len(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1).peekable();
+    match chars.next() {
+        Some((_, '[')) => {
+            // CSI: skip until the final byte
+            for (i, c) in chars {
+                if ('\x40'..='\x7E').contains(&c) {
+                    return i + 1;
+                }
+            }
+            text.len()
+        }
+        Some((_, ']')) => {
+            // OSC: skip until BEL or ST (ESC \)
+            while let Some((i, c)) = chars.next() {
+                if c == '\x07' {
+                    return i + 1;
+                }
+                if c == '\x1B' && matches!(chars.peek(), Some((_, '\\'))) {
+                    return i + 2;
+                }
+            }
+            text.len()
+        }
+        Some((i, c)) => i + c.len_utf8(),
+        None => 1,
+    }
+}
@@ -0,0 +1,45 @@
+//! Suspension of the Cli while another program uses the terminal.
+use crate::{printer, Cli};
+
+/**
+ * Guard returned by [`Cli::suspend`].
+ *
+ * The terminal is configured back in its original state while the guard is alive, so a child
+ * process (editor, pager, ssh, ...) can use it. Dropping the guard configures the terminal
+ * in character mode again, and writes back the command line if it was displayed.
+ */
+pub struct Suspend<'a> {
+    cli: &'a mut Cli,
+    active: bool,
+}
+
+impl<'a> Suspend<'a> {
+    pub(crate) fn new(cli: &'a mut Cli) -> crate::Result<Self> {
+        let active = {
+            let mut screen = printer::lock(&cli.screen);
+            let active = screen.active;
+            if active {
+                out!(screen.output, "{}", screen.erase);
+            }
+            screen.active = false;
+            active
+        };
+        cli.disable_raw_mode()?;
+        Ok(Self { cli, active })
+    }
+}
+
+impl Drop for Suspend<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.cli.enable_raw_mode() {
+            outln!(self.cli.output, "Failed to configure terminal: {:?}", e);
+        }
+        self.cli.keypad_transmit();
+        if self.active {
+            let mut screen = printer::lock(&self.cli.screen);
+            self.cli.wrap_rows.set((0, 0));
+            let _ = self.cli.refresh_line();
+            self.cli.snapshot(&mut screen, true);
+        }
+    }
+}
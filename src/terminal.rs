@@ -0,0 +1,476 @@
+//! Terminal the Cli is interacting with: raw-mode control and output.
+use crate::recorder::{RecordKind, Recorder, SharedRecorder};
+use crate::{Error, Result};
+use std::fmt::Arguments;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, RawFd};
+use std::sync::{Arc, Mutex};
+#[cfg(unix)]
+use termios::*;
+use tokio::io::{stdin, AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+/**
+ * Control of the terminal the Cli is interacting with, see [`crate::Cli::with_backend`].
+ *
+ * The default implementations do nothing and report a 80x24 terminal, which fits a backend
+ * without any line discipline (e.g. a raw TCP socket).
+ */
+pub trait Terminal: Send {
+    /** Configure the terminal in character mode: no echo, no line buffering */
+    fn raw(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /** Configure back the terminal in its original mode */
+    fn restore(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /** Return the size of the terminal (columns, rows) */
+    fn size(&self) -> (usize, usize) {
+        (80, 24)
+    }
+
+    /**
+     * Return a function configuring back the terminal in its original mode, which can be
+     * called from a panic hook (see [`crate::Cli::restore_on_panic`]).
+     */
+    fn restorer(&self) -> Option<Box<dyn Fn() + Send>> {
+        None
+    }
+
+    /**
+     * Tell if it is the terminal of the process, on which a program run by the Cli (e.g. the
+     * editor of [`crate::EditAction::EditInEditor`]) interacts with the user
+     */
+    fn local(&self) -> bool {
+        false
+    }
+}
+
+/** Input of the terminal of the process, and the control of the terminal */
+type Opened = (Box<dyn AsyncRead + Send + Unpin>, Box<dyn Terminal>);
+
+/**
+ * Open the terminal of the process: the standard input if it is a terminal, or the
+ * controlling terminal (/dev/tty) otherwise.
+ */
+#[cfg(unix)]
+pub(crate) fn open() -> Result<Opened> {
+    use std::io::IsTerminal;
+    if std::io::stdin().is_terminal() {
+        return Ok((
+            Box::new(stdin()),
+            Box::new(Tty::new(libc::STDIN_FILENO, None, true)?),
+        ));
+    }
+    let tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|_| Error::NotATty)?;
+    let reader = tokio::fs::File::from_std(tty.try_clone()?);
+    Ok((
+        Box::new(reader),
+        Box::new(Tty::new(tty.as_raw_fd(), Some(tty), true)?),
+    ))
+}
+
+/** Open the console of the process, which must be the standard input */
+#[cfg(windows)]
+pub(crate) fn open() -> Result<Opened> {
+    Ok((Box::new(stdin()), Box::new(Console::new()?)))
+}
+
+/** The controlling terminal of the process, configured with termios */
+#[cfg(unix)]
+pub(crate) struct Tty {
+    fd: RawFd,
+    saved: Termios,
+    raw: Termios,
+    _tty: Option<std::fs::File>,
+    /** The terminal of the process, rather than another terminal (e.g. a pseudo-terminal) */
+    local: bool,
+}
+
+#[cfg(unix)]
+impl Tty {
+    /**
+     * Use the terminal on the provided file descriptor, keeping its device open if provided.
+     *
+     * The termios settings are only applied to this terminal, not to the standard input.
+     */
+    pub fn new(fd: RawFd, tty: Option<std::fs::File>, local: bool) -> Result<Self> {
+        let saved = Termios::from_fd(fd).map_err(|_| Error::NotATty)?;
+        let mut raw = saved;
+        raw.c_lflag &= !(ECHO | ECHONL | ICANON);
+        // Ctrl+S searches the history forward instead of suspending the output
+        raw.c_iflag &= !IXON;
+        Ok(Self {
+            fd,
+            saved,
+            raw,
+            _tty: tty,
+            local,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Terminal for Tty {
+    fn raw(&mut self) -> Result<()> {
+        tcsetattr(self.fd, TCSANOW, &self.raw).map_err(Error::Terminal)?;
+        Ok(())
+    }
+
+    fn restore(&mut self) -> Result<()> {
+        tcsetattr(self.fd, TCSANOW, &self.saved).map_err(Error::Terminal)?;
+        Ok(())
+    }
+
+    fn restorer(&self) -> Option<Box<dyn Fn() + Send>> {
+        let (fd, saved) = (self.fd, self.saved);
+        Some(Box::new(move || {
+            let _ = tcsetattr(fd, TCSANOW, &saved);
+        }))
+    }
+
+    fn local(&self) -> bool {
+        self.local
+    }
+
+    fn size(&self) -> (usize, usize) {
+        let mut winsize = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: TIOCGWINSZ only writes in the provided winsize structure
+        let rc = unsafe { libc::ioctl(self.fd, libc::TIOCGWINSZ, &mut winsize) };
+        match rc == 0 && winsize.ws_col > 0 && winsize.ws_row > 0 {
+            true => (winsize.ws_col as usize, winsize.ws_row as usize),
+            false => (80, 24),
+        }
+    }
+}
+
+/**
+ * The Windows console of the process, configured with the console modes.
+ *
+ * The standard handles are retrieved on each call, as they are not owned by the Cli.
+ */
+#[cfg(windows)]
+pub(crate) struct Console {
+    saved_input: u32,
+    saved_output: u32,
+}
+
+#[cfg(windows)]
+mod console {
+    pub use windows_sys::Win32::System::Console::*;
+
+    /** Return the mode of a standard handle */
+    pub fn mode(handle: STD_HANDLE) -> std::io::Result<u32> {
+        let mut mode = 0;
+        // SAFETY: GetConsoleMode only writes in the provided mode
+        match unsafe { GetConsoleMode(GetStdHandle(handle), &mut mode) } {
+            0 => Err(std::io::Error::last_os_error()),
+            _ => Ok(mode),
+        }
+    }
+
+    /** Set the mode of a standard handle */
+    pub fn setmode(handle: STD_HANDLE, mode: u32) -> std::io::Result<()> {
+        // SAFETY: SetConsoleMode does not retain any pointer
+        match unsafe { SetConsoleMode(GetStdHandle(handle), mode) } {
+            0 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Console {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            saved_input: console::mode(console::STD_INPUT_HANDLE).map_err(|_| Error::NotATty)?,
+            saved_output: console::mode(console::STD_ERROR_HANDLE).map_err(|_| Error::NotATty)?,
+        })
+    }
+}
+
+#[cfg(windows)]
+impl Terminal for Console {
+    fn raw(&mut self) -> Result<()> {
+        use console::*;
+        // Ctrl+C is still processed by the system, as ISIG is kept with termios
+        let input = self.saved_input & !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT);
+        setmode(STD_INPUT_HANDLE, input | ENABLE_VIRTUAL_TERMINAL_INPUT)
+            .map_err(Error::Terminal)?;
+        let output = self.saved_output | ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+        setmode(STD_ERROR_HANDLE, output).map_err(Error::Terminal)?;
+        // The standard output may be redirected: the error is ignored
+        let _ = setmode(STD_OUTPUT_HANDLE, output);
+        Ok(())
+    }
+
+    fn restore(&mut self) -> Result<()> {
+        console::setmode(console::STD_INPUT_HANDLE, self.saved_input).map_err(Error::Terminal)?;
+        console::setmode(console::STD_ERROR_HANDLE, self.saved_output).map_err(Error::Terminal)?;
+        Ok(())
+    }
+
+    fn restorer(&self) -> Option<Box<dyn Fn() + Send>> {
+        let (input, output) = (self.saved_input, self.saved_output);
+        Some(Box::new(move || {
+            let _ = console::setmode(console::STD_INPUT_HANDLE, input);
+            let _ = console::setmode(console::STD_ERROR_HANDLE, output);
+        }))
+    }
+
+    fn local(&self) -> bool {
+        true
+    }
+
+    fn size(&self) -> (usize, usize) {
+        use console::*;
+        // SAFETY: CONSOLE_SCREEN_BUFFER_INFO is a plain structure, valid when zeroed
+        let mut info: CONSOLE_SCREEN_BUFFER_INFO = unsafe { std::mem::zeroed() };
+        // SAFETY: GetConsoleScreenBufferInfo only writes in the provided structure
+        match unsafe { GetConsoleScreenBufferInfo(GetStdHandle(STD_ERROR_HANDLE), &mut info) } {
+            0 => (80, 24),
+            _ => {
+                let window = info.srWindow;
+                let cols = (window.Right - window.Left + 1) as usize;
+                let rows = (window.Bottom - window.Top + 1) as usize;
+                (cols, rows)
+            }
+        }
+    }
+}
+
+/**
+ * Destination of the interactive output of the Cli: prompt, command line, completions and
+ * messages of the Printers.
+ */
+pub enum OutputTarget {
+    /** The standard error, the messages printed on the standard output excepted. */
+    Stderr,
+    /** The standard output, including the messages printed on the standard error. */
+    Stdout,
+    /**
+     * A writer, driven by a spawned task. The newlines are written as is: the terminal line
+     * discipline is expected to translate them.
+     */
+    Writer(Box<dyn AsyncWrite + Send + Unpin>),
+}
+
+/** Destination of the text written by the Cli and the Printers */
+#[derive(Clone, Default)]
+enum Sink {
+    /** Standard error, or standard output for the messages printed on it */
+    #[default]
+    Stdio,
+    /** Standard output, for all the text */
+    Stdout,
+    /** Text sent to the task writing to an AsyncWrite */
+    Channel(UnboundedSender<Vec<u8>>),
+    /** Text kept in memory, see [`crate::MockTerminal`] */
+    Buffer(Arc<Mutex<Vec<u8>>>),
+}
+
+impl Sink {
+    /** Write bytes on the standard error, or on the standard output if stderr is false */
+    fn send(&self, stderr: bool, bytes: &[u8]) {
+        match self {
+            Self::Stdio if stderr => {
+                let mut stderr = std::io::stderr().lock();
+                let _ = stderr.write_all(bytes);
+            }
+            Self::Stdio | Self::Stdout => {
+                let mut stdout = std::io::stdout().lock();
+                let _ = stdout.write_all(bytes);
+                let _ = stdout.flush();
+            }
+            Self::Channel(tx) => {
+                let _ = tx.send(bytes.to_vec());
+            }
+            Self::Buffer(buffer) => {
+                let mut buffer = buffer.lock().unwrap_or_else(|err| err.into_inner());
+                buffer.extend_from_slice(bytes);
+            }
+        }
+    }
+}
+
+/**
+ * Output of the Cli and the Printers, which can collect the text written to the terminal
+ * and write it at once (e.g. all the escape sequences updating the command line after a
+ * keystroke), to reduce the number of writes and the flicker on slow links.
+ */
+#[derive(Clone, Default)]
+pub(crate) struct Output {
+    sink: Sink,
+    batch: Arc<Mutex<Option<Vec<u8>>>>,
+    recorder: SharedRecorder,
+}
+
+impl Output {
+    /** Write to the provided target */
+    pub fn target(target: OutputTarget) -> Self {
+        match target {
+            OutputTarget::Stderr => Self::default(),
+            OutputTarget::Stdout => Self {
+                sink: Sink::Stdout,
+                ..Self::default()
+            },
+            OutputTarget::Writer(writer) => Self::spawn(writer, false),
+        }
+    }
+
+    /** Keep the text in memory */
+    pub fn buffer(buffer: Arc<Mutex<Vec<u8>>>) -> Self {
+        Self {
+            sink: Sink::Buffer(buffer),
+            ..Self::default()
+        }
+    }
+
+    /**
+     * Write to an AsyncWrite from a spawned task.
+     *
+     * If crlf is true, newlines are written as CR LF, as a terminal line discipline would do.
+     */
+    pub fn spawn(mut writer: impl AsyncWrite + Send + Unpin + 'static, crlf: bool) -> Self {
+        let (tx, mut rx) = unbounded_channel::<Vec<u8>>();
+        tokio::spawn(async move {
+            while let Some(text) = rx.recv().await {
+                let mut bytes = Vec::with_capacity(text.len());
+                for byte in text {
+                    if crlf && byte == b'\n' {
+                        bytes.push(b'\r');
+                    }
+                    bytes.push(byte);
+                }
+                if writer.write_all(&bytes).await.is_err() || writer.flush().await.is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            sink: Sink::Channel(tx),
+            ..Self::default()
+        }
+    }
+
+    /**
+     * Write text on the standard error, or on the standard output if stderr is false.
+     *
+     * The text written on the standard error is collected while a batch is pending. The
+     * batch is written before any text on the standard output, to keep them in order.
+     */
+    pub fn write(&self, stderr: bool, args: Arguments<'_>) {
+        if stderr {
+            let mut batch = self.batch.lock().unwrap_or_else(|err| err.into_inner());
+            if let Some(batch) = batch.as_mut() {
+                let _ = batch.write_fmt(args);
+                return;
+            }
+        } else {
+            self.flush();
+        }
+        self.send(stderr, args.to_string().as_bytes());
+    }
+
+    /** Write bytes to the sink, and record them if a recording is in progress */
+    fn send(&self, stderr: bool, bytes: &[u8]) {
+        self.record(RecordKind::Output, bytes);
+        self.sink.send(stderr, bytes);
+    }
+
+    /** Record bytes read or written, if a recording is in progress */
+    pub fn record(&self, kind: RecordKind, bytes: &[u8]) {
+        let mut recorder = self.recorder.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(kind, bytes);
+        }
+    }
+
+    /**
+     * Hold the next recorded events in memory, until they are released. The text collected
+     * by the pending batch is recorded first.
+     */
+    pub fn hold_record(&self) {
+        self.drain();
+        let mut recorder = self.recorder.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.hold();
+        }
+    }
+
+    /**
+     * Record the held events if `keep` is true, or leave them out of the recording. The
+     * text collected by the pending batch is written first, to be released along.
+     */
+    pub fn release_record(&self, keep: bool) {
+        self.drain();
+        let mut recorder = self.recorder.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.release(keep);
+        }
+    }
+
+    /** Replace the recorder of the session, and return the previous one */
+    pub fn setrecorder(&self, recorder: Option<Recorder>) -> Option<Recorder> {
+        let mut current = self.recorder.lock().unwrap_or_else(|err| err.into_inner());
+        std::mem::replace(&mut current, recorder)
+    }
+
+    /** Collect the text written on the standard error, until the returned Batch is dropped */
+    pub fn batch(&self) -> Batch {
+        let mut batch = self.batch.lock().unwrap_or_else(|err| err.into_inner());
+        batch.get_or_insert_with(Vec::new);
+        Batch {
+            output: self.clone(),
+        }
+    }
+
+    /** Write the text collected by the pending batch, if any, and keep collecting */
+    fn drain(&self) {
+        let batch = self
+            .batch
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .as_mut()
+            .map(std::mem::take);
+        if let Some(bytes) = batch.filter(|bytes| !bytes.is_empty()) {
+            self.send(true, &bytes);
+        }
+    }
+
+    /** Write the text collected by the pending batch, if any, and stop collecting */
+    fn flush(&self) {
+        let batch = self
+            .batch
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+        if let Some(bytes) = batch.filter(|bytes| !bytes.is_empty()) {
+            self.send(true, &bytes);
+        }
+    }
+}
+
+/** Batch of text written at once when dropped, see [`Output::batch`] */
+pub(crate) struct Batch {
+    output: Output,
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        self.output.flush();
+    }
+}
@@ -0,0 +1,367 @@
+//! Splitting of the command line in arguments.
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::Chars;
+
+/**
+ * Split a command line in arguments.
+ *
+ * A Tokenizer can be installed with [`crate::Cli::settokenizer`] to replace the default
+ * syntax ([`DefaultTokenizer`]). It is used to report the arguments of the commands, to
+ * find the word to auto-complete and to quote the inserted completions.
+ *
+ * Only [`Tokenizer::tokenize`] is required: the other methods default to the default
+ * syntax, and should be implemented by a tokenizer with another quoting syntax.
+ */
+pub trait Tokenizer {
+    /**
+     * Split a command line in arguments, and tell if it ends inside a string: the command
+     * line is then incomplete.
+     *
+     * When variables are provided, the references to variables should be replaced by their
+     * values (see [`crate::Cli::setvariables`]).
+     *
+     * The arguments of an empty line may be an empty list: the Cli then reports a single
+     * empty argument, as the command name.
+     */
+    fn tokenize(
+        &self,
+        line: &str,
+        variables: Option<&BTreeMap<String, String>>,
+    ) -> (Vec<String>, bool);
+
+    /**
+     * Split the command line before the cursor, to complete its last argument: a line ending
+     * with a separator ends with an empty argument, the word being started.
+     *
+     * By default, the line is split by [`Tokenizer::tokenize`].
+     */
+    fn tokenize_partial(&self, line: &str) -> (Vec<String>, bool) {
+        self.tokenize(line, None)
+    }
+
+    /**
+     * Quote a word inserted at the end of `line` (e.g. a completion), so it is read back as
+     * is: inside the string the line ends with, if any. When the word is `completed`, the
+     * string is closed.
+     */
+    fn quote(&self, line: &str, word: &str, completed: bool) -> String {
+        quote_default(self, line, word, completed, false)
+    }
+
+    /** Return the range of the word under the cursor, the word being completed */
+    fn word_range(&self, line: &str, cursor: usize) -> Range<usize> {
+        word_range(line, cursor, &[' ', '\n'], &['"'])
+    }
+}
+
+/** Quote a word with the default syntax, see [`Tokenizer::quote`] */
+fn quote_default<T: Tokenizer + ?Sized>(
+    tokenizer: &T,
+    line: &str,
+    word: &str,
+    completed: bool,
+    comments: bool,
+) -> String {
+    let (args, is_string) = tokenizer.tokenize_partial(line);
+    let word_start = !is_string && args.last().is_none_or(|arg| arg.is_empty());
+    let mut quoted = String::with_capacity(word.len());
+    for (i, c) in word.chars().enumerate() {
+        let special = match c {
+            '"' | '\\' => true,
+            ' ' => !is_string,
+            '#' => comments && word_start && i == 0,
+            _ => false,
+        };
+        if special {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    if is_string && completed {
+        quoted.push('"');
+    }
+    quoted
+}
+
+/**
+ * Return the range of the word under the cursor, the words being delimited by the separators
+ * outside of the quotes. A backslash escapes the next character, except inside single quotes.
+ */
+fn word_range(line: &str, cursor: usize, separators: &[char], quotes: &[char]) -> Range<usize> {
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some(quote_char), c) if c == quote_char => quote = None,
+            (None, c) if quotes.contains(&c) => quote = Some(c),
+            (None, c) if separators.contains(&c) => {
+                if i >= cursor {
+                    return start..i;
+                }
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    start..line.len()
+}
+
+/**
+ * The default syntax of the command line.
+ *
+ * Arguments are separated by a space, so consecutive spaces delimit empty arguments. Spaces
+ * are kept inside double quotes, and a backslash escapes the next character. If comments
+ * are enabled, a word starting with '#' ends the line. References to variables are written
+ * `$name` or `${name}`, and `$?` is the status of the last command.
+ */
+#[derive(Default)]
+pub struct DefaultTokenizer {
+    /** Ignore the end of the line from a word starting with '#' */
+    pub comments: bool,
+}
+
+impl Tokenizer for DefaultTokenizer {
+    fn tokenize(
+        &self,
+        line: &str,
+        variables: Option<&BTreeMap<String, String>>,
+    ) -> (Vec<String>, bool) {
+        let mut args = Vec::<String>::new();
+        let mut arg = String::new();
+        let mut is_string = false;
+        let mut is_escaped = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if is_escaped {
+                arg.push(c);
+                is_escaped = false;
+                continue;
+            }
+            match c {
+                '\\' => {
+                    is_escaped = true;
+                }
+                '"' => {
+                    is_string = !is_string;
+                }
+                '#' if self.comments && !is_string && arg.is_empty() => {
+                    // Comment: ignore the rest of the line
+                    break;
+                }
+                ' ' | '\n' => {
+                    match is_string {
+                        true => {
+                            arg.push(c);
+                        }
+                        false => {
+                            args.push(arg.clone());
+                            arg.clear();
+                        }
+                    };
+                }
+                '$' if variables.is_some() => {
+                    expand(&mut chars, variables, &mut arg);
+                }
+                _ => {
+                    arg.push(c);
+                }
+            }
+        }
+        args.push(arg);
+        (args, is_string)
+    }
+
+    fn quote(&self, line: &str, word: &str, completed: bool) -> String {
+        quote_default(self, line, word, completed, self.comments)
+    }
+}
+
+/**
+ * A syntax following the word splitting of POSIX shells.
+ *
+ * Arguments are separated by spaces, tabulations or newlines, and repeated separators do
+ * not delimit empty arguments: an empty argument is written `""` or `''`. Nothing is
+ * interpreted inside single quotes. Inside double quotes, a backslash only escapes `$`,
+ * `"` and `\`. Outside quotes, a backslash escapes the next character. References to
+ * variables (`$name` or `${name}`) are not replaced inside single quotes.
+ *
+ * A line ending with a separator does not end with an empty argument, except when the word
+ * being started is completed.
+ *
+ * ```no_run
+ * # fn example() -> eyre::Result<()> {
+ * let mut cli = tokiocli::Cli::new()?;
+ * cli.settokenizer(tokiocli::ShellTokenizer::default());
+ * # Ok(())
+ * # }
+ * ```
+ */
+#[derive(Default)]
+pub struct ShellTokenizer {
+    /** Ignore the end of the line from a word starting with '#' */
+    pub comments: bool,
+}
+
+impl Tokenizer for ShellTokenizer {
+    fn tokenize(
+        &self,
+        line: &str,
+        variables: Option<&BTreeMap<String, String>>,
+    ) -> (Vec<String>, bool) {
+        let (args, quote) = self.split(line, variables, false);
+        (args, quote.is_some())
+    }
+
+    fn tokenize_partial(&self, line: &str) -> (Vec<String>, bool) {
+        let (args, quote) = self.split(line, None, true);
+        (args, quote.is_some())
+    }
+
+    fn quote(&self, line: &str, word: &str, completed: bool) -> String {
+        let (args, quote) = self.split(line, None, true);
+        let word_start = quote.is_none() && args.last().is_none_or(|arg| arg.is_empty());
+        let mut quoted = String::with_capacity(word.len());
+        for (i, c) in word.chars().enumerate() {
+            match quote {
+                // A single quote can not be escaped inside single quotes: close and reopen
+                Some('\'') if c == '\'' => quoted += "'\\''",
+                Some('\'') => quoted.push(c),
+                Some(_) => {
+                    if matches!(c, '$' | '"' | '\\') {
+                        quoted.push('\\');
+                    }
+                    quoted.push(c);
+                }
+                None => {
+                    let special = match c {
+                        ' ' | '\t' | '\n' | '\'' | '"' | '\\' | '$' => true,
+                        '#' => self.comments && word_start && i == 0,
+                        _ => false,
+                    };
+                    if special {
+                        quoted.push('\\');
+                    }
+                    quoted.push(c);
+                }
+            }
+        }
+        if let Some(quote) = quote.filter(|_| completed) {
+            quoted.push(quote);
+        }
+        quoted
+    }
+
+    fn word_range(&self, line: &str, cursor: usize) -> Range<usize> {
+        word_range(line, cursor, &[' ', '\t', '\n'], &['\'', '"'])
+    }
+}
+
+impl ShellTokenizer {
+    /**
+     * Split a command line in arguments, and return the quote the line ends inside, if any.
+     * With `partial`, a line ending with a separator ends with an empty argument.
+     */
+    fn split(
+        &self,
+        line: &str,
+        variables: Option<&BTreeMap<String, String>>,
+        partial: bool,
+    ) -> (Vec<String>, Option<char>) {
+        let mut args = Vec::<String>::new();
+        let mut arg = String::new();
+        // The current word was started, even if it is still empty (e.g. "")
+        let mut started = false;
+        let mut quote = None;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some('\''), '\'') => {
+                    quote = None;
+                }
+                (Some('\''), _) => {
+                    arg.push(c);
+                }
+                (Some('"'), '"') => {
+                    quote = None;
+                }
+                (Some(_), '\\') => {
+                    let escaped = chars.next_if(|c| matches!(c, '$' | '"' | '\\'));
+                    arg.push(escaped.unwrap_or(c));
+                }
+                (None, '\\') => {
+                    if let Some(c) = chars.next() {
+                        arg.push(c);
+                    }
+                    started = true;
+                }
+                (None, '\'' | '"') => {
+                    quote = Some(c);
+                    started = true;
+                }
+                (None, ' ' | '\t' | '\n') => {
+                    if started {
+                        args.push(std::mem::take(&mut arg));
+                        started = false;
+                    }
+                }
+                (None, '#') if self.comments && !started => {
+                    // Comment: ignore the rest of the line
+                    break;
+                }
+                (_, '$') if variables.is_some() => {
+                    expand(&mut chars, variables, &mut arg);
+                    started = true;
+                }
+                _ => {
+                    arg.push(c);
+                    started = true;
+                }
+            }
+        }
+        if started || args.is_empty() || (partial && line.ends_with([' ', '\t', '\n'])) {
+            args.push(arg);
+        }
+        (args, quote)
+    }
+}
+
+/** Replace the reference to a variable following a '$' by its value */
+fn expand(
+    chars: &mut Peekable<Chars>,
+    variables: Option<&BTreeMap<String, String>>,
+    arg: &mut String,
+) {
+    let braced = chars.next_if_eq(&'{').is_some();
+    let mut name = String::new();
+    if let Some(c) = chars.next_if_eq(&'?') {
+        // Status of the last command
+        name.push(c);
+    }
+    while let Some(c) = chars.next_if(|c| name != "?" && is_varchar(*c)) {
+        name.push(c);
+    }
+    if braced && chars.next_if_eq(&'}').is_none() {
+        // Not a variable reference: keep it as is
+        arg.push_str("${");
+        arg.push_str(&name);
+    } else if name.is_empty() && !braced {
+        arg.push('$');
+    } else if let Some(value) = variables.and_then(|vars| vars.get(&name)) {
+        arg.push_str(value);
+    }
+}
+
+/** Tell if a character can be part of a variable name */
+pub(crate) fn is_varchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
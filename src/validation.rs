@@ -0,0 +1,33 @@
+//! Validation of the command line before its submission.
+
+/** Result of the validation of a command line */
+pub enum Validation {
+    /** The command line can be submitted. */
+    Valid,
+    /** The command line is not complete yet (e.g. unterminated string). */
+    Incomplete,
+    /** The command line is invalid and should be fixed by the user (Error message). */
+    Invalid(String),
+}
+
+/**
+ * Validate the command line when the user presses Enter.
+ *
+ * A Validator can be installed with [`crate::Cli::setvalidator`]. Incomplete command lines are
+ * handled according to the [`IncompletePolicy`] of the Cli, and invalid command lines are never
+ * submitted.
+ */
+pub trait Validator {
+    /** Validate the command line (possibly spanning multiple lines in continuation mode). */
+    fn validate(&self, line: &str) -> Validation;
+}
+
+/** Policy applied when Enter is pressed on an incomplete command line */
+pub enum IncompletePolicy {
+    /** Submit the command line anyway. */
+    Submit,
+    /** Continue the command line on a new line, with the continuation prompt. */
+    Continue,
+    /** Display a warning and let the user fix the command line. */
+    Warn,
+}
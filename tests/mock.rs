@@ -0,0 +1,263 @@
+//! Rendering and editing of the Cli, driven by a MockTerminal.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokiocli::{
+    Action, Cli, Completer, FileCompleter, KeyEvent, MockTerminal, ShellTokenizer, Tokenizer,
+};
+
+/** Feed keys to the Cli, and return the command submitted (Line, Arguments) */
+async fn submit(cli: &mut Cli, terminal: &MockTerminal, keys: &[u8]) -> (String, Vec<String>) {
+    terminal.feed(keys);
+    match cli.getaction().await.unwrap() {
+        Action::Command { line, args } => (line, args),
+        _ => panic!("command expected"),
+    }
+}
+
+/** Return a path in the temporary directory, unique to the test */
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("tokiocli-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[tokio::test]
+async fn render_typed_line() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    cli.setprompt("> ");
+    let (line, args) = submit(&mut cli, &terminal, b"hi\r").await;
+    assert_eq!(line, "hi");
+    assert_eq!(args, ["hi"]);
+    assert_eq!(terminal.output(), "> hi\n\x1b[2K");
+}
+
+#[tokio::test]
+async fn type_multibyte_characters() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    let (line, _) = submit(&mut cli, &terminal, "é€😀\r".as_bytes()).await;
+    assert_eq!(line, "é€😀");
+    // Inserted literally after Ctrl+V
+    let (line, _) = submit(&mut cli, &terminal, "a\x16éb\r".as_bytes()).await;
+    assert_eq!(line, "aéb");
+}
+
+#[tokio::test]
+async fn edit_multibyte_line() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    // Left twice, Backspace and x
+    let keys = "caffé\x1b[D\x1b[D\x7Fx\r";
+    let (line, _) = submit(&mut cli, &terminal, keys.as_bytes()).await;
+    assert_eq!(line, "caxfé");
+    // Ctrl+A, Right and Ctrl+T: the first two characters are swapped
+    let keys = "éa\x01\x1b[C\x14\r";
+    let (line, _) = submit(&mut cli, &terminal, keys.as_bytes()).await;
+    assert_eq!(line, "aé");
+}
+
+/** A tokenizer returning no argument for an empty line */
+struct Whitespace;
+
+impl Tokenizer for Whitespace {
+    fn tokenize(
+        &self,
+        line: &str,
+        _variables: Option<&BTreeMap<String, String>>,
+    ) -> (Vec<String>, bool) {
+        (line.split_whitespace().map(String::from).collect(), false)
+    }
+}
+
+#[tokio::test]
+async fn empty_tokenizer_result() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    cli.settokenizer(Whitespace);
+    let (line, args) = submit(&mut cli, &terminal, b"\r").await;
+    assert_eq!(line, "");
+    assert_eq!(args, [""]);
+    let (_, args) = submit(&mut cli, &terminal, b"  ls   -l \r").await;
+    assert_eq!(args, ["ls", "-l"]);
+}
+
+/** A completer recording the arguments of the last completion */
+#[derive(Clone, Default)]
+struct Recorded(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+impl Completer for Recorded {
+    fn completions(&self, args: &[String]) -> Vec<String> {
+        *self.0.lock().unwrap() = args.to_vec();
+        vec!["it's here".into()]
+    }
+}
+
+#[tokio::test]
+async fn shell_tokenizer_trailing_space() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    let completer = Recorded::default();
+    cli.settokenizer(ShellTokenizer::default());
+    cli.setcompleter(completer.clone());
+
+    // Submitted: no trailing empty argument
+    let (_, args) = submit(&mut cli, &terminal, b"ls  -l  \r").await;
+    assert_eq!(args, ["ls", "-l"]);
+
+    // Completed: the word being started is empty
+    let (line, args) = submit(&mut cli, &terminal, b"cat \t\r").await;
+    assert_eq!(*completer.0.lock().unwrap(), ["cat", ""]);
+    assert_eq!(line, "cat it\\'s\\ here");
+    assert_eq!(args, ["cat", "it's here"]);
+
+    // Completed inside single quotes: the quote is closed
+    let (line, args) = submit(&mut cli, &terminal, b"cat 'it\t\r").await;
+    assert_eq!(line, "cat 'it'\\''s here'");
+    assert_eq!(args, ["cat", "it's here"]);
+}
+
+#[tokio::test]
+async fn sensitive_lines_not_recorded() {
+    let path = temp_path("sensitive.rec");
+    {
+        let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+        cli.addsensitivepattern("login * --password *");
+        cli.record(&path).unwrap();
+        submit(&mut cli, &terminal, b"hello\r").await;
+        submit(&mut cli, &terminal, b"login bob --password s3cret\r").await;
+        submit(&mut cli, &terminal, b"bye\r").await;
+        // Typed but not submitted when the Cli is dropped
+        terminal.feed(b"login bob --password t0p");
+        let wait = std::time::Duration::from_millis(100);
+        let _ = tokio::time::timeout(wait, cli.getaction()).await;
+    }
+    let recording = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert!(recording.contains("hello"));
+    assert!(recording.contains("bye"));
+    assert!(!recording.contains("s3cret"));
+    assert!(!recording.contains("t0p"));
+}
+
+#[tokio::test]
+async fn history_file_roundtrip() {
+    let path = temp_path("history");
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    // A pasted multi-line command, and a command looking like a metadata line
+    submit(&mut cli, &terminal, b"\x1b[200~echo a\necho b\x1b[201~\r").await;
+    submit(&mut cli, &terminal, b"#1700000000\r").await;
+    cli.save_history(&path).await.unwrap();
+
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    cli.load_history(&path).await.unwrap();
+    let _ = std::fs::remove_file(&path);
+    let (line, _) = submit(&mut cli, &terminal, b"\x1b[A\r").await;
+    assert_eq!(line, "#1700000000");
+    let (line, _) = submit(&mut cli, &terminal, b"\x1b[A\x1b[A\x1b[A\r").await;
+    assert_eq!(line, "echo a\necho b");
+}
+
+#[tokio::test]
+async fn read_multibyte_password() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    terminal.feed("pässwörd€\x7F😀\r".as_bytes());
+    let password = cli.read_password("Password: ").await.unwrap();
+    assert_eq!(password.as_deref(), Some("pässwörd😀"));
+    assert!(!terminal.output().contains("ä"));
+}
+
+#[tokio::test]
+async fn capture_outlived_by_newer_capture() {
+    let (cli, _terminal) = MockTerminal::new(80, 24).unwrap();
+    let printer = cli.printer();
+    let finished = printer.capture(false);
+    let dropped = printer.capture(false);
+    let current = printer.capture(false);
+    printer.println("a");
+    assert_eq!(finished.finish(), "");
+    drop(dropped);
+    printer.println("b");
+    assert_eq!(current.finish(), "a\nb\n");
+}
+
+#[tokio::test]
+async fn run_typed_while_command_runs() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    let executed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    // "ab" is typed while "slow" runs, and completed once it is done
+    terminal.feed(b"slow\rfast\ra");
+    let sender = cli.input_sender();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let _ = sender.send(b"b\rquit\r".to_vec());
+    });
+    let recorded = executed.clone();
+    cli.run(move |args| {
+        let recorded = recorded.clone();
+        async move {
+            if args[0] == "slow" {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+            recorded.lock().unwrap().push(args.join(" "));
+            Ok(args[0] != "quit")
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(*executed.lock().unwrap(), ["slow", "fast", "ab", "quit"]);
+}
+
+#[tokio::test]
+async fn complete_multibyte_file_names() {
+    let dir = temp_path("files");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir(&dir).unwrap();
+    std::fs::write(dir.join("éa"), "").unwrap();
+    std::fs::write(dir.join("éb"), "").unwrap();
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    cli.setcompleter(FileCompleter::new());
+    let keys = format!("cat {}/\t\r", dir.display());
+    let (line, _) = submit(&mut cli, &terminal, keys.as_bytes()).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(line, format!("cat {}/é", dir.display()));
+}
+
+#[tokio::test]
+async fn comment_line_in_history() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    cli.setcomments(true);
+    let (line, args) = submit(&mut cli, &terminal, b"# a note\r").await;
+    assert_eq!(line, "# a note");
+    assert_eq!(args, [""]);
+    submit(&mut cli, &terminal, b"   \r").await;
+    let (line, _) = submit(&mut cli, &terminal, b"\x1b[A\r").await;
+    assert_eq!(line, "# a note");
+}
+
+#[tokio::test]
+async fn report_lone_escape() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    terminal.feed(b"ab\x1b");
+    let wait = std::time::Duration::from_secs(1);
+    let action = tokio::time::timeout(wait, cli.getaction()).await;
+    assert!(matches!(action, Ok(Ok(Action::Key(KeyEvent::Escape)))));
+    // The next key is not merged with the ESC
+    let (line, _) = submit(&mut cli, &terminal, b"c\r").await;
+    assert_eq!(line, "abc");
+}
+
+#[tokio::test]
+async fn nested_context_scope() {
+    let (mut cli, terminal) = MockTerminal::new(80, 24).unwrap();
+    cli.sethistorymax(10);
+    submit(&mut cli, &terminal, b"configure\r").await;
+    cli.push_context("(config)> ", true);
+    cli.setcompleter(Recorded::default());
+    submit(&mut cli, &terminal, b"hostname\r").await;
+    // A setting changed in the context is kept by the parent context
+    cli.sethistorymax(1);
+    assert!(cli.pop_context());
+    submit(&mut cli, &terminal, b"show\r").await;
+    let (line, _) = submit(&mut cli, &terminal, b"\x1b[A\x1b[A\r").await;
+    assert_eq!(line, "show");
+    // Without the completer of the context, the completion is demanded to the application
+    terminal.feed(b"cat \t");
+    let action = cli.getaction().await.unwrap();
+    assert!(matches!(action, Action::AutoComplete(_)));
+}